@@ -244,6 +244,9 @@ pub fn derive_managed_state(tokens: TokenStream) -> TokenStream {
 /// //
 /// // The client module will have a module for each service, and inside each module
 /// // it will have a 'send' function for each message handler
+/// //
+/// // `StreamHandler`s are declared as `Message => stream (Item, Error)`, and they get a
+/// // 'stream' function instead
 /// make_registry! {
 ///     TestService: [
 ///         Ping => (Pong, NoopError),
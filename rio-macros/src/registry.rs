@@ -19,6 +19,8 @@ pub(crate) struct RegistryItemHandler {
     pub(crate) input: syn::Path,
     pub(crate) output: syn::Path,
     pub(crate) error: syn::Path,
+    /// Whether it is a `StreamHandler`, declared as `=> stream (item type, err type)`
+    pub(crate) stream: bool,
 }
 
 impl Parse for RegistryItemHandler {
@@ -27,6 +29,7 @@ impl Parse for RegistryItemHandler {
             input: Ident2::new("tmp", Span2::call_site()).into(),
             output: Ident2::new("tmp", Span2::call_site()).into(),
             error: Ident2::new("tmp", Span2::call_site()).into(),
+            stream: false,
         };
 
         let input_type = input.parse::<syn::Path>()?;
@@ -34,6 +37,15 @@ impl Parse for RegistryItemHandler {
 
         // Now find the ok and err types as in `=> (ok type, err type)`
         input.parse::<Token![=>]>()?;
+
+        // Stream handlers are marked as in `=> stream (item type, err type)`
+        if input.peek(syn::Ident) {
+            let marker = input.parse::<Ident2>()?;
+            if marker != "stream" {
+                return Err(syn::Error::new(marker.span(), "expected `stream` or `(`"));
+            }
+            self_.stream = true;
+        }
         let res_types;
         syn::parenthesized!(res_types in input);
         let mut res_types = res_types.parse_terminated(syn::Path::parse, Token![,])?;
@@ -103,9 +115,16 @@ impl Codegen for RegistryInput {
                 let input_ = &handlers_def.input;
                 let output_ = &handlers_def.output;
                 let error_ = &handlers_def.error;
-                let fragment = quote! {
-                    reg.add_handler::<super::#service_path, super::#input_>();
-                    assert_handler_type::<super::#service_path, super::#input_, super::#output_, super::#error_>();
+                let fragment = if handlers_def.stream {
+                    quote! {
+                        reg.add_stream_handler::<super::#service_path, super::#input_>();
+                        assert_stream_handler_type::<super::#service_path, super::#input_, super::#output_, super::#error_>();
+                    }
+                } else {
+                    quote! {
+                        reg.add_handler::<super::#service_path, super::#input_>();
+                        assert_handler_type::<super::#service_path, super::#input_, super::#output_, super::#error_>();
+                    }
                 };
                 server_code_fragments.push(fragment);
             }
@@ -153,6 +172,27 @@ impl Codegen for RegistryInput {
                     .ident
                     .to_string()
                     .to_snake_case();
+                if handlers_def.stream {
+                    let fn_name = format!("stream_{}", input_snake);
+                    let fn_name = Ident2::new(&fn_name, Span2::call_site());
+                    let fragment = quote! {
+                        pub async fn #fn_name<S>(
+                            client: &mut rio_rs::client::Client<S>,
+                            object_id: impl AsRef<str>,
+                            msg: &super::super::#input_,
+                        ) -> Result<
+                            rio_rs::client::RequestStream<super::super::#output_, super::super::#error_>,
+                            rio_rs::protocol::RequestError<super::super::#error_>
+                        >
+                        where S: rio_rs::cluster::storage::MembershipStorage + 'static,
+                        {
+                            client.send_stream(#service_name_str, object_id, msg).await
+                        }
+                    };
+                    module_fragment.push(fragment);
+                    continue;
+                }
+
                 let fn_name = format!("send_{}", input_snake);
                 let fn_name = Ident2::new(&fn_name, Span2::call_site());
 
@@ -194,6 +234,12 @@ impl Codegen for RegistryInput {
                     E: Send + Sync,
                 {}
 
+                #[allow(dead_code)]
+                fn assert_stream_handler_type<T, I, O, E>() where
+                    T: 'static + rio_rs::registry::StreamHandler<I, Item=O, Error=E> + Send + Sync,
+                    I: rio_rs::registry::Message + Send + Sync,
+                {}
+
                 #server_registry_fragment
             }
 
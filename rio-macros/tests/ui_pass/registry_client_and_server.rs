@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::StreamExt;
 use rio_rs::cluster::storage::http::HttpMembershipStorage;
 use rio_rs::prelude::*;
+use rio_rs::registry::{HandlerStream, StreamHandler};
 use serde::{Deserialize, Serialize};
 
 use rio_macros::make_registry;
@@ -57,6 +59,31 @@ impl Handler<Pong> for TestService {
     }
 }
 
+#[derive(TypeName, Message, Debug, Deserialize, Serialize)]
+pub struct CountTo {
+    pub limit: u32,
+}
+
+#[derive(TypeName, Message, Debug, Deserialize, Serialize)]
+pub struct Counted {
+    pub value: u32,
+}
+
+#[async_trait]
+impl StreamHandler<CountTo> for TestService {
+    type Item = Counted;
+    type Error = NoopError;
+
+    async fn handle_stream(
+        &mut self,
+        message: CountTo,
+        _app_data: Arc<AppData>,
+    ) -> Result<HandlerStream<Self::Item, Self::Error>, Self::Error> {
+        let items = (0..message.limit).map(|value| Ok(Counted { value }));
+        Ok(futures::stream::iter(items).boxed())
+    }
+}
+
 mod messages {
     use super::*;
 
@@ -94,6 +121,7 @@ make_registry! {
     TestService: [
         Ping => (Pong, NoopError),
         Pong => (Pong, NoopError),
+        CountTo => stream (Counted, NoopError),
     ],
     services::TestServicePingOnly: [
         messages::Ping2 => (messages::Ping2, NoopError),
@@ -123,6 +151,13 @@ async fn test_client() -> Result<(), Box<dyn std::error::Error>> {
     )
     .await?;
 
+    let _counter = client::test_service::stream_count_to(
+        &mut client,
+        "counter",
+        &CountTo { limit: 10 },
+    )
+    .await?;
+
     let _ping = client::test_service_ping_only::send_ping2(
         &mut client,
         "ping2",
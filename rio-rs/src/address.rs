@@ -0,0 +1,169 @@
+//! Addresses of the servers in the cluster
+//!
+//! An [Address] is a host, either an IP or a DNS name, and a port. It is written as
+//! `host:port`, with IPv6 hosts in brackets (`[::1]:5000`)
+
+use std::fmt::Display;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::errors::AddressError;
+
+/// Address of a server: the host (IP or DNS name) and the port it listens on
+///
+/// It is (de)serialized as its `host:port` text
+///
+/// # Example
+///
+/// ```
+/// # use rio_rs::address::Address;
+/// let address: Address = "[::1]:5000".parse().unwrap();
+/// assert_eq!(address.host(), "::1");
+/// assert_eq!(address.port(), 5000);
+/// assert_eq!(address.to_string(), "[::1]:5000");
+///
+/// let address: Address = "rio-0.rio.svc:5000".parse().unwrap();
+/// assert_eq!(address.host(), "rio-0.rio.svc");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    /// IPv6 hosts are kept without the brackets
+    host: String,
+    port: u16,
+}
+
+impl Address {
+    pub fn new(host: impl Into<String>, port: u16) -> Address {
+        let host = host.into();
+        let host = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+            Some(host) => host.to_string(),
+            None => host,
+        };
+        Address { host, port }
+    }
+
+    /// The IP or DNS name, IPv6 addresses without brackets
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        let (host, port) = address
+            .rsplit_once(':')
+            .ok_or_else(|| AddressError::MissingPort(address.to_string()))?;
+        let port = port
+            .parse()
+            .map_err(|_| AddressError::InvalidPort(address.to_string()))?;
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => {
+                let host = bracketed
+                    .strip_suffix(']')
+                    .ok_or_else(|| AddressError::InvalidHost(address.to_string()))?;
+                Ipv6Addr::from_str(host)
+                    .map_err(|_| AddressError::InvalidHost(address.to_string()))?;
+                host
+            }
+            // IPv6 addresses need brackets, otherwise the port is ambiguous
+            None if host.is_empty() || host.contains([':', '[', ']']) => {
+                return Err(AddressError::InvalidHost(address.to_string()));
+            }
+            None => host,
+        };
+        Ok(Address::new(host, port))
+    }
+}
+
+impl From<SocketAddr> for Address {
+    fn from(address: SocketAddr) -> Self {
+        Address::new(address.ip().to_string(), address.port())
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = String::deserialize(deserializer)?;
+        address.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let address: Address = "127.0.0.1:5000".parse().unwrap();
+        assert_eq!(address, Address::new("127.0.0.1", 5000));
+        let address: Address = "[2001:db8::1]:5000".parse().unwrap();
+        assert_eq!(address, Address::new("2001:db8::1", 5000));
+        let address: Address = "localhost:5000".parse().unwrap();
+        assert_eq!(address, Address::new("localhost", 5000));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(matches!(
+            "127.0.0.1".parse::<Address>(),
+            Err(AddressError::MissingPort(_))
+        ));
+        assert!(matches!(
+            "127.0.0.1:http".parse::<Address>(),
+            Err(AddressError::InvalidPort(_))
+        ));
+        assert!(matches!(
+            "2001:db8::1:5000".parse::<Address>(),
+            Err(AddressError::InvalidHost(_))
+        ));
+        assert!(matches!(
+            "[localhost]:5000".parse::<Address>(),
+            Err(AddressError::InvalidHost(_))
+        ));
+        assert!(matches!(
+            ":5000".parse::<Address>(),
+            Err(AddressError::InvalidHost(_))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Address::new("10.0.0.1", 80).to_string(), "10.0.0.1:80");
+        assert_eq!(Address::new("::1", 80).to_string(), "[::1]:80");
+        assert_eq!(Address::new("[::1]", 80).to_string(), "[::1]:80");
+        let socket_address: SocketAddr = "[::1]:80".parse().unwrap();
+        assert_eq!(Address::from(socket_address).to_string(), "[::1]:80");
+    }
+
+    #[test]
+    fn test_serde() {
+        let address = Address::new("::1", 80);
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, r#""[::1]:80""#);
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+    }
+}
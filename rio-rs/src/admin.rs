@@ -0,0 +1,399 @@
+//! Admin HTTP API to inspect and control a [Server](crate::server::Server)
+//!
+//! It is served on `http_admin_address` (see [Server::builder](crate::server::Server::builder)):
+//!
+//! - `GET /objects`: the ids of the active objects, by type
+//! - `GET /objects/{type}/{id}`: where an object is placed, and whether it is active here
+//! - `POST /objects/{type}/{id}/deactivate`: deactivates an object, wherever it lives in the
+//!   cluster (see [AdminCommands::ShutdownWithAck])
+//! - `POST /drain`: puts the server in draining mode (see [Server::drain](crate::server::Server::drain))
+//! - `DELETE /drain`: takes the server out of draining mode
+//! - `POST /shutdown`: shuts the server down gracefully (see [Server::shutdown](crate::server::Server::shutdown))
+//! - `GET /types`: the registered types, with their handlers
+//!
+//! <div class="warning">
+//! The API has no authentication, so it must only be reachable by the operators
+//! </div>
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    routing,
+};
+use serde::{Deserialize, Serialize};
+use tokio::net::ToSocketAddrs;
+use tokio::sync::{RwLock, oneshot};
+
+use crate::ObjectId;
+use crate::address::Address;
+use crate::object_placement::ObjectPlacement;
+use crate::protocol::ResponseError;
+use crate::protocol::error_code::{ErrorCode, ErrorDetails};
+use crate::registry::Registry;
+use crate::server::{AdminCommands, AdminSender, DrainHandle, ShutdownHandle};
+
+/// Where an object is placed, see `GET /objects/{type}/{id}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectPlacementInfo {
+    pub object_kind: String,
+    pub object_id: String,
+    /// Address of the server holding the object, if it is placed anywhere
+    pub placement: Option<Address>,
+    /// Whether the object is active in this server
+    pub active: bool,
+}
+
+/// A registered type and its handlers, see `GET /types`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeInfo {
+    pub object_kind: String,
+    /// Messages handled by [Handler](crate::registry::Handler)s
+    pub handlers: Vec<String>,
+    /// Messages handled by [StreamHandler](crate::registry::StreamHandler)s
+    pub stream_handlers: Vec<String>,
+}
+
+/// Body of the failed responses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminError {
+    pub code: ErrorCode,
+    pub error: String,
+}
+
+impl From<ResponseError> for AdminError {
+    fn from(err: ResponseError) -> Self {
+        AdminError {
+            code: err.code(),
+            error: err.to_string(),
+        }
+    }
+}
+
+type AdminResult<T> = Result<Json<T>, (StatusCode, Json<AdminError>)>;
+
+/// What the admin API needs from the server
+pub struct AdminApi<P: ObjectPlacement> {
+    pub(crate) registry: Arc<RwLock<Registry>>,
+    pub(crate) object_placement_provider: Arc<RwLock<P>>,
+    pub(crate) admin_sender: AdminSender,
+    pub(crate) shutdown: ShutdownHandle,
+    pub(crate) draining: DrainHandle,
+}
+
+impl<P: ObjectPlacement> Clone for AdminApi<P> {
+    fn clone(&self) -> Self {
+        AdminApi {
+            registry: self.registry.clone(),
+            object_placement_provider: self.object_placement_provider.clone(),
+            admin_sender: self.admin_sender.clone(),
+            shutdown: self.shutdown.clone(),
+            draining: self.draining.clone(),
+        }
+    }
+}
+
+/// Serves the admin API, see the [module docs](self)
+pub async fn serve<P: ObjectPlacement + 'static>(
+    bind: impl ToSocketAddrs,
+    api: AdminApi<P>,
+) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    axum::serve(listener, router(api)).await
+}
+
+fn router<P: ObjectPlacement + 'static>(api: AdminApi<P>) -> Router {
+    Router::new()
+        .route("/objects", routing::get(list_objects::<P>))
+        .route("/objects/{type}/{id}", routing::get(object_placement::<P>))
+        .route(
+            "/objects/{type}/{id}/deactivate",
+            routing::post(deactivate_object::<P>),
+        )
+        .route("/drain", routing::post(drain::<P>).delete(resume::<P>))
+        .route("/shutdown", routing::post(shutdown::<P>))
+        .route("/types", routing::get(list_types::<P>))
+        .with_state(api)
+}
+
+async fn list_objects<P: ObjectPlacement>(
+    State(api): State<AdminApi<P>>,
+) -> Json<BTreeMap<String, Vec<String>>> {
+    let mut objects: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (object_kind, object_id) in api.registry.read().await.objects() {
+        objects.entry(object_kind).or_default().push(object_id);
+    }
+    objects.values_mut().for_each(|ids| ids.sort());
+    Json(objects)
+}
+
+async fn object_placement<P: ObjectPlacement>(
+    Path((object_kind, object_id)): Path<(String, String)>,
+    State(api): State<AdminApi<P>>,
+) -> AdminResult<ObjectPlacementInfo> {
+    let placement = api
+        .object_placement_provider
+        .read()
+        .await
+        .lookup(&ObjectId::new(&object_kind, &object_id))
+        .await
+        .map_err(|err| internal_error(err.into()))?;
+    let active = api
+        .registry
+        .read()
+        .await
+        .has(&object_kind, &object_id)
+        .await;
+    Ok(Json(ObjectPlacementInfo {
+        object_kind,
+        object_id,
+        placement,
+        active,
+    }))
+}
+
+async fn deactivate_object<P: ObjectPlacement>(
+    Path((object_kind, object_id)): Path<(String, String)>,
+    State(api): State<AdminApi<P>>,
+) -> Result<StatusCode, (StatusCode, Json<AdminError>)> {
+    let (ack, ack_receiver) = oneshot::channel();
+    let unavailable = || {
+        let details = ErrorDetails::new("The server isn't taking admin commands");
+        internal_error(ResponseError::Unavailable(details.into()))
+    };
+    api.admin_sender
+        .send(AdminCommands::ShutdownWithAck(object_kind, object_id, ack))
+        .map_err(|_| unavailable())?;
+    ack_receiver
+        .await
+        .map_err(|_| unavailable())?
+        .map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn drain<P: ObjectPlacement>(State(api): State<AdminApi<P>>) -> StatusCode {
+    api.draining.drain();
+    StatusCode::ACCEPTED
+}
+
+async fn resume<P: ObjectPlacement>(State(api): State<AdminApi<P>>) -> StatusCode {
+    api.draining.resume();
+    StatusCode::ACCEPTED
+}
+
+async fn shutdown<P: ObjectPlacement>(State(api): State<AdminApi<P>>) -> StatusCode {
+    api.shutdown.shutdown();
+    StatusCode::ACCEPTED
+}
+
+async fn list_types<P: ObjectPlacement>(State(api): State<AdminApi<P>>) -> Json<Vec<TypeInfo>> {
+    let registry = api.registry.read().await;
+    let mut types: BTreeMap<String, TypeInfo> = registry
+        .types()
+        .into_iter()
+        .map(|object_kind| {
+            let info = TypeInfo {
+                object_kind: object_kind.clone(),
+                handlers: vec![],
+                stream_handlers: vec![],
+            };
+            (object_kind, info)
+        })
+        .collect();
+    for (object_kind, message_type) in registry.handlers() {
+        if let Some(info) = types.get_mut(&object_kind) {
+            info.handlers.push(message_type);
+        }
+    }
+    for (object_kind, message_type) in registry.stream_handlers() {
+        if let Some(info) = types.get_mut(&object_kind) {
+            info.stream_handlers.push(message_type);
+        }
+    }
+    let mut types: Vec<_> = types.into_values().collect();
+    for info in types.iter_mut() {
+        info.handlers.sort();
+        info.stream_handlers.sort();
+    }
+    Json(types)
+}
+
+fn internal_error(err: ResponseError) -> (StatusCode, Json<AdminError>) {
+    let status = if err.is_retryable() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    (status, Json(err.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use axum::body::{Body, to_bytes};
+    use axum::http::Request;
+    use serde::de::DeserializeOwned;
+    use tokio::sync::mpsc;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::object_placement::ObjectPlacementItem;
+    use crate::object_placement::local::LocalObjectPlacement;
+    use crate::server::AdminReceiver;
+    use crate::{LifecycleMessage, registry::Handler};
+    use rio_macros::{TypeName, WithId};
+
+    #[derive(Default, WithId, TypeName)]
+    #[rio_path = "crate"]
+    struct MockService {
+        id: String,
+    }
+
+    #[async_trait::async_trait]
+    impl Handler<LifecycleMessage> for MockService {
+        type Returns = ();
+        type Error = crate::errors::ServiceObjectLifeCycleError;
+        async fn handle(
+            &mut self,
+            _: LifecycleMessage,
+            _: Arc<crate::app_data::AppData>,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    async fn api() -> (AdminApi<LocalObjectPlacement>, AdminReceiver) {
+        let mut registry = Registry::new();
+        registry.add_type::<MockService>();
+        registry.add_handler::<MockService, LifecycleMessage>();
+        registry
+            .add(
+                "1".to_string(),
+                MockService {
+                    id: "1".to_string(),
+                },
+            )
+            .await;
+        let object_placement_provider = LocalObjectPlacement::default();
+        object_placement_provider
+            .update(ObjectPlacementItem::new(
+                ObjectId::new("MockService", "1"),
+                Some(Address::new("0.0.0.0", 5000)),
+            ))
+            .await
+            .unwrap();
+        let (admin_sender, admin_receiver) = mpsc::unbounded_channel();
+        let api = AdminApi {
+            registry: Arc::new(RwLock::new(registry)),
+            object_placement_provider: Arc::new(RwLock::new(object_placement_provider)),
+            admin_sender,
+            shutdown: ShutdownHandle::default(),
+            draining: DrainHandle::default(),
+        };
+        (api, admin_receiver)
+    }
+
+    async fn request<T: DeserializeOwned>(
+        api: AdminApi<LocalObjectPlacement>,
+        method: &str,
+        uri: &str,
+    ) -> (StatusCode, Option<T>) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = router(api).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    #[tokio::test]
+    async fn test_list_objects_and_types() {
+        let (api, _admin_receiver) = api().await;
+        let (status, objects) = request(api.clone(), "GET", "/objects").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            objects,
+            Some(BTreeMap::from([(
+                "MockService".to_string(),
+                vec!["1".to_string()]
+            )]))
+        );
+
+        let (status, types) = request(api, "GET", "/types").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            types,
+            Some(vec![TypeInfo {
+                object_kind: "MockService".to_string(),
+                handlers: vec!["LifecycleMessage".to_string()],
+                stream_handlers: vec![],
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_object_placement() {
+        let (api, _admin_receiver) = api().await;
+        let (status, info) = request(api, "GET", "/objects/MockService/1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            info,
+            Some(ObjectPlacementInfo {
+                object_kind: "MockService".to_string(),
+                object_id: "1".to_string(),
+                placement: Some(Address::new("0.0.0.0", 5000)),
+                active: true,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deactivate_object() {
+        let (api, mut admin_receiver) = api().await;
+        let server = tokio::spawn(async move {
+            match admin_receiver.recv().await {
+                Some(AdminCommands::ShutdownWithAck(object_kind, object_id, ack)) => {
+                    assert_eq!(
+                        (object_kind.as_str(), object_id.as_str()),
+                        ("MockService", "2")
+                    );
+                    ack.send(Err(ResponseError::ObjectNotFound)).unwrap();
+                }
+                command => panic!("Unexpected command {:?}", command),
+            }
+        });
+        let (status, error): (_, Option<AdminError>) =
+            request(api, "POST", "/objects/MockService/2/deactivate").await;
+        server.await.unwrap();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error.unwrap().code, ErrorCode::ObjectNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_drain() {
+        let (api, _admin_receiver) = api().await;
+        let draining = api.draining.clone();
+        let (status, _): (_, Option<()>) = request(api.clone(), "POST", "/drain").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(draining.is_draining());
+        assert!(!api.shutdown.is_shutdown());
+
+        let (status, _): (_, Option<()>) = request(api, "DELETE", "/drain").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(!draining.is_draining());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let (api, _admin_receiver) = api().await;
+        let shutdown = api.shutdown.clone();
+        let (status, _): (_, Option<()>) = request(api, "POST", "/shutdown").await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(shutdown.is_shutdown());
+    }
+}
@@ -3,8 +3,15 @@
 //! There is a pooled client. The client also does proper placement lookups and controls its own
 //! caching strategy
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::cluster::storage::MembershipStorage;
+use crate::codec::{Codec, default_codec};
 use crate::errors::ClientBuilderError;
+use crate::protocol::compression::CompressionConfig;
+#[cfg(feature = "tls")]
+use crate::tls::TlsClientConfig;
 
 use super::Client;
 use super::DEFAULT_TIMEOUT_MILLIS;
@@ -15,6 +22,11 @@ use super::pool::ClientConnectionManager;
 pub struct ClientBuilder<S> {
     members_storage: Option<S>,
     timeout_millis: u64,
+    codec: Arc<dyn Codec>,
+    compression: CompressionConfig,
+    request_timeout: Option<Duration>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsClientConfig>,
 }
 
 impl<S: MembershipStorage> Default for ClientBuilder<S> {
@@ -22,6 +34,11 @@ impl<S: MembershipStorage> Default for ClientBuilder<S> {
         ClientBuilder {
             members_storage: None,
             timeout_millis: 0,
+            codec: default_codec(),
+            compression: CompressionConfig::default(),
+            request_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 }
@@ -44,6 +61,35 @@ impl<S: MembershipStorage + 'static> ClientBuilder<S> {
         self
     }
 
+    /// Codec for the messages and their responses
+    ///
+    /// It needs to match the one set on the servers' [Registry](crate::registry::Registry)
+    pub fn codec(mut self, codec: Arc<dyn Codec>) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Compression algorithms offered to the servers, and the size from which requests
+    /// are compressed
+    pub fn compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Deadline for the requests that don't set their own
+    /// ([RequestEnvelope::deadline](crate::protocol::RequestEnvelope::deadline))
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// Connects to the servers over TLS
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsClientConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
     pub fn build(self) -> Result<Client<S>, ClientBuilderError> {
         let members_storage = self
             .members_storage
@@ -52,6 +98,13 @@ impl<S: MembershipStorage + 'static> ClientBuilder<S> {
 
         let mut client = Client::new(members_storage);
         client.timeout_millis = self.timeout_millis;
+        client.codec = self.codec;
+        client.compression = self.compression;
+        client.request_timeout = self.request_timeout;
+        #[cfg(feature = "tls")]
+        {
+            client.tls = self.tls;
+        }
         Ok(client)
     }
 
@@ -64,6 +117,13 @@ impl<S: MembershipStorage + 'static> ClientBuilder<S> {
             .ok_or(ClientBuilderError::NoMembershipStorage)?;
         let mut connection_manager = ClientConnectionManager::new(members_storage);
         connection_manager.timeout_millis = self.timeout_millis;
+        connection_manager.codec = self.codec.clone();
+        connection_manager.compression = self.compression.clone();
+        connection_manager.request_timeout = self.request_timeout;
+        #[cfg(feature = "tls")]
+        {
+            connection_manager.tls = self.tls.clone();
+        }
         Ok(connection_manager)
     }
 }
@@ -0,0 +1,969 @@
+//! Multiplexed connection to a single server
+//!
+//! Many requests share the same TCP stream. Each request is tagged with a
+//! [RequestEnvelope::request_id], and a background task reads the responses
+//! and routes them back to their callers, regardless of the order in which they arrive
+//!
+//! Subscriptions share the stream as well. Each one is tagged with a
+//! [SubscriptionRequest::subscription_id], and the messages published to it are routed
+//! to its [Subscription]
+//!
+//! Streamed responses are routed the same way, by their request id, to their
+//! [ResponseStream]
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::{Context, Poll};
+
+use dashmap::DashMap;
+use futures::{SinkExt, Stream as FuturesStream, StreamExt};
+use log::error;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::bytes::Bytes;
+
+use crate::protocol::compression::{CompressionConfig, FrameEncoder};
+use crate::protocol::frame::{self, FrameKind};
+use crate::protocol::handshake::{DEFAULT_CODEC, Handshake, HandshakeAccepted, HandshakeResponse};
+use crate::protocol::pubsub::{
+    SubscriptionClosed, SubscriptionEnvelope, SubscriptionRequest, SubscriptionResponse,
+    Unsubscribe,
+};
+use crate::protocol::streaming::{
+    INITIAL_CREDITS, StreamCancel, StreamCredit, StreamItem, StreamItemEnvelope,
+};
+use crate::protocol::{
+    BatchRequestEnvelope, BatchResponseEnvelope, ClientError, DeactivateRequest, RequestEnvelope,
+    ResponseEnvelope, ResponseError,
+};
+#[cfg(feature = "tls")]
+use crate::tls::TlsClientConfig;
+use crate::transport::{FramedStream, Stream};
+
+/// Number of messages a [Subscription] holds before it is closed for falling behind
+pub const SUBSCRIPTION_BUFFER: usize = 1024;
+
+type PendingRequests = DashMap<u64, oneshot::Sender<ResponseEnvelope>>;
+type PendingBatches = DashMap<u64, oneshot::Sender<BatchResponseEnvelope>>;
+type ActiveSubscriptions = DashMap<u64, mpsc::Sender<SubscriptionResponse>>;
+type ActiveStreams = DashMap<u64, mpsc::UnboundedSender<StreamItem>>;
+
+/// Aborts the connection's background tasks once the last clone of the
+/// [Connection] is dropped
+#[derive(Debug)]
+struct ConnectionTasks {
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+impl Drop for ConnectionTasks {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.writer.abort();
+    }
+}
+
+/// How new connections are set up
+#[derive(Clone, Debug)]
+pub struct ConnectionOptions {
+    /// Name of the codec the client uses
+    pub codec: &'static str,
+
+    /// Compression the client supports, and the threshold for compressing its requests
+    pub compression: CompressionConfig,
+
+    /// Connects to the servers over TLS if set
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsClientConfig>,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            codec: DEFAULT_CODEC,
+            compression: CompressionConfig::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// [Handshake] announcing these options to the server
+    pub fn handshake(&self) -> Handshake {
+        Handshake {
+            codec: self.codec.to_string(),
+            compression: self.compression.algorithm_names(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Cheaply clonable handle to a multiplexed connection
+#[derive(Clone, Debug)]
+pub struct Connection {
+    next_request_id: Arc<AtomicU64>,
+    encoder: FrameEncoder,
+    pending: Arc<PendingRequests>,
+    pending_batches: Arc<PendingBatches>,
+    subscriptions: Arc<ActiveSubscriptions>,
+    streams: Arc<ActiveStreams>,
+    outgoing: mpsc::UnboundedSender<Bytes>,
+    closed: Arc<AtomicBool>,
+    _tasks: Arc<ConnectionTasks>,
+}
+
+impl Connection {
+    /// Connects to `address`, runs the [Handshake] and starts the reader and writer tasks
+    pub async fn connect(
+        address: &str,
+        options: &ConnectionOptions,
+    ) -> Result<Connection, ClientError> {
+        let (framed, accepted) = connect(address, options).await?;
+        let encoder = options
+            .compression
+            .encoder(accepted.compression.as_deref())
+            .with_version(accepted.protocol_version);
+        Ok(Connection::new(framed, encoder))
+    }
+
+    /// Wraps an existing framed stream, which has already done the [Handshake]
+    ///
+    /// `encoder` builds the request frames, compressing them as negotiated on the handshake
+    pub fn new(framed: FramedStream, encoder: FrameEncoder) -> Connection {
+        let (mut sink, mut stream) = framed.split();
+        let pending: Arc<PendingRequests> = Arc::default();
+        let pending_batches: Arc<PendingBatches> = Arc::default();
+        let subscriptions: Arc<ActiveSubscriptions> = Arc::default();
+        let streams: Arc<ActiveStreams> = Arc::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        let (outgoing, mut outgoing_receiver) = mpsc::unbounded_channel::<Bytes>();
+
+        let writer_closed = closed.clone();
+        let writer = tokio::spawn(async move {
+            while let Some(frame) = outgoing_receiver.recv().await {
+                if let Err(err) = sink.send(frame).await {
+                    error!("Error writing to the connection: {}", err);
+                    break;
+                }
+            }
+            writer_closed.store(true, Ordering::SeqCst);
+        });
+
+        let reader_closed = closed.clone();
+        let reader_pending = pending.clone();
+        let reader_pending_batches = pending_batches.clone();
+        let reader_subscriptions = subscriptions.clone();
+        let reader_streams = streams.clone();
+        let reader_outgoing = outgoing.clone();
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(frame)) = stream.next().await {
+                let payload = match frame::decode(&frame) {
+                    Ok((header, payload)) if header.kind == FrameKind::Response => payload,
+                    Ok((header, payload)) if header.kind == FrameKind::SubscriptionResponse => {
+                        let lagged = route_subscription_response(&reader_subscriptions, &payload);
+                        // The server stops sending the messages of a closed subscription
+                        if let Some(subscription_id) = lagged {
+                            let ser_unsubscribe =
+                                bincode::serialize(&Unsubscribe { subscription_id })
+                                    .expect("Unsubscribe serialization should be infalible");
+                            reader_outgoing
+                                .send(encoder.encode(FrameKind::Unsubscribe, &ser_unsubscribe))
+                                .ok();
+                        }
+                        continue;
+                    }
+                    Ok((header, payload)) if header.kind == FrameKind::BatchResponse => {
+                        match bincode::deserialize::<BatchResponseEnvelope>(&payload) {
+                            Ok(batch) => {
+                                if let Some((_, sender)) =
+                                    reader_pending_batches.remove(&batch.request_id)
+                                {
+                                    sender.send(batch).ok();
+                                }
+                            }
+                            Err(err) => error!("Error deserializing batch response: {}", err),
+                        }
+                        continue;
+                    }
+                    Ok((header, payload)) if header.kind == FrameKind::StreamItem => {
+                        route_stream_item(&reader_streams, &payload);
+                        continue;
+                    }
+                    Ok((header, payload)) if header.kind == FrameKind::SubscriptionClosed => {
+                        match bincode::deserialize::<SubscriptionClosed>(&payload) {
+                            Ok(closed) => {
+                                reader_subscriptions.remove(&closed.subscription_id);
+                            }
+                            Err(err) => error!("Error deserializing subscription end: {}", err),
+                        }
+                        continue;
+                    }
+                    Ok((header, payload)) if header.kind == FrameKind::Error => {
+                        // Not tagged with a request, so there is no telling which one
+                        // failed. The connection is closed, failing all of them
+                        let server_error: Result<ResponseError, _> = bincode::deserialize(&payload);
+                        error!("Server could not process a frame: {:?}", server_error);
+                        break;
+                    }
+                    Ok((header, _)) => {
+                        error!("Unexpected frame kind {:?}", header.kind);
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("Invalid frame: {}", err);
+                        continue;
+                    }
+                };
+                let response: ResponseEnvelope = match bincode::deserialize(&payload) {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!("Error deserializing response: {}", err);
+                        continue;
+                    }
+                };
+                if let Some((_, sender)) = reader_pending.remove(&response.request_id) {
+                    // The caller might have given up on this request
+                    sender.send(response).ok();
+                }
+            }
+            reader_closed.store(true, Ordering::SeqCst);
+            // Dropping the senders signals a disconnection to all the requests
+            // that are still waiting for a response
+            reader_pending.clear();
+            reader_pending_batches.clear();
+            reader_subscriptions.clear();
+            reader_streams.clear();
+        });
+
+        Connection {
+            next_request_id: Arc::new(AtomicU64::new(1)),
+            encoder,
+            pending,
+            pending_batches,
+            subscriptions,
+            streams,
+            outgoing,
+            closed,
+            _tasks: Arc::new(ConnectionTasks { reader, writer }),
+        }
+    }
+
+    /// Whether the underlying stream is no longer usable
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Sends a request and waits for its response
+    ///
+    /// The request id is overwritten by one that is unique for this connection
+    ///
+    /// It fails with [ClientError::Connectivity] if the connection is closed before the
+    /// request is sent, and with [ClientError::Disconnect] if it is lost while waiting for
+    /// the response, as the server might have handled the request by then
+    pub async fn request(&self, request: RequestEnvelope) -> Result<ResponseEnvelope, ClientError> {
+        self.request_with_kind(FrameKind::Request, request).await
+    }
+
+    /// Asks the server to deactivate an object, and waits for its acknowledgement
+    ///
+    /// The server answers with [ResponseError::Redirect](crate::protocol::ResponseError)
+    /// if the object lives in another server. See [DeactivateRequest] for `hooks_ran`
+    pub async fn deactivate(
+        &self,
+        handler_type: impl ToString,
+        handler_id: impl ToString,
+        hooks_ran: bool,
+    ) -> Result<ResponseEnvelope, ClientError> {
+        let payload = bincode::serialize(&DeactivateRequest { hooks_ran })
+            .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+        let request = RequestEnvelope::new(
+            handler_type.to_string(),
+            handler_id.to_string(),
+            String::new(),
+            payload,
+        );
+        self.request_with_kind(FrameKind::Deactivate, request).await
+    }
+
+    /// Sends a request on a frame of `kind`, and waits for its [ResponseEnvelope]
+    async fn request_with_kind(
+        &self,
+        kind: FrameKind,
+        mut request: RequestEnvelope,
+    ) -> Result<ResponseEnvelope, ClientError> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        request.request_id = request_id;
+        let ser_request = bincode::serialize(&request)
+            .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending.insert(request_id, sender);
+        let _guard = PendingGuard {
+            pending: &self.pending,
+            request_id,
+        };
+
+        // Checked after registering the request, as the reader flags the connection
+        // as closed before it drops the pending requests
+        if self.is_closed() {
+            return Err(ClientError::Connectivity);
+        }
+
+        self.outgoing
+            .send(self.encoder.encode(kind, &ser_request))
+            .map_err(|_| ClientError::Connectivity)?;
+        receiver.await.map_err(|_| ClientError::Disconnect)
+    }
+
+    /// Sends many requests on a single frame and waits for all their responses
+    ///
+    /// The responses are in the same order as `requests`
+    pub async fn request_batch(
+        &self,
+        requests: Vec<RequestEnvelope>,
+    ) -> Result<Vec<ResponseEnvelope>, ClientError> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let count = requests.len();
+        let batch = BatchRequestEnvelope {
+            request_id,
+            // The requests are told apart by their position on the batch
+            requests: requests
+                .into_iter()
+                .enumerate()
+                .map(|(i, mut request)| {
+                    request.request_id = i as u64;
+                    request
+                })
+                .collect(),
+        };
+        let ser_batch = bincode::serialize(&batch)
+            .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+
+        let (sender, receiver) = oneshot::channel();
+        self.pending_batches.insert(request_id, sender);
+        let _guard = PendingGuard {
+            pending: &self.pending_batches,
+            request_id,
+        };
+
+        // Checked after registering the batch, as the reader flags the connection
+        // as closed before it drops the pending batches
+        if self.is_closed() {
+            return Err(ClientError::Connectivity);
+        }
+
+        self.outgoing
+            .send(self.encoder.encode(FrameKind::BatchRequest, &ser_batch))
+            .map_err(|_| ClientError::Connectivity)?;
+        let batch = receiver.await.map_err(|_| ClientError::Disconnect)?;
+        if let Some(error) = batch.error {
+            let responses = (0..count)
+                .map(|i| ResponseEnvelope::err(error.clone()).with_request_id(i as u64))
+                .collect();
+            return Ok(responses);
+        }
+        if batch.responses.len() != count {
+            let message = format!(
+                "expected {} responses, got {}",
+                count,
+                batch.responses.len()
+            );
+            return Err(ClientError::Unknown(message));
+        }
+        Ok(batch.responses)
+    }
+
+    /// Sends a request that is answered with a stream of items
+    ///
+    /// The request id is overwritten by one that is unique for this connection. The
+    /// server stops streaming once the returned [ResponseStream] is dropped
+    pub fn request_stream(
+        &self,
+        mut request: RequestEnvelope,
+    ) -> Result<ResponseStream, ClientError> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        request.request_id = request_id;
+        let ser_request = bincode::serialize(&request)
+            .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.streams.insert(request_id, sender);
+        let response_stream = ResponseStream {
+            request_id,
+            receiver,
+            connection: self.clone(),
+            consumed: 0,
+            complete: false,
+        };
+
+        // Checked after registering the stream, as the reader flags the connection
+        // as closed before it drops the streams
+        if self.is_closed() {
+            return Err(ClientError::Connectivity);
+        }
+
+        self.outgoing
+            .send(self.encoder.encode(FrameKind::StreamRequest, &ser_request))
+            .map_err(|_| ClientError::Connectivity)?;
+        Ok(response_stream)
+    }
+
+    /// Sends `message` to the server, unless the connection is closed
+    fn send_control<T: serde::Serialize>(&self, kind: FrameKind, message: &T) {
+        if self.is_closed() {
+            return;
+        }
+        let ser_message =
+            bincode::serialize(message).expect("Control message serialization should be infalible");
+        self.outgoing
+            .send(self.encoder.encode(kind, &ser_message))
+            .ok();
+    }
+
+    /// Subscribes to the messages published by `handler_type`/`handler_id`
+    ///
+    /// The subscription ends once the returned [Subscription] is dropped
+    pub fn subscribe(
+        &self,
+        handler_type: impl Into<String>,
+        handler_id: impl Into<String>,
+    ) -> Result<Subscription, ClientError> {
+        let subscription_id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let request = SubscriptionRequest {
+            subscription_id,
+            handler_type: handler_type.into(),
+            handler_id: handler_id.into(),
+        };
+        let ser_request = bincode::serialize(&request)
+            .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel(SUBSCRIPTION_BUFFER);
+        self.subscriptions.insert(subscription_id, sender);
+        let subscription = Subscription {
+            subscription_id,
+            receiver,
+            connection: self.clone(),
+        };
+
+        // Checked after registering the subscription, as the reader flags the connection
+        // as closed before it drops the subscriptions
+        if self.is_closed() {
+            return Err(ClientError::Connectivity);
+        }
+
+        self.outgoing
+            .send(
+                self.encoder
+                    .encode(FrameKind::SubscriptionRequest, &ser_request),
+            )
+            .map_err(|_| ClientError::Connectivity)?;
+        Ok(subscription)
+    }
+
+    /// Tells the server to stop sending messages for `subscription_id`
+    fn unsubscribe(&self, subscription_id: u64) {
+        self.subscriptions.remove(&subscription_id);
+        self.send_control(FrameKind::Unsubscribe, &Unsubscribe { subscription_id });
+    }
+}
+
+/// Forwards a published message to the [Subscription] it belongs to
+///
+/// The subscriptions that fall [SUBSCRIPTION_BUFFER] messages behind are closed, their last
+/// message being [ResponseError::Overloaded]. It returns the id of the subscription it
+/// closed that way, if any
+fn route_subscription_response(subscriptions: &ActiveSubscriptions, payload: &[u8]) -> Option<u64> {
+    let envelope: SubscriptionEnvelope = match bincode::deserialize(payload) {
+        Ok(envelope) => envelope,
+        Err(err) => {
+            error!("Error deserializing subscription message: {}", err);
+            return None;
+        }
+    };
+    let subscription_id = envelope.subscription_id;
+    let (delivered, lagged) = match subscriptions.get(&subscription_id) {
+        Some(sender) if sender.capacity() <= 1 => {
+            let overloaded = SubscriptionResponse::err(ResponseError::Overloaded);
+            sender.try_send(overloaded).ok();
+            (false, true)
+        }
+        Some(sender) => (sender.try_send(envelope.response).is_ok(), false),
+        None => return None,
+    };
+    if !delivered {
+        subscriptions.remove(&subscription_id);
+    }
+    lagged.then_some(subscription_id)
+}
+
+/// Forwards an item of a streamed response to the [ResponseStream] it belongs to
+fn route_stream_item(streams: &ActiveStreams, payload: &[u8]) {
+    let envelope: StreamItemEnvelope = match bincode::deserialize(payload) {
+        Ok(envelope) => envelope,
+        Err(err) => {
+            error!("Error deserializing stream item: {}", err);
+            return;
+        }
+    };
+    let request_id = envelope.request_id;
+    let is_end = envelope.item == StreamItem::End;
+    let delivered = streams
+        .get(&request_id)
+        .map(|sender| sender.send(envelope.item).is_ok());
+    if is_end || delivered == Some(false) {
+        streams.remove(&request_id);
+    }
+}
+
+/// Items of a response sent with [Connection::request_stream]
+///
+/// Every item consumed lets the server send another one, so the server is never more
+/// than [INITIAL_CREDITS] items ahead of the consumer.
+///
+/// The stream ends once the server sends all the items or the connection is lost, which
+/// can be told apart with [ResponseStream::is_complete]. Dropping it before that cancels
+/// the response, while the connection stays open for other requests
+#[derive(Debug)]
+pub struct ResponseStream {
+    request_id: u64,
+    receiver: mpsc::UnboundedReceiver<StreamItem>,
+    connection: Connection,
+    /// Items consumed since the last credit was granted
+    consumed: u32,
+    complete: bool,
+}
+
+impl ResponseStream {
+    /// Whether the server sent all the items
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+impl FuturesStream for ResponseStream {
+    type Item = Result<Vec<u8>, ResponseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = match self.receiver.poll_recv(cx) {
+            Poll::Ready(Some(item)) => item,
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        let item = match item {
+            StreamItem::Item(item) => Ok(item),
+            StreamItem::Error(err) => Err(err),
+            StreamItem::End => {
+                self.complete = true;
+                return Poll::Ready(None);
+            }
+        };
+
+        // Credits are granted in batches, to avoid a frame per item
+        self.consumed += 1;
+        if self.consumed >= INITIAL_CREDITS / 2 {
+            let credit = StreamCredit {
+                request_id: self.request_id,
+                credits: self.consumed,
+            };
+            self.connection
+                .send_control(FrameKind::StreamCredit, &credit);
+            self.consumed = 0;
+        }
+        Poll::Ready(Some(item))
+    }
+}
+
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        self.connection.streams.remove(&self.request_id);
+        if !self.complete {
+            let cancel = StreamCancel {
+                request_id: self.request_id,
+            };
+            self.connection
+                .send_control(FrameKind::StreamCancel, &cancel);
+        }
+    }
+}
+
+/// Messages published to a subscription made with [Connection::subscribe]
+///
+/// The stream ends once the server closes the subscription or the connection is lost.
+/// Dropping it unsubscribes, while the connection stays open for other requests
+///
+/// The messages that arrive while it isn't being read are buffered, up to
+/// [SUBSCRIPTION_BUFFER] of them. Past that it yields [ResponseError::Overloaded] and ends
+#[derive(Debug)]
+pub struct Subscription {
+    subscription_id: u64,
+    receiver: mpsc::Receiver<SubscriptionResponse>,
+    connection: Connection,
+}
+
+impl Subscription {
+    /// Id of the subscription on its connection
+    pub fn id(&self) -> u64 {
+        self.subscription_id
+    }
+}
+
+impl FuturesStream for Subscription {
+    type Item = SubscriptionResponse;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.connection.unsubscribe(self.subscription_id);
+    }
+}
+
+/// Opens a stream to `address` and runs the [Handshake] on it
+///
+/// The TLS handshake, if configured, happens before the protocol handshake. It fails with
+/// [ClientError::Connectivity] if the server can't be reached
+pub(crate) async fn connect(
+    address: &str,
+    options: &ConnectionOptions,
+) -> Result<(FramedStream, HandshakeAccepted), ClientError> {
+    let stream = TcpStream::connect(address)
+        .await
+        .map_err(|_| ClientError::Connectivity)?;
+    #[cfg(feature = "tls")]
+    let stream = match &options.tls {
+        Some(tls) => tls
+            .connect(address, stream)
+            .await
+            .map_err(|e| ClientError::Tls(e.to_string()))?,
+        None => Stream::from(stream),
+    };
+    #[cfg(not(feature = "tls"))]
+    let stream = Stream::from(stream);
+    let mut framed = stream.framed();
+    let accepted = handshake(&mut framed, &options.handshake())
+        .await
+        .map_err(|err| match err {
+            ClientError::Disconnect | ClientError::IoError(_) => ClientError::Connectivity,
+            err => err,
+        })?;
+    Ok((framed, accepted))
+}
+
+/// Sends the [Handshake] to the server and waits for it to be accepted
+///
+/// It must be the first thing sent on a new stream
+pub(crate) async fn handshake(
+    framed: &mut FramedStream,
+    handshake: &Handshake,
+) -> Result<HandshakeAccepted, ClientError> {
+    let ser_handshake =
+        bincode::serialize(handshake).map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+    framed
+        .send(frame::encode(FrameKind::Handshake, &ser_handshake))
+        .await?;
+
+    let response_frame = framed.next().await.ok_or(ClientError::Disconnect)??;
+    let incompatible = |message: String| ClientError::IncompatibleProtocol(message);
+    let (header, payload) =
+        frame::decode(&response_frame).map_err(|e| incompatible(e.to_string()))?;
+    if header.kind != FrameKind::HandshakeResponse {
+        let message = format!("expected a handshake response, got {:?}", header.kind);
+        return Err(incompatible(message));
+    }
+
+    let response: HandshakeResponse = bincode::deserialize(&payload)
+        .map_err(|e| ClientError::DeseralizationError(e.to_string()))?;
+    response.result.map_err(|err| match err {
+        ResponseError::IncompatibleProtocol(message) => incompatible(message),
+        err => incompatible(err.to_string()),
+    })
+}
+
+/// Removes the pending entry if the caller stops waiting before the response arrives
+struct PendingGuard<'a, T> {
+    pending: &'a DashMap<u64, T>,
+    request_id: u64,
+}
+
+impl<T> Drop for PendingGuard<'_, T> {
+    fn drop(&mut self) {
+        self.pending.remove(&self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    use super::*;
+
+    /// Echoes the requests back, answering them in reverse order once it gets `count` of them
+    async fn reverse_echo_server(count: usize) -> String {
+        reverse_echo_server_with_compression(count, CompressionConfig::default()).await
+    }
+
+    /// Same as [reverse_echo_server], but it negotiates compression with the client
+    ///
+    /// Once compression is negotiated, it expects every request to be compressed
+    async fn reverse_echo_server_with_compression(
+        count: usize,
+        compression: CompressionConfig,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut frames = Framed::new(stream, LengthDelimitedCodec::new());
+            let accepted = accept_handshake(&mut frames, &compression).await;
+            let encoder = compression.encoder(accepted.compression.as_deref());
+            let mut requests = vec![];
+            while let Some(Ok(request_frame)) = frames.next().await {
+                let (header, payload) = frame::decode(&request_frame).unwrap();
+                assert_eq!(
+                    header.compression.map(|c| c.name().to_string()),
+                    accepted.compression
+                );
+                let request: RequestEnvelope = bincode::deserialize(&payload).unwrap();
+                requests.push(request);
+                if requests.len() == count {
+                    break;
+                }
+            }
+            for request in requests.into_iter().rev() {
+                let response =
+                    ResponseEnvelope::new(request.payload).with_request_id(request.request_id);
+                let ser_response = bincode::serialize(&response).unwrap();
+                frames
+                    .send(encoder.encode(FrameKind::Response, &ser_response))
+                    .await
+                    .unwrap();
+            }
+        });
+        address
+    }
+
+    async fn accept_handshake(
+        frames: &mut Framed<TcpStream, LengthDelimitedCodec>,
+        compression: &CompressionConfig,
+    ) -> HandshakeAccepted {
+        let handshake_frame = frames.next().await.unwrap().unwrap();
+        let (_, payload) = frame::decode(&handshake_frame).unwrap();
+        let handshake: Handshake = bincode::deserialize(&payload).unwrap();
+        let response = HandshakeResponse {
+            result: handshake.negotiate(
+                frame::SUPPORTED_VERSIONS,
+                DEFAULT_CODEC,
+                &compression.algorithm_names(),
+            ),
+        };
+        let ser_response = bincode::serialize(&response).unwrap();
+        frames
+            .send(frame::encode(FrameKind::HandshakeResponse, &ser_response))
+            .await
+            .unwrap();
+        response.result.unwrap()
+    }
+
+    fn request(payload: u8) -> RequestEnvelope {
+        RequestEnvelope::new("T".into(), "1".into(), "M".into(), vec![payload])
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_responses() {
+        let address = reverse_echo_server(3).await;
+        let connection = Connection::connect(&address, &ConnectionOptions::default())
+            .await
+            .unwrap();
+
+        let (a, b, c) = tokio::time::timeout(
+            Duration::from_secs(3),
+            futures::future::join3(
+                connection.request(request(1)),
+                connection.request(request(2)),
+                connection.request(request(3)),
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(a.unwrap().body, Ok(vec![1]));
+        assert_eq!(b.unwrap().body, Ok(vec![2]));
+        assert_eq!(c.unwrap().body, Ok(vec![3]));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[tokio::test]
+    async fn test_compressed_requests() {
+        use crate::protocol::compression::Compression;
+
+        let compression = CompressionConfig::new(vec![Compression::Lz4]).with_threshold(0);
+        let address = reverse_echo_server_with_compression(1, compression.clone()).await;
+        let options = ConnectionOptions {
+            compression,
+            ..Default::default()
+        };
+        let connection = Connection::connect(&address, &options).await.unwrap();
+        assert_eq!(connection.encoder, options.compression.encoder(Some("lz4")));
+
+        let response = connection.request(request(1)).await.unwrap();
+        assert_eq!(response.body, Ok(vec![1]));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect() {
+        let address = reverse_echo_server(1).await;
+        let connection = Connection::connect(&address, &ConnectionOptions::default())
+            .await
+            .unwrap();
+        connection.request(request(1)).await.unwrap();
+
+        // The server closes the connection after responding
+        tokio::time::timeout(Duration::from_secs(3), async {
+            while !connection.is_closed() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // The request is never sent, so it is safe to retry
+        let response = connection.request(request(2)).await;
+        assert_eq!(response.unwrap_err(), ClientError::Connectivity);
+    }
+
+    #[tokio::test]
+    async fn test_untagged_server_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut frames = Framed::new(stream, LengthDelimitedCodec::new());
+            accept_handshake(&mut frames, &CompressionConfig::default()).await;
+            frames.next().await.unwrap().unwrap();
+            let error = ResponseError::InvalidFrame("garbage".to_string());
+            let ser_error = bincode::serialize(&error).unwrap();
+            frames
+                .send(frame::encode(FrameKind::Error, &ser_error))
+                .await
+                .unwrap();
+            // Keeps the connection open
+            frames.next().await;
+        });
+        let connection = Connection::connect(&address, &ConnectionOptions::default())
+            .await
+            .unwrap();
+
+        let response = tokio::time::timeout(Duration::from_secs(3), connection.request(request(1)))
+            .await
+            .unwrap();
+        assert_eq!(response.unwrap_err(), ClientError::Disconnect);
+        assert!(connection.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_whole_batch_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut frames = Framed::new(stream, LengthDelimitedCodec::new());
+            accept_handshake(&mut frames, &CompressionConfig::default()).await;
+            let request_frame = frames.next().await.unwrap().unwrap();
+            let (_, payload) = frame::decode(&request_frame).unwrap();
+            let request_id: u64 = bincode::deserialize(&payload).unwrap();
+            let batch = BatchResponseEnvelope {
+                request_id,
+                responses: vec![],
+                error: Some(ResponseError::InvalidFrame("garbage".to_string())),
+            };
+            let ser_batch = bincode::serialize(&batch).unwrap();
+            frames
+                .send(frame::encode(FrameKind::BatchResponse, &ser_batch))
+                .await
+                .unwrap();
+            frames.next().await;
+        });
+        let connection = Connection::connect(&address, &ConnectionOptions::default())
+            .await
+            .unwrap();
+
+        let responses = connection
+            .request_batch(vec![request(1), request(2)])
+            .await
+            .unwrap();
+        assert_eq!(responses.len(), 2);
+        for response in responses {
+            assert!(matches!(response.body, Err(ResponseError::InvalidFrame(_))));
+        }
+        // Unlike an untagged error, it doesn't close the connection
+        assert!(!connection.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscription() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (unsubscribed_sender, unsubscribed) = oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut frames = Framed::new(stream, LengthDelimitedCodec::new());
+            accept_handshake(&mut frames, &CompressionConfig::default()).await;
+            let request_frame = frames.next().await.unwrap().unwrap();
+            let (_, payload) = frame::decode(&request_frame).unwrap();
+            let request: SubscriptionRequest = bincode::deserialize(&payload).unwrap();
+            for i in 0..SUBSCRIPTION_BUFFER + 10 {
+                let envelope = SubscriptionEnvelope {
+                    subscription_id: request.subscription_id,
+                    response: SubscriptionResponse::new(i.to_be_bytes().to_vec()),
+                };
+                let ser_envelope = bincode::serialize(&envelope).unwrap();
+                frames
+                    .send(frame::encode(
+                        FrameKind::SubscriptionResponse,
+                        &ser_envelope,
+                    ))
+                    .await
+                    .unwrap();
+            }
+            let unsubscribe_frame = frames.next().await.unwrap().unwrap();
+            let (header, payload) = frame::decode(&unsubscribe_frame).unwrap();
+            assert_eq!(header.kind, FrameKind::Unsubscribe);
+            let unsubscribe: Unsubscribe = bincode::deserialize(&payload).unwrap();
+            unsubscribed_sender
+                .send(unsubscribe.subscription_id)
+                .unwrap();
+        });
+        let connection = Connection::connect(&address, &ConnectionOptions::default())
+            .await
+            .unwrap();
+        let subscription = connection.subscribe("T", "1").unwrap();
+        let subscription_id = subscription.id();
+
+        // Nothing is read until the server sent everything
+        let unsubscribed = tokio::time::timeout(Duration::from_secs(3), unsubscribed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(unsubscribed, subscription_id);
+
+        let responses: Vec<_> = subscription.collect().await;
+        assert_eq!(responses.len(), SUBSCRIPTION_BUFFER);
+        assert!(
+            responses[..SUBSCRIPTION_BUFFER - 1]
+                .iter()
+                .all(|response| response.body.is_ok())
+        );
+        assert_eq!(
+            responses[SUBSCRIPTION_BUFFER - 1].body,
+            Err(ResponseError::Overloaded)
+        );
+    }
+}
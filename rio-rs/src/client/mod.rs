@@ -7,40 +7,54 @@
 //! caching strategy
 
 mod builder;
+mod connection;
 mod pool;
 pub mod tower_services;
 
 use async_stream::stream;
 pub use builder::ClientBuilder;
+pub use connection::{Connection, ConnectionOptions, ResponseStream, Subscription};
 pub use pool::ClientConnectionManager;
 pub use pool::Pool;
 pub use pool::PooledConnection;
 
 use dashmap::DashMap;
-use dashmap::mapref::one::RefMut;
-use futures::SinkExt;
+use futures::stream::BoxStream;
 use futures::{Stream, StreamExt};
 use lru::LruCache;
 use rand::rng;
 use rand::seq::IndexedRandom;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::num::NonZeroUsize;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::net::TcpStream;
-use tokio::time::timeout;
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio::time::{Instant, timeout};
 use tower::Service as TowerService;
 
 use crate::cluster::storage::MembershipStorage;
-use crate::protocol::pubsub::{SubscriptionRequest, SubscriptionResponse};
-use crate::protocol::{ClientError, RequestEnvelope, RequestError, ResponseError};
+use crate::codec::{Codec, default_codec};
+use crate::protocol::compression::CompressionConfig;
+use crate::protocol::error_code::ErrorDetails;
+use crate::protocol::metadata::Metadata;
+use crate::protocol::{ClientError, NoopError, RequestEnvelope, RequestError, ResponseError};
 use crate::registry::IdentifiableType;
+#[cfg(feature = "tls")]
+use crate::tls::TlsClientConfig;
 
 pub const DEFAULT_TIMEOUT_MILLIS: u64 = 500;
 
+/// Number of times [Client::send_batch_requests], [Client::send_stream] and
+/// [Client::deactivate] follow redirects before giving up on a request
+const MAX_REDIRECTS: usize = 3;
+
+/// How long the client uses its list of active servers before fetching it again, so it
+/// finds out about the servers that joined the cluster or started draining
+const ACTIVE_SERVERS_TTL: Duration = Duration::from_secs(5);
+
 /// Client struct to interact with a cluster for requests and subscriptions
 ///
 /// S is the MembershipStorage implementation to fetch the cluster members
@@ -54,23 +68,42 @@ pub struct Client<S> {
     /// List of servers that are accepting requests
     active_servers: HashSet<String>,
 
-    /// Timestamp of the last time self.active_servers was refresh
-    ts_active_servers_refresh: u64,
+    /// Active servers that don't take new objects, see [DrainHandle](crate::server::DrainHandle)
+    draining_servers: HashSet<String>,
+
+    /// Last time self.active_servers was refreshed, `None` forces a refresh
+    active_servers_refreshed_at: Option<Instant>,
 
-    /// Framed TCP Stream mapped by ip+port address
-    streams: Arc<DashMap<String, Framed<TcpStream, LengthDelimitedCodec>>>,
+    /// Multiplexed connections mapped by ip+port address
+    streams: Arc<DashMap<String, Connection>>,
 
     /// Cached location of objects previously used by  the client
     placement: Arc<RwLock<LruCache<(String, String), String>>>,
+
+    /// Serialization for the messages and their responses, it needs to be the same
+    /// one the servers use
+    codec: Arc<dyn Codec>,
+
+    /// Compression offered to the servers
+    compression: CompressionConfig,
+
+    /// Deadline for the requests that don't set their own
+    request_timeout: Option<Duration>,
+
+    /// Connects to the servers over TLS if set
+    #[cfg(feature = "tls")]
+    tls: Option<TlsClientConfig>,
 }
 
 /// Stream of subscription messages. This is used for pub/sub.
+///
+/// Dropping it unsubscribes, without closing the connection it shares with other requests
 pub struct SubscriptionStream<T>
 where
     T: DeserializeOwned,
 {
-    // TODO make this over an impl G instead of Framed
-    pub tcp_stream: Framed<TcpStream, LengthDelimitedCodec>,
+    pub subscription: Subscription,
+    codec: Arc<dyn Codec>,
     _phantom: PhantomData<T>,
 }
 
@@ -78,9 +111,16 @@ impl<T> SubscriptionStream<T>
 where
     T: DeserializeOwned,
 {
-    pub fn new(tcp_stream: Framed<TcpStream, LengthDelimitedCodec>) -> Self {
+    /// Stream that deserializes the published messages with the [default_codec]
+    pub fn new(subscription: Subscription) -> Self {
+        Self::with_codec(subscription, default_codec())
+    }
+
+    /// Stream that deserializes the published messages with `codec`
+    pub fn with_codec(subscription: Subscription, codec: Arc<dyn Codec>) -> Self {
         SubscriptionStream {
-            tcp_stream,
+            subscription,
+            codec,
             _phantom: PhantomData {},
         }
     }
@@ -99,35 +139,28 @@ where
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
         let self_mut = self.get_mut();
-        self_mut.tcp_stream.poll_next_unpin(cx).map(|maybe_bytes| {
-            let bytes_result = maybe_bytes?;
-            let bytes_message = match bytes_result {
-                Ok(bytes_message) => bytes_message,
-                Err(err) => {
-                    return Some(Err(ResponseError::DeseralizationError(err.to_string())));
-                }
-            };
-
-            let sub_response: SubscriptionResponse = match bincode::deserialize(&bytes_message) {
-                Ok(sub_response) => sub_response,
-                Err(err) => return Some(Err(ResponseError::DeseralizationError(err.to_string()))),
-            };
-
-            let final_message = match sub_response.body {
-                Ok(v) => {
-                    let response: Result<T, _> = bincode::deserialize(&v)
-                        .map_err(|e| ResponseError::DeseralizationError(e.to_string()));
-                    response
-                }
-                Err(err) => Err(err),
-            };
-            Some(final_message)
-        })
+        self_mut
+            .subscription
+            .poll_next_unpin(cx)
+            .map(|maybe_response| {
+                let sub_response = maybe_response?;
+                let final_message = match sub_response.body {
+                    Ok(v) => self_mut
+                        .codec
+                        .deserialize(&v)
+                        .map_err(|e| ResponseError::DeseralizationError(e.to_string())),
+                    Err(err) => Err(err),
+                };
+                Some(final_message)
+            })
     }
 }
 
 type ClientResult<T> = Result<T, ClientError>;
 
+/// Items of a response streamed back by [Client::send_stream]
+pub type RequestStream<T, E> = BoxStream<'static, Result<T, RequestError<E>>>;
+
 impl<S> Client<S>
 where
     S: 'static + MembershipStorage,
@@ -140,9 +173,25 @@ where
             membership_storage: members_storage,
             timeout_millis: DEFAULT_TIMEOUT_MILLIS,
             active_servers: Default::default(),
-            ts_active_servers_refresh: 0,
+            draining_servers: Default::default(),
+            active_servers_refreshed_at: None,
             streams: Arc::default(),
             placement: Arc::new(RwLock::new(LruCache::new(lru_limit))),
+            codec: default_codec(),
+            compression: CompressionConfig::default(),
+            request_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Options for every new connection, announcing the client's codec and compression
+    fn connection_options(&self) -> ConnectionOptions {
+        ConnectionOptions {
+            codec: self.codec.name(),
+            compression: self.compression.clone(),
+            #[cfg(feature = "tls")]
+            tls: self.tls.clone(),
         }
     }
 
@@ -151,27 +200,37 @@ where
     /// Note that this is not an incremental operation. It will replace all the current active servers
     /// cached on the client
     async fn fetch_active_servers(&mut self) -> ClientResult<()> {
-        // if there are active servers and the refresh time stamp has changed
-        // We assume the cache is good
-        if !self.active_servers.is_empty() && self.ts_active_servers_refresh > 0 {
+        // If there are active servers and they were fetched recently, we assume the cache
+        // is good
+        let is_fresh = self
+            .active_servers_refreshed_at
+            .is_some_and(|refreshed_at| refreshed_at.elapsed() < ACTIVE_SERVERS_TTL);
+        if !self.active_servers.is_empty() && is_fresh {
             return Ok(());
         }
 
-        let active_servers: HashSet<String> = self
+        let active_members = self
             .membership_storage
             .active_members()
             .await
-            .map_err(|_| ClientError::RendevouzUnavailable)?
+            .map_err(|_| ClientError::RendevouzUnavailable)?;
+
+        self.active_servers = active_members
             .iter()
-            .map(|member| member.address())
+            .map(|member| member.address().to_string())
             .collect();
-
-        self.active_servers = active_servers;
-        self.ts_active_servers_refresh = 1;
+        self.draining_servers = active_members
+            .iter()
+            .filter(|member| member.draining())
+            .map(|member| member.address().to_string())
+            .collect();
+        self.active_servers_refreshed_at = Some(Instant::now());
         Ok(())
     }
 
-    async fn ensure_stream_exists(&mut self, address: &str) -> ClientResult<()> {
+    /// Checks whether `address` is one of the active servers, refreshing the list of active
+    /// servers if needed
+    async fn ensure_server_is_active(&mut self, address: &str) -> ClientResult<()> {
         self.fetch_active_servers().await?;
 
         // We start this method fetching the active servers, so if there are no active servers we
@@ -183,7 +242,7 @@ where
         // If we do have items but the asked address is not there, the active_servers might be
         // outdated and it will reset the refresh time and fetch it again
         if !self.active_servers.contains(address) {
-            self.ts_active_servers_refresh = 0;
+            self.active_servers_refreshed_at = None;
             self.fetch_active_servers().await?;
         }
 
@@ -192,43 +251,28 @@ where
         if !self.active_servers.contains(address) {
             return Err(ClientError::ServerNotAvailable(address.to_string()));
         }
-
-        // If there are no stream for the address, create a new one
-        // This is on a nested block so it controlls the guards in `self.stream`
-        if self.streams.get(address).is_none() {
-            let stream = TcpStream::connect(&address)
-                .await
-                .map_err(|_| ClientError::Disconnect)?;
-            let stream = Framed::new(stream, LengthDelimitedCodec::new());
-            self.streams.insert(address.to_string(), stream);
-        };
         Ok(())
     }
 
     /// Get an existing connection to server `address` or create a new one
     ///
+    /// The connection is shared by all the requests to the same server. If it has
+    /// been closed, a new one replaces it.
+    ///
     /// If the address is not one of the known online servers, it will fetch
     /// the list of active servers again
-    async fn server_stream(
-        &mut self,
-        address: &String,
-    ) -> ClientResult<RefMut<'_, String, Framed<TcpStream, LengthDelimitedCodec>>> {
-        self.ensure_stream_exists(address).await?;
-        self.streams
-            .get_mut(address)
-            .ok_or(ClientError::Connectivity)
-    }
+    async fn server_stream(&mut self, address: &String) -> ClientResult<Connection> {
+        self.ensure_server_is_active(address).await?;
 
-    /// Same as [Self::server_stream], but it pops from the stream cache
-    async fn pop_server_stream(
-        &mut self,
-        address: &String,
-    ) -> ClientResult<Framed<TcpStream, LengthDelimitedCodec>> {
-        self.ensure_stream_exists(address).await?;
-        self.streams
-            .remove(address)
-            .map(|(_, v)| v)
-            .ok_or(ClientError::Connectivity)
+        // Clone it so there are no guards on `self.streams` held across awaits
+        let existing = self.streams.get(address).map(|conn| conn.clone());
+        if let Some(conn) = existing.filter(|conn| !conn.is_closed()) {
+            return Ok(conn);
+        }
+
+        let conn = Connection::connect(address, &self.connection_options()).await?;
+        self.streams.insert(address.to_string(), conn.clone());
+        Ok(conn)
     }
 
     /// Returns the address for a given service object
@@ -253,9 +297,17 @@ where
                 Some(address) => address.clone(),
                 None => {
                     // If there is no address associated with this service,
-                    // it will pick one at random (allowing the server to 'correct' it)
+                    // it will pick one at random (allowing the server to 'correct' it),
+                    // avoiding the draining ones unless they are all draining
                     let mut rng = rng();
-                    let servers: Vec<String> = self.active_servers.iter().cloned().collect();
+                    let mut servers: Vec<String> = self
+                        .active_servers
+                        .difference(&self.draining_servers)
+                        .cloned()
+                        .collect();
+                    if servers.is_empty() {
+                        servers = self.active_servers.iter().cloned().collect();
+                    }
                     let random_server = servers
                         .choose(&mut rng)
                         .ok_or(ClientError::NoServersAvailable)?;
@@ -266,12 +318,12 @@ where
         Ok(address)
     }
 
-    /// Returns a stream to the server that a given ServiceObject might be allocated into
+    /// Returns a connection to the server that a given ServiceObject might be allocated into
     async fn service_object_stream(
         &mut self,
         service_object_type: impl ToString,
         service_object_id: impl ToString,
-    ) -> ClientResult<RefMut<'_, String, Framed<TcpStream, LengthDelimitedCodec>>> {
+    ) -> ClientResult<Connection> {
         self.fetch_active_servers().await?;
         let address = self
             .get_service_object_address(service_object_type, service_object_id)
@@ -299,12 +351,28 @@ where
         T: DeserializeOwned,
         E: std::error::Error + DeserializeOwned + Clone + Send + Sync,
     {
-        // TODO move fetch_active_servers into poll_ready self.ready().await?;
-        self.fetch_active_servers().await?;
+        self.send_with_metadata(handler_type, handler_id, payload, Metadata::default())
+            .await
+    }
 
+    /// Same as [Self::send], but the request carries `metadata`, which is exposed to the
+    /// handler (see [crate::protocol::metadata])
+    pub async fn send_with_metadata<T, E>(
+        &mut self,
+        handler_type: impl AsRef<str>,
+        handler_id: impl AsRef<str>,
+        payload: &(impl Serialize + IdentifiableType + Send + Sync),
+        metadata: Metadata,
+    ) -> Result<T, RequestError<E>>
+    where
+        T: DeserializeOwned,
+        E: std::error::Error + DeserializeOwned + Clone + Send + Sync,
+    {
         let handler_type = handler_type.as_ref().to_string();
         let handler_id = handler_id.as_ref().to_string();
-        let ser_payload = bincode::serialize(&payload)
+        let ser_payload = self
+            .codec
+            .serialize(payload)
             .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
         let message_type = payload.instance_type_id().to_string();
 
@@ -313,53 +381,338 @@ where
             handler_id.clone(),
             message_type.clone(),
             ser_payload.clone(),
-        );
-        let tower_svc = tower_services::Request::new(self.clone());
-        let mut tower_svc = tower_services::RequestRedirect::new(tower_svc);
-        let response = tower_svc.call(request).await;
+        )
+        .with_metadata(metadata);
+        let response = self.send_request(request).await;
         response.and_then(|x| {
-            let body: T = bincode::deserialize(&x)
+            let body: T = self
+                .codec
+                .deserialize(&x)
                 .map_err(|e| ClientError::DeseralizationError(e.to_string()))?;
             Ok(body)
         })
     }
 
     /// Same as [Self::send], but it uses the [RequestEnvelope] ready for serialization
+    ///
+    /// Requests without a [RequestEnvelope::deadline] get the client's default one, if any
     pub async fn send_request<E: std::error::Error + DeserializeOwned + Clone + Send + Sync>(
         &mut self,
-        request: RequestEnvelope,
+        mut request: RequestEnvelope,
     ) -> Result<Vec<u8>, RequestError<E>> {
         // TODO move fetch_active_servers into poll_ready self.ready().await?;
         self.fetch_active_servers().await?;
 
+        if request.deadline.is_none() {
+            request.deadline = self.request_timeout;
+        }
+
         let tower_svc = tower_services::Request::new(self.clone());
         let mut tower_svc = tower_services::RequestRedirect::new(tower_svc);
         let response = tower_svc.call(request).await?;
         Ok(response)
     }
 
+    /// Send many messages to objects of the same type at once
+    ///
+    /// `messages` pairs each object id with the message for it. The messages are grouped
+    /// by the server their objects are placed on, and each group is sent on a single frame.
+    ///
+    /// Each message gets its own result, in the same order as `messages`. The whole batch
+    /// fails only if the messages can't be serialized or there are no servers available.
+    pub async fn send_batch<T, E>(
+        &mut self,
+        handler_type: impl AsRef<str>,
+        messages: &[(
+            impl AsRef<str>,
+            impl Serialize + IdentifiableType + Send + Sync,
+        )],
+    ) -> Result<Vec<Result<T, RequestError<E>>>, RequestError<E>>
+    where
+        T: DeserializeOwned,
+        E: std::error::Error + DeserializeOwned,
+    {
+        let handler_type = handler_type.as_ref().to_string();
+        let requests = messages
+            .iter()
+            .map(|(handler_id, payload)| {
+                let ser_payload = self
+                    .codec
+                    .serialize(payload)
+                    .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+                Ok(RequestEnvelope::new(
+                    handler_type.clone(),
+                    handler_id.as_ref().to_string(),
+                    payload.instance_type_id().to_string(),
+                    ser_payload,
+                ))
+            })
+            .collect::<Result<Vec<_>, ClientError>>()?;
+
+        let responses = self.send_batch_requests::<E>(requests).await?;
+        let results = responses
+            .into_iter()
+            .map(|response| {
+                let body = response?;
+                self.codec
+                    .deserialize(&body)
+                    .map_err(|e| ClientError::DeseralizationError(e.to_string()).into())
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Same as [Self::send_batch], but it uses the [RequestEnvelope]s ready for
+    /// serialization, which might be for objects of different types
+    ///
+    /// Requests without a [RequestEnvelope::deadline] get the client's default one, if any.
+    ///
+    /// The requests that are redirected to another server are sent again, on another batch
+    /// to that server
+    pub async fn send_batch_requests<E>(
+        &mut self,
+        mut requests: Vec<RequestEnvelope>,
+    ) -> Result<Vec<Result<Vec<u8>, RequestError<E>>>, RequestError<E>>
+    where
+        E: std::error::Error + DeserializeOwned,
+    {
+        self.fetch_active_servers().await?;
+        for request in requests.iter_mut() {
+            if request.deadline.is_none() {
+                request.deadline = self.request_timeout;
+            }
+        }
+
+        let mut results: Vec<Option<Result<Vec<u8>, RequestError<E>>>> =
+            requests.iter().map(|_| None).collect();
+        let mut pending: Vec<usize> = (0..requests.len()).collect();
+        let mut redirects = 0;
+        while !pending.is_empty() {
+            // Groups the requests by the server their objects are (probably) placed on
+            let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+            for i in pending.drain(..) {
+                let request = &requests[i];
+                let address = self
+                    .get_service_object_address(&request.handler_type, &request.handler_id)
+                    .await?;
+                groups.entry(address).or_default().push(i);
+            }
+
+            let batches = groups.into_iter().map(|(address, indexes)| {
+                let mut client = self.clone();
+                let batch: Vec<RequestEnvelope> =
+                    indexes.iter().map(|i| requests[*i].clone()).collect();
+                async move {
+                    let connection = client.server_stream(&address).await;
+                    let responses = match connection {
+                        Ok(connection) => connection.request_batch(batch).await,
+                        Err(err) => Err(err),
+                    };
+                    (indexes, responses)
+                }
+            });
+
+            for (indexes, responses) in futures::future::join_all(batches).await {
+                let responses = match responses {
+                    Ok(responses) => responses,
+                    Err(err) => {
+                        for i in indexes {
+                            results[i] = Some(Err(err.clone().into()));
+                        }
+                        continue;
+                    }
+                };
+                for (i, response) in indexes.into_iter().zip(responses) {
+                    match response.body {
+                        Err(ResponseError::Redirect(to)) if redirects < MAX_REDIRECTS => {
+                            // The next round picks up the new address from the placement
+                            let request = &requests[i];
+                            let object_id =
+                                (request.handler_id.clone(), request.handler_type.clone());
+                            self.placement
+                                .write()
+                                .map_err(|_| ClientError::PlacementLock)?
+                                .put(object_id, to.to_string());
+                            pending.push(i);
+                        }
+                        body => {
+                            results[i] = Some(body.map_err(|err| {
+                                RequestError::from_response_error(err, self.codec.as_ref())
+                            }));
+                        }
+                    }
+                }
+            }
+            redirects += 1;
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                result.expect("Every request has a result once there are no pending ones")
+            })
+            .collect())
+    }
+
+    /// Send a message handled by a [StreamHandler](crate::registry::StreamHandler), and
+    /// stream back the items of its response
+    ///
+    /// The items are delivered only to this caller. The server stops producing items once
+    /// the returned stream is dropped, and it waits for the caller to consume the items it
+    /// already sent before producing more.
+    ///
+    /// If the connection is lost before the server sends all the items, the stream yields
+    /// [ClientError::Disconnect] and ends.
+    ///
+    /// It fails with the last [ResponseError::Redirect] if the object is still elsewhere
+    /// after following `MAX_REDIRECTS` redirects
+    pub async fn send_stream<T, E>(
+        &mut self,
+        handler_type: impl AsRef<str>,
+        handler_id: impl AsRef<str>,
+        payload: &(impl Serialize + IdentifiableType + Send + Sync),
+    ) -> Result<RequestStream<T, E>, RequestError<E>>
+    where
+        T: DeserializeOwned + Send + 'static,
+        E: std::error::Error + DeserializeOwned + Send + 'static,
+    {
+        let handler_type = handler_type.as_ref().to_string();
+        let handler_id = handler_id.as_ref().to_string();
+        let ser_payload = self
+            .codec
+            .serialize(payload)
+            .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
+        let message_type = payload.instance_type_id().to_string();
+        let request = RequestEnvelope::new(
+            handler_type.clone(),
+            handler_id.clone(),
+            message_type,
+            ser_payload,
+        );
+
+        let object_id = (handler_id.clone(), handler_type.clone());
+        let mut address = self
+            .get_service_object_address(&handler_type, &handler_id)
+            .await?;
+        let mut redirects = 0;
+        let (first, mut response_stream) = loop {
+            let connection = self.server_stream(&address).await?;
+            let mut response_stream = connection.request_stream(request.clone())?;
+
+            // The server tells whether the object lives elsewhere before sending any item
+            match response_stream.next().await {
+                Some(Err(ResponseError::Redirect(to))) if redirects < MAX_REDIRECTS => {
+                    redirects += 1;
+                    self.placement
+                        .write()
+                        .map_err(|_| ClientError::PlacementLock)?
+                        .put(object_id.clone(), to.to_string());
+                    address = to.to_string();
+                }
+                Some(Err(err @ ResponseError::Redirect(_))) => {
+                    return Err(RequestError::ResponseError(err));
+                }
+                first => break (first, response_stream),
+            }
+        };
+
+        let codec = self.codec.clone();
+        let stream = stream! {
+            let mut next = first;
+            while let Some(item) = next {
+                yield match item {
+                    Ok(item) => codec.deserialize(&item).map_err(|e| {
+                        ClientError::DeseralizationError(e.to_string()).into()
+                    }),
+                    Err(err) => Err(RequestError::from_response_error(err, codec.as_ref())),
+                };
+                next = response_stream.next().await;
+            }
+            if !response_stream.is_complete() {
+                yield Err(ClientError::Disconnect.into());
+            }
+        };
+        Ok(stream.boxed())
+    }
+
+    /// Deactivates an object wherever it lives in the cluster, and waits for the server
+    /// holding it to acknowledge it
+    ///
+    /// The object runs its [LifecycleMessage::Shutdown](crate::LifecycleMessage) hooks once
+    /// the messages it already got are handled. Then it is removed from its server and from
+    /// the object placement, so the next message sent to it activates it again.
+    /// Deactivating an object that is not active succeeds
+    pub async fn deactivate(
+        &mut self,
+        handler_type: impl AsRef<str>,
+        handler_id: impl AsRef<str>,
+    ) -> Result<(), RequestError<NoopError>> {
+        self.send_deactivate(handler_type, handler_id, false).await
+    }
+
+    /// Same as [Client::deactivate], see
+    /// [DeactivateRequest](crate::protocol::DeactivateRequest) for `hooks_ran`
+    pub(crate) async fn send_deactivate(
+        &mut self,
+        handler_type: impl AsRef<str>,
+        handler_id: impl AsRef<str>,
+        hooks_ran: bool,
+    ) -> Result<(), RequestError<NoopError>> {
+        let handler_type = handler_type.as_ref().to_string();
+        let handler_id = handler_id.as_ref().to_string();
+        let object_id = (handler_id.clone(), handler_type.clone());
+        self.fetch_active_servers().await?;
+
+        let mut address = self
+            .get_service_object_address(&handler_type, &handler_id)
+            .await?;
+        let mut redirects = 0;
+        loop {
+            let connection = self.server_stream(&address).await?;
+            let response = connection
+                .deactivate(&handler_type, &handler_id, hooks_ran)
+                .await?;
+            match response.body {
+                Err(ResponseError::Redirect(to)) if redirects < MAX_REDIRECTS => {
+                    redirects += 1;
+                    address = to.to_string();
+                }
+                Ok(_) => {
+                    self.placement
+                        .write()
+                        .map_err(|_| ClientError::PlacementLock)?
+                        .pop(&object_id);
+                    return Ok(());
+                }
+                Err(err) => return Err(RequestError::ResponseError(err)),
+            }
+        }
+    }
+
     async fn _subscribe<'a, T>(
         &'a mut self,
         handler_type: &str,
         handler_id: &str,
         address: &str,
-    ) -> SubscriptionStream<T>
+    ) -> Result<SubscriptionStream<T>, ClientError>
     where
         Self: 'a,
         T: DeserializeOwned + std::marker::Unpin + 'a + std::fmt::Debug,
     {
-        let mut svc_stream = self.pop_server_stream(&address.to_string()).await.unwrap();
-        let req = SubscriptionRequest {
-            handler_type: handler_type.to_string(),
-            handler_id: handler_id.to_string(),
-        };
-        let ser_request = bincode::serialize(&req).unwrap();
-        svc_stream.send(ser_request.into()).await.unwrap();
-        SubscriptionStream::<T>::new(svc_stream)
+        let connection = self.server_stream(&address.to_string()).await?;
+        let subscription = connection.subscribe(handler_type, handler_id)?;
+        Ok(SubscriptionStream::<T>::with_codec(
+            subscription,
+            self.codec.clone(),
+        ))
     }
 
     /// Subscribe to events from a service object
     ///
+    /// It fails if the server holding the object can't be reached. If the object moves
+    /// and the new server can't be reached, the stream yields [ResponseError::Unavailable]
+    /// and ends
+    ///
     /// <div class="warning">
     /// <b>TODO</b>
     ///
@@ -367,7 +720,7 @@ where
     /// - [x] Handle redirects
     /// - [ ] Move this logic into a tower service
     /// - [ ] Support moving service object (after you connect to a node and the handler you are listening to moves to some other node)
-    /// - [x] Use dedicated connection
+    /// - [x] Share the connection with the requests to the same server
     ///
     /// </div>
     pub async fn subscribe<'a, T>(
@@ -384,17 +737,26 @@ where
         let mut address = self
             .get_service_object_address(&handler_type, &handler_id)
             .await?;
+        let mut subscription_stream = self
+            ._subscribe(&handler_type, &handler_id, &address)
+            .await?;
 
         let stream = stream! {
             loop {
-                let mut subscription_stream = self._subscribe(&handler_type, &handler_id, &address).await;
                 while let Some(v) = subscription_stream.next().await {
                     if let Err(ResponseError::Redirect(to)) = v {
-                        address = to;
+                        address = to.to_string();
                         break;
                     }
                     yield v;
                 }
+                subscription_stream = match self._subscribe(&handler_type, &handler_id, &address).await {
+                    Ok(subscription_stream) => subscription_stream,
+                    Err(err) => {
+                        yield Err(ResponseError::Unavailable(ErrorDetails::new(err.to_string()).into()));
+                        break;
+                    }
+                };
             }
         };
         Ok(stream)
@@ -421,7 +783,7 @@ where
 
         match timeout(
             std::time::Duration::from_millis(self.timeout_millis),
-            conn(&server.address()),
+            conn(&server.address().to_string()),
         )
         .await
         {
@@ -434,6 +796,7 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::address::Address;
     use crate::cluster::storage::{Member, MembershipStorage, local::LocalStorage};
 
     fn client() -> Client<LocalStorage> {
@@ -441,15 +804,21 @@ mod test {
             timeout_millis: 1000,
             membership_storage: LocalStorage::default(),
             active_servers: Default::default(),
-            ts_active_servers_refresh: 0,
+            draining_servers: Default::default(),
+            active_servers_refreshed_at: None,
             streams: Arc::default(),
             placement: Arc::new(RwLock::new(LruCache::new(NonZeroUsize::new(10).unwrap()))),
+            codec: default_codec(),
+            compression: CompressionConfig::default(),
+            request_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
     async fn client_with_members() -> Client<LocalStorage> {
         let client = client();
-        let mut server = Member::new("0.0.0.0".to_string(), "1234".to_string());
+        let mut server = Member::new(Address::new("0.0.0.0", 1234));
         server.set_active(true);
         client
             .membership_storage
@@ -491,7 +860,17 @@ mod test {
         //  this test used to match against ClientError::Unknown,
         //  I don't recall why, so I need to investigate wether it was
         //  broken before or it is broken now
-        assert!(matches!(stream, Err(ClientError::Disconnect)));
+        assert!(matches!(stream, Err(ClientError::Connectivity)));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_cant_connect_to_server() {
+        let mut client = client_with_members().await;
+        let subscription = client
+            .subscribe::<String>("MockService", "1")
+            .await
+            .map(|_| ());
+        assert_eq!(subscription, Err(ClientError::Connectivity));
     }
 
     #[tokio::test]
@@ -499,4 +878,48 @@ mod test {
         let client = client_with_members().await;
         let _ = client.clone();
     }
+
+    #[tokio::test]
+    async fn test_new_objects_avoid_draining_servers() {
+        let mut client = client_with_members().await;
+        let mut draining_server = Member::new(Address::new("0.0.0.0", 1235));
+        draining_server.set_active(true);
+        draining_server.set_draining(true);
+        client
+            .membership_storage
+            .push(draining_server)
+            .await
+            .expect("add member");
+
+        for id in 0..10 {
+            let address = client
+                .get_service_object_address("Service", id)
+                .await
+                .unwrap();
+            assert_eq!(address, "0.0.0.0:1234");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_active_servers_refresh() {
+        let mut client = client_with_members().await;
+        client.fetch_active_servers().await.unwrap();
+        client
+            .membership_storage
+            .set_is_draining(&Address::new("0.0.0.0", 1234), true)
+            .await
+            .unwrap();
+
+        // The list is cached for a while
+        client.fetch_active_servers().await.unwrap();
+        assert!(client.draining_servers.is_empty());
+
+        // And then fetched again
+        client.active_servers_refreshed_at = Some(Instant::now() - ACTIVE_SERVERS_TTL);
+        client.fetch_active_servers().await.unwrap();
+        assert_eq!(
+            client.draining_servers,
+            HashSet::from(["0.0.0.0:1234".to_string()])
+        );
+    }
 }
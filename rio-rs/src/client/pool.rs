@@ -2,6 +2,8 @@
 //! caching strategy
 
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 
 // TODO expose the bb8 pool so the user ensure it uses the right one
 #[allow(unused)]
@@ -9,7 +11,11 @@ pub use bb8::PooledConnection;
 pub use bb8::{ManageConnection, Pool};
 
 use crate::cluster::storage::MembershipStorage;
+use crate::codec::{Codec, default_codec};
 use crate::protocol::ClientError;
+use crate::protocol::compression::CompressionConfig;
+#[cfg(feature = "tls")]
+use crate::tls::TlsClientConfig;
 
 use super::Client;
 use super::ClientBuilder;
@@ -26,6 +32,11 @@ use super::DEFAULT_TIMEOUT_MILLIS;
 pub struct ClientConnectionManager<S> {
     pub(crate) members_storage: S,
     pub(crate) timeout_millis: u64,
+    pub(crate) codec: Arc<dyn Codec>,
+    pub(crate) compression: CompressionConfig,
+    pub(crate) request_timeout: Option<Duration>,
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<TlsClientConfig>,
 }
 
 impl<S: MembershipStorage + 'static> ClientConnectionManager<S> {
@@ -33,6 +44,11 @@ impl<S: MembershipStorage + 'static> ClientConnectionManager<S> {
         ClientConnectionManager {
             members_storage,
             timeout_millis: DEFAULT_TIMEOUT_MILLIS,
+            codec: default_codec(),
+            compression: CompressionConfig::default(),
+            request_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
@@ -45,10 +61,22 @@ impl<S: MembershipStorage + 'static> ManageConnection for ClientConnectionManage
     type Connection = Client<S>;
     type Error = ClientError;
     fn connect(&self) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send {
+        let builder = ClientBuilder::new()
+            .members_storage(self.members_storage.clone())
+            .timeout_millis(self.timeout_millis)
+            .codec(self.codec.clone())
+            .compression(self.compression.clone());
+        let builder = match self.request_timeout {
+            Some(request_timeout) => builder.request_timeout(request_timeout),
+            None => builder,
+        };
+        #[cfg(feature = "tls")]
+        let builder = match &self.tls {
+            Some(tls) => builder.tls(tls.clone()),
+            None => builder,
+        };
         futures::future::ready(
-            ClientBuilder::new()
-                .members_storage(self.members_storage.clone())
-                .timeout_millis(self.timeout_millis)
+            builder
                 .build()
                 .map_err(|err| ClientError::Unknown(err.to_string())),
         )
@@ -70,6 +98,7 @@ impl<S: MembershipStorage + 'static> ManageConnection for ClientConnectionManage
 mod test {
     use bb8::Pool;
 
+    use crate::address::Address;
     use crate::cluster::storage::Member;
     use crate::cluster::storage::local::LocalStorage;
 
@@ -79,7 +108,7 @@ mod test {
     async fn basic_usage() {
         let local_members_storage = LocalStorage::default();
         local_members_storage
-            .push(Member::new("0.0.0.0".to_string(), "9999".to_string()))
+            .push(Member::new(Address::new("0.0.0.0", 9999)))
             .await
             .unwrap();
 
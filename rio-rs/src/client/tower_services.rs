@@ -3,14 +3,17 @@ use std::task::Poll;
 use std::time::Duration;
 
 use futures::future::BoxFuture;
-use futures::{FutureExt, SinkExt, StreamExt, pin_mut};
+use futures::{FutureExt, pin_mut};
 use log::{debug, error, info, warn};
 use serde::de::DeserializeOwned;
+use tokio::time::{Instant, timeout_at};
 use tower::Service as TowerService;
 
 use crate::cluster::storage::MembershipStorage;
+use crate::metrics;
 use crate::protocol::RequestError;
-use crate::protocol::{ClientError, RequestEnvelope, ResponseEnvelope, ResponseError};
+use crate::protocol::error_code::Retryability;
+use crate::protocol::{ClientError, RequestEnvelope, ResponseError};
 
 use super::Client;
 
@@ -60,31 +63,21 @@ where
             .map_err(|e| e.into())
     }
 
-    /// TODO
+    /// Sends the request through the connection that is shared by all the requests to the
+    /// same server, and waits for its response
     fn call(&mut self, req: RequestEnvelope) -> Self::Future {
         let mut client = self.client.clone();
         Box::pin(async move {
-            let mut stream = client
+            let connection = client
                 .service_object_stream(&req.handler_type, &req.handler_id)
                 .await?;
 
-            let ser_request = bincode::serialize(&req)
-                .map_err(|e| ClientError::SeralizationError(e.to_string()))?;
-
-            stream.send(ser_request.into()).await?;
-            match stream.next().await {
-                Some(Ok(frame)) => {
-                    let message: ResponseEnvelope = bincode::deserialize(&frame)
-                        .map_err(|e| ClientError::DeseralizationError(e.to_string()))?;
-                    Ok(message.body?)
-                }
-                Some(Err(e)) => Err(RequestError::ClientError(ClientError::IoError(
-                    e.to_string(),
-                ))),
-                // When there are no more items on the stream, it means the TCP stream was
-                // disconnected
-                None => Err(RequestError::ClientError(ClientError::Disconnect)),
-            }
+            // The connection is shared with other requests, it routes back only the response
+            // for this one
+            let message = connection.request(req).await?;
+            message
+                .body
+                .map_err(|err| RequestError::from_response_error(err, client.codec.as_ref()))
         })
     }
 }
@@ -93,6 +86,12 @@ where
 ///
 /// - When the object is not on the cached/expected placement
 /// - When the object is not yet allocated
+/// - When the error is [Retryability::Retryable] (see [crate::protocol::error_code])
+/// - When the error is [Retryability::IfIdempotent] and the request is
+///   [idempotent](RequestEnvelope::idempotent)
+///
+/// If the request has a [RequestEnvelope::deadline], it stops retrying and fails with
+/// [ResponseError::DeadlineExceeded] once the deadline passes
 pub struct RequestRedirect<'a, S, E>
 where
     S: MembershipStorage,
@@ -136,8 +135,10 @@ where
         // function lives shorter than the actual service (as in `'b: 'a`)
         let handler_type = req.handler_type.clone();
         let handler_id = req.handler_id.clone();
-        let request = req.clone();
+        let mut request = req.clone();
         let mut inner_service = self.inner.clone();
+        let deadline = req.deadline.map(|deadline| Instant::now() + deadline);
+        let idempotent = req.idempotent;
 
         // TODO move this to config
         let retry_min_duration = Duration::from_nanos(1_000); // 0.01ms
@@ -147,8 +148,13 @@ where
 
         let mut retry_count = 0;
         let mut retry_duration = retry_min_duration;
-        Box::pin(async move {
+        let retries = async move {
             loop {
+                // Every attempt tells the server how much time is left
+                if let Some(deadline) = deadline {
+                    request.deadline = Some(deadline.saturating_duration_since(Instant::now()));
+                }
+
                 // Used a cloned request, so it can be used in a loop
                 let response = inner_service.call(request.clone()).await;
                 match response {
@@ -159,24 +165,23 @@ where
                         // Add the new address to the placement so in the next iteration
                         // it will use the right server
                         info!("Redirect to {}", to);
+                        metrics::client_retry("redirect");
                         inner_service
                             .client
                             .placement
                             .write()
                             .map_err(|_| ClientError::PlacementLock)?
-                            .put((handler_type.clone(), handler_id.clone()), to);
+                            .put((handler_type.clone(), handler_id.clone()), to.to_string());
                     }
-                    // All these errors indicate that the server we've tried is no longer available
-                    // When facing one of the errors below, we need to retry the
+                    // These errors indicate that the server we've tried is no longer available,
+                    // or that it couldn't take the request at the moment. We need to retry the
                     // request so it picks up a new Server on the cluster
+                    //
+                    // If the connection is lost after the request was sent, it might have been
+                    // handled already, so it is only sent again if that is harmless
                     Err(e)
-                        if matches!(
-                            e,
-                            RequestError::ResponseError(ResponseError::DeallocateServiceObject,)
-                                | RequestError::ClientError(ClientError::Disconnect)
-                                | RequestError::ClientError(ClientError::ServerNotAvailable(_))
-                                | RequestError::ClientError(ClientError::IoError(_))
-                        ) =>
+                        if e.is_retryable()
+                            || (idempotent && e.retryability() == Retryability::IfIdempotent) =>
                     {
                         // early quiting if max_retries reached
                         if let Some(max_retries) = max_retries {
@@ -186,6 +191,7 @@ where
                             }
                         }
                         warn!("{:?}", e);
+                        metrics::client_retry("retryable");
 
                         // update retry info
                         debug!("Retry in {:?}", retry_duration);
@@ -198,7 +204,7 @@ where
                         // Removed the old placement, the next request
                         // will pickup a new placement to try from
                         warn!("Refresh the list of servers");
-                        inner_service.client.ts_active_servers_refresh = 0; // forces re-fetching the
+                        inner_service.client.active_servers_refreshed_at = None; // forces re-fetching the
                         // active servers
                         inner_service
                             .client
@@ -221,6 +227,20 @@ where
                     rest => return rest,
                 }
             }
+        };
+
+        // Gives up on the request, including its retries, once the deadline passes
+        Box::pin(async move {
+            match deadline {
+                Some(deadline) => {
+                    timeout_at(deadline, retries)
+                        .await
+                        .unwrap_or(Err(RequestError::ResponseError(
+                            ResponseError::DeadlineExceeded,
+                        )))
+                }
+                None => retries.await,
+            }
         })
     }
 }
@@ -229,24 +249,34 @@ where
 mod test {
     use async_trait::async_trait;
     use chrono::{DateTime, Utc};
+    use futures::{SinkExt, StreamExt};
     use lru::LruCache;
     use serde::{Deserialize, Serialize};
     use std::{
         num::NonZero,
-        sync::{Arc, RwLock},
+        sync::{
+            Arc, RwLock,
+            atomic::{AtomicUsize, Ordering},
+        },
     };
     use thiserror::Error;
+    use tokio::net::TcpListener;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
     use tower::ServiceExt;
 
     use super::*;
     use crate::{
+        address::Address,
         cluster::storage::{
             Member, MembershipResult, MembershipStorage, MembershipUnitResult, local::LocalStorage,
         },
         errors::MembershipError,
+        protocol::ResponseEnvelope,
+        protocol::frame::{self, FrameKind},
+        protocol::handshake::{DEFAULT_CODEC, Handshake, HandshakeResponse},
     };
 
-    #[derive(Error, Debug, Serialize, Deserialize, PartialEq)]
+    #[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq)]
     enum NoopError {
         #[error("No-op")]
         Noop,
@@ -260,19 +290,22 @@ mod test {
         async fn push(&self, _: Member) -> MembershipUnitResult {
             Ok(())
         }
-        async fn remove(&self, _: &str, _: &str) -> MembershipUnitResult {
+        async fn remove(&self, _: &Address) -> MembershipUnitResult {
+            Ok(())
+        }
+        async fn set_is_active(&self, _: &Address, _: bool) -> MembershipUnitResult {
             Ok(())
         }
-        async fn set_is_active(&self, _: &str, _: &str, _: bool) -> MembershipUnitResult {
+        async fn set_is_draining(&self, _: &Address, _: bool) -> MembershipUnitResult {
             Ok(())
         }
         async fn members(&self) -> MembershipResult<Vec<Member>> {
             Err(MembershipError::Unknown("".to_string()))
         }
-        async fn notify_failure(&self, _: &str, _: &str) -> MembershipUnitResult {
+        async fn notify_failure(&self, _: &Address) -> MembershipUnitResult {
             Ok(())
         }
-        async fn member_failures(&self, _: &str, _: &str) -> MembershipResult<Vec<DateTime<Utc>>> {
+        async fn member_failures(&self, _: &Address) -> MembershipResult<Vec<DateTime<Utc>>> {
             Ok(vec![])
         }
     }
@@ -282,9 +315,15 @@ mod test {
             timeout_millis: 1000,
             membership_storage: LocalStorage::default(),
             active_servers: Default::default(),
-            ts_active_servers_refresh: 0,
+            draining_servers: Default::default(),
+            active_servers_refreshed_at: None,
             streams: Arc::default(),
             placement: Arc::new(RwLock::new(LruCache::new(NonZero::new(10).unwrap()))),
+            codec: crate::codec::default_codec(),
+            compression: Default::default(),
+            request_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
@@ -302,7 +341,7 @@ mod test {
         let client = client();
         assert!(client.active_servers.is_empty());
 
-        let mut server = Member::new("0.0.0.0".to_string(), "1234".to_string());
+        let mut server = Member::new(Address::new("0.0.0.0", 1234));
         server.set_active(true);
         client
             .membership_storage
@@ -322,9 +361,15 @@ mod test {
             timeout_millis: 1000,
             membership_storage: FailMembershipStorage {},
             active_servers: Default::default(),
-            ts_active_servers_refresh: 0,
+            draining_servers: Default::default(),
+            active_servers_refreshed_at: None,
             streams: Arc::default(),
             placement: Arc::new(RwLock::new(LruCache::new(NonZero::new(10).unwrap()))),
+            codec: crate::codec::default_codec(),
+            compression: Default::default(),
+            request_timeout: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         };
         let mut request: Request<_, NoopError> = Request::new(client);
         let waker = futures::task::noop_waker();
@@ -337,4 +382,79 @@ mod test {
             )))
         );
     }
+
+    /// Drops the connection on the first request it gets, and answers the next ones.
+    /// Returns its address and the number of requests it got
+    async fn flaky_server() -> (Address, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let server_attempts = attempts.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut frames = Framed::new(stream, LengthDelimitedCodec::new());
+                frames.next().await.unwrap().unwrap();
+                let response = HandshakeResponse {
+                    result: Handshake::default().negotiate(
+                        frame::SUPPORTED_VERSIONS,
+                        DEFAULT_CODEC,
+                        &[],
+                    ),
+                };
+                let ser_response = bincode::serialize(&response).unwrap();
+                frames
+                    .send(frame::encode(FrameKind::HandshakeResponse, &ser_response))
+                    .await
+                    .unwrap();
+
+                let Some(Ok(request_frame)) = frames.next().await else {
+                    continue;
+                };
+                if server_attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    continue;
+                }
+                let (_, payload) = frame::decode(&request_frame).unwrap();
+                let request: RequestEnvelope = bincode::deserialize(&payload).unwrap();
+                let response =
+                    ResponseEnvelope::new(request.payload).with_request_id(request.request_id);
+                let ser_response = bincode::serialize(&response).unwrap();
+                frames
+                    .send(frame::encode(FrameKind::Response, &ser_response))
+                    .await
+                    .unwrap();
+            }
+        });
+        (Address::new("127.0.0.1", port), attempts)
+    }
+
+    #[tokio::test]
+    async fn test_retry_if_idempotent() {
+        let (address, attempts) = flaky_server().await;
+        let client = client();
+        let mut server = Member::new(address);
+        server.set_active(true);
+        client.membership_storage.push(server).await.unwrap();
+        let request = RequestEnvelope::new("T".into(), "1".into(), "M".into(), vec![1]);
+
+        // It might have been handled, so it isn't sent again
+        let mut service: RequestRedirect<_, NoopError> =
+            RequestRedirect::new(Request::new(client.clone()));
+        let response = service.ready().await.unwrap().call(request.clone()).await;
+        assert_eq!(
+            response,
+            Err(RequestError::ClientError(ClientError::Disconnect))
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        attempts.store(0, Ordering::SeqCst);
+        let mut service: RequestRedirect<_, NoopError> = RequestRedirect::new(Request::new(client));
+        let response = service
+            .ready()
+            .await
+            .unwrap()
+            .call(request.with_idempotent(true))
+            .await;
+        assert_eq!(response, Ok(vec![1]));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
 }
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use std::time::Duration;
 
 use super::ClusterProvider;
+use crate::address::Address;
 use crate::{cluster::storage::MembershipStorage, errors::ClusterProviderServeError};
 
 /// Local server compatible with the ClusterProvider API
@@ -24,7 +25,7 @@ where
         &self.members_storage
     }
 
-    async fn serve(&self, _address: &str) -> Result<(), ClusterProviderServeError> {
+    async fn serve(&self, _address: &Address) -> Result<(), ClusterProviderServeError> {
         loop {
             tokio::time::sleep(Duration::from_secs(1)).await;
         }
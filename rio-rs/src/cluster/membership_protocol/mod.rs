@@ -1,6 +1,8 @@
 use async_trait::async_trait;
 
+use crate::address::Address;
 use crate::errors::ClusterProviderServeError;
+use crate::server::DrainHandle;
 
 pub mod local;
 pub mod peer_to_peer;
@@ -27,5 +29,20 @@ where
     ///
     /// Each CLusterProvider will implement different logic for its membership algorithm, but it
     /// needs to be able to run it along the duration of the [Server](crate::server::Server).
-    async fn serve(&self, address: &str) -> Result<(), ClusterProviderServeError>;
+    async fn serve(&self, address: &Address) -> Result<(), ClusterProviderServeError>;
+
+    /// Same as [ClusterProvider::serve], for a server that might be draining
+    ///
+    /// The [Server](crate::server::Server) only advertises the changes on its draining
+    /// status, so the providers that add it to the
+    /// [MembershipStorage](super::storage::MembershipStorage) must add it with the current
+    /// status, from `draining`. By default, `draining` is ignored
+    async fn serve_with_drain_handle(
+        &self,
+        address: &Address,
+        draining: DrainHandle,
+    ) -> Result<(), ClusterProviderServeError> {
+        let _ = draining;
+        self.serve(address).await
+    }
 }
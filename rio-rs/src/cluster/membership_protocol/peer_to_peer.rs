@@ -8,13 +8,14 @@ use bon::Builder;
 use chrono::Utc;
 use log::debug;
 use std::time::{Duration, SystemTime};
-use std::{net::SocketAddr, str::FromStr};
 
+use crate::address::Address;
 use crate::client::Client;
 use crate::cluster::membership_protocol::ClusterProvider;
 use crate::cluster::storage::local::LocalStorage;
 use crate::cluster::storage::{Member, MembershipStorage};
 use crate::errors::ClusterProviderServeError;
+use crate::server::DrainHandle;
 
 /// Gossip-based [ClusterProvider]
 ///
@@ -49,12 +50,12 @@ where
 {
     async fn get_sorted_members(&self) -> Result<Vec<Member>, ClusterProviderServeError> {
         let mut members = self.members_storage().members().await?;
-        members.sort_by_key(|x| x.address());
+        members.sort_by(|a, b| a.address().cmp(b.address()));
         Ok(members)
     }
 
     /// TODO docs
-    fn get_members_to_monitor(&self, address: &str, sorted_members: &[Member]) -> Vec<Member> {
+    fn get_members_to_monitor(&self, address: &Address, sorted_members: &[Member]) -> Vec<Member> {
         let limit_monitored_members = self.limit_monitored_members;
         let mut visited = 0;
 
@@ -88,7 +89,7 @@ where
         let ping = client.ping().await;
         if ping.is_err() {
             self.members_storage()
-                .notify_failure(member.ip(), member.port())
+                .notify_failure(member.address())
                 .await?;
         }
         Ok(())
@@ -103,7 +104,7 @@ where
 
         let failures = self
             .members_storage()
-            .member_failures(member.ip(), member.port())
+            .member_failures(member.address())
             .await?;
 
         let failures_over_threshold = failures.iter().filter(|&time| time > &t0).count() as u64
@@ -141,16 +142,25 @@ where
     /// 1. _It shouldn't bring dead servers back to life_
     ///
     /// </div>
-    async fn serve(&self, address: &str) -> Result<(), ClusterProviderServeError> {
+    async fn serve(&self, address: &Address) -> Result<(), ClusterProviderServeError> {
+        self.serve_with_drain_handle(address, DrainHandle::default())
+            .await
+    }
+
+    /// Same as [PeerToPeerClusterProvider::serve], but this server joins the cluster with
+    /// the draining status it has at that point
+    async fn serve_with_drain_handle(
+        &self,
+        address: &Address,
+        draining: DrainHandle,
+    ) -> Result<(), ClusterProviderServeError> {
         let sleep_period = std::time::Duration::from_secs(self.interval_secs);
-        let socket_address = SocketAddr::from_str(address)
-            .or(Err(ClusterProviderServeError::SocketAddrParsingError))?;
-        let ip = socket_address.ip().to_string();
-        let port = socket_address.port().to_string();
 
-        let mut self_member = Member::new(ip, port);
+        let mut self_member = Member::new(address.clone());
         self_member.set_active(true);
         self_member.set_last_seen();
+        // Pushing the member overwrites the status the server might have advertised already
+        self_member.set_draining(draining.is_draining());
         self.members_storage().push(self_member).await?;
 
         loop {
@@ -169,7 +179,7 @@ where
 
                     if is_broken {
                         self.members_storage()
-                            .set_inactive(test_member.ip(), test_member.port())
+                            .set_inactive(test_member.address())
                             .await?;
 
                         if let Some(drop_inactive_after_secs) = self.drop_inactive_after_secs {
@@ -178,16 +188,14 @@ where
                                 now - Duration::from_secs(drop_inactive_after_secs as u64);
 
                             if test_member.last_seen() < &drop_threshold {
-                                self.members_storage()
-                                    .remove(test_member.ip(), test_member.port())
-                                    .await?;
+                                self.members_storage().remove(test_member.address()).await?;
                             }
                         }
 
                         return Ok::<_, ClusterProviderServeError>((test_member, false));
                     } else if !test_member.active() {
                         self.members_storage()
-                            .set_active(test_member.ip(), test_member.port())
+                            .set_active(test_member.address())
                             .await?;
                     }
                     debug!("[{}] {:?} is OK ", address, test_member.address());
@@ -219,16 +227,9 @@ mod test {
 
     async fn storage() -> impl MembershipStorage {
         let storage = LocalStorage::default();
-        for (ip, port) in [
-            ("0.0.0.0", "5000"),
-            ("0.0.0.0", "5001"),
-            ("0.0.0.0", "5002"),
-            ("0.0.0.0", "5003"),
-            ("0.0.0.0", "5004"),
-            ("0.0.0.0", "5005"),
-        ] {
+        for port in 5000..=5005 {
             storage
-                .push(Member::new(ip.to_string(), port.to_string()))
+                .push(Member::new(Address::new("0.0.0.0", port)))
                 .await
                 .unwrap();
         }
@@ -243,44 +244,74 @@ mod test {
             .build();
         let failures = membership
             .members_storage()
-            .member_failures("0.0.0.0", "-1")
+            .member_failures(&Address::new("0.0.0.0", 0))
             .await
             .unwrap();
         assert_eq!(failures.len(), 0);
 
         membership
-            .test_member(&Member::new("0.0.0.0".to_string(), "-1".to_string()))
+            .test_member(&Member::new(Address::new("0.0.0.0", 0)))
             .await?;
         let failures = membership
             .members_storage()
-            .member_failures("0.0.0.0", "-1")
+            .member_failures(&Address::new("0.0.0.0", 0))
             .await?;
         assert_eq!(failures.len(), 1);
 
         membership
-            .test_member(&Member::new("0.0.0.0".to_string(), "-1".to_string()))
+            .test_member(&Member::new(Address::new("0.0.0.0", 0)))
             .await?;
         let failures = membership
             .members_storage()
-            .member_failures("0.0.0.0", "-1")
+            .member_failures(&Address::new("0.0.0.0", 0))
             .await?;
         assert_eq!(failures.len(), 2);
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_serve_while_draining() -> TestResult {
+        let storage = LocalStorage::default();
+        let membership = PeerToPeerClusterProvider::builder()
+            .members_storage(storage.clone())
+            .build();
+        let address = Address::new("0.0.0.0", 5000);
+
+        // The server was drained before joining the cluster
+        let draining = DrainHandle::default();
+        draining.drain();
+        let serving =
+            tokio::spawn(
+                async move { membership.serve_with_drain_handle(&address, draining).await },
+            );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        serving.abort();
+
+        let members = storage.members().await?;
+        assert_eq!(members.len(), 1);
+        assert!(members[0].draining());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_is_broken() -> TestResult {
         let storage = LocalStorage::default();
-        storage.notify_failure("0.0.0.0", "5000").await?;
-        storage.notify_failure("0.0.0.0", "5000").await?;
-        storage.notify_failure("0.0.0.0", "5001").await?;
+        storage
+            .notify_failure(&Address::new("0.0.0.0", 5000))
+            .await?;
+        storage
+            .notify_failure(&Address::new("0.0.0.0", 5000))
+            .await?;
+        storage
+            .notify_failure(&Address::new("0.0.0.0", 5001))
+            .await?;
 
         let membership = PeerToPeerClusterProvider::builder()
             .members_storage(storage.clone())
             .build();
 
         let is_broken = membership
-            .is_broken(&Member::new("0.0.0.0".to_string(), "5000".to_string()))
+            .is_broken(&Member::new(Address::new("0.0.0.0", 5000)))
             .await;
         assert!(!is_broken?);
 
@@ -290,7 +321,7 @@ mod test {
             .build();
 
         let is_broken = membership
-            .is_broken(&Member::new("0.0.0.0".to_string(), "5000".to_string()))
+            .is_broken(&Member::new(Address::new("0.0.0.0", 5000)))
             .await;
         assert!(is_broken?);
 
@@ -301,7 +332,7 @@ mod test {
             .build();
 
         let is_broken = membership
-            .is_broken(&Member::new("0.0.0.0".to_string(), "5000".to_string()))
+            .is_broken(&Member::new(Address::new("0.0.0.0", 5000)))
             .await;
         assert!(!is_broken?);
         Ok(())
@@ -318,9 +349,9 @@ mod test {
             .build();
 
         // Maps monitored addresses, grouped by server (tester)
-        let mut monitored_counter: HashMap<String, usize> = HashMap::new();
+        let mut monitored_counter: HashMap<Address, usize> = HashMap::new();
         for i in items.iter() {
-            let members = membership.get_members_to_monitor(&i.address(), &items);
+            let members = membership.get_members_to_monitor(i.address(), &items);
             monitored_counter.insert(i.address().clone(), members.len());
         }
 
@@ -336,14 +367,14 @@ mod test {
     async fn get_members_to_monitor_few_members() -> TestResult {
         let storage = LocalStorage::default();
         storage
-            .push(Member::new("0.0.0.0".to_string(), "5000".to_string()))
+            .push(Member::new(Address::new("0.0.0.0", 5000)))
             .await?;
         let items = storage.members().await?;
         let membership = PeerToPeerClusterProvider::builder()
             .members_storage(storage)
             .build();
 
-        let members = membership.get_members_to_monitor("0.0.0.0:5000", &items);
+        let members = membership.get_members_to_monitor(&Address::new("0.0.0.0", 5000), &items);
         assert_eq!(members.len(), 0, "{:?}", members);
         Ok(())
     }
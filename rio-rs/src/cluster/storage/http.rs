@@ -15,6 +15,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::net::ToSocketAddrs;
 
+use crate::address::Address;
 use crate::errors::MembershipError;
 
 use super::{Member, MembershipResult, MembershipStorage, MembershipUnitResult};
@@ -24,6 +25,9 @@ pub struct HttpMember {
     ip: String,
     port: String,
     active: bool,
+    /// Absent on older servers
+    #[serde(default)]
+    draining: bool,
     last_seen: String,
 }
 
@@ -60,9 +64,10 @@ async fn list_members<S: MembershipStorage + 'static>(
         .iter()
         .map(|x| HttpMember {
             active: x.active,
-            ip: x.ip.clone(),
+            draining: x.draining,
+            ip: x.host().to_string(),
             last_seen: x.last_seen.to_string(),
-            port: x.port.clone(),
+            port: x.port().to_string(),
         })
         .collect();
 
@@ -70,10 +75,13 @@ async fn list_members<S: MembershipStorage + 'static>(
 }
 
 async fn member_failures<S: MembershipStorage + 'static>(
-    Path((ip, port)): Path<(String, String)>,
+    Path((ip, port)): Path<(String, u16)>,
     State(app_data): State<AppData<S>>,
 ) -> (StatusCode, Json<Vec<String>>) {
-    let data = app_data.inner.member_failures(&ip, &port).await;
+    let data = app_data
+        .inner
+        .member_failures(&Address::new(ip, port))
+        .await;
     let data = match data {
         Ok(x) => x,
         Err(_err) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(vec![])),
@@ -93,25 +101,33 @@ impl MembershipStorage for HttpMembershipStorage {
         Err(MembershipError::ReadOnly("push".to_string()))
     }
 
-    async fn remove(&self, _ip: &str, _port: &str) -> MembershipUnitResult {
+    async fn remove(&self, _address: &Address) -> MembershipUnitResult {
         Err(MembershipError::ReadOnly("remove".to_string()))
     }
 
-    async fn set_is_active(
+    async fn set_is_active(&self, _address: &Address, _is_active: bool) -> MembershipUnitResult {
+        Err(MembershipError::ReadOnly("set_is_active".to_string()))
+    }
+
+    async fn set_is_draining(
         &self,
-        _ip: &str,
-        _port: &str,
-        _is_active: bool,
+        _address: &Address,
+        _is_draining: bool,
     ) -> MembershipUnitResult {
-        Err(MembershipError::ReadOnly("set_is_active".to_string()))
+        Err(MembershipError::ReadOnly("set_is_draining".to_string()))
     }
 
-    async fn notify_failure(&self, _ip: &str, _port: &str) -> MembershipUnitResult {
+    async fn notify_failure(&self, _address: &Address) -> MembershipUnitResult {
         Err(MembershipError::ReadOnly("notify_failure".to_string()))
     }
 
-    async fn member_failures(&self, ip: &str, port: &str) -> MembershipResult<Vec<DateTime<Utc>>> {
-        let url = format!("{}/members/{}/{}", self.remote_address, ip, port);
+    async fn member_failures(&self, address: &Address) -> MembershipResult<Vec<DateTime<Utc>>> {
+        let url = format!(
+            "{}/members/{}/{}",
+            self.remote_address,
+            address.host(),
+            address.port()
+        );
         let resp = reqwest::get(url)
             .await
             .map_err(|err| MembershipError::Upstream(err.to_string()))?;
@@ -136,15 +152,20 @@ impl MembershipStorage for HttpMembershipStorage {
             .await
             .map_err(|err| MembershipError::Unknown(err.to_string()))?;
 
-        let members: Vec<Member> = http_members
+        http_members
             .iter()
-            .map(|x| Member {
-                active: x.active,
-                ip: x.ip.clone(),
-                last_seen: x.last_seen.parse().unwrap(),
-                port: x.port.clone(),
+            .map(|x| {
+                let port = x
+                    .port
+                    .parse()
+                    .map_err(|_| MembershipError::DeserializationError)?;
+                Ok(Member {
+                    address: Address::new(x.ip.clone(), port),
+                    active: x.active,
+                    draining: x.draining,
+                    last_seen: x.last_seen.parse().unwrap(),
+                })
             })
-            .collect();
-        Ok(members)
+            .collect()
     }
 }
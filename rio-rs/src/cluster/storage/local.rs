@@ -5,10 +5,12 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use tokio::sync::RwLock;
 
+use crate::address::Address;
+
 use super::{Member, MembershipResult, MembershipStorage, MembershipUnitResult};
 
 type ArcMembers = Arc<RwLock<Vec<Member>>>;
-type ArcFailures = Arc<RwLock<Vec<(String, String, DateTime<Utc>)>>>;
+type ArcFailures = Arc<RwLock<Vec<(Address, DateTime<Utc>)>>>;
 
 #[derive(Clone, Default, Debug)]
 pub struct LocalStorage {
@@ -23,17 +25,17 @@ impl MembershipStorage for LocalStorage {
         Ok(())
     }
 
-    async fn remove(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn remove(&self, address: &Address) -> MembershipUnitResult {
         let mut guard = self.members.write().await;
-        guard.retain(|x| x.ip() != ip || x.port() != port);
+        guard.retain(|x| x.address() != address);
         Ok(())
     }
 
-    async fn set_is_active(&self, ip: &str, port: &str, is_active: bool) -> MembershipUnitResult {
+    async fn set_is_active(&self, address: &Address, is_active: bool) -> MembershipUnitResult {
         let last_seen = Utc::now();
         let mut guard = self.members.write().await;
         for i in guard.iter_mut() {
-            if i.ip() == ip && i.port() == port {
+            if i.address() == address {
                 i.set_active(is_active);
                 i.last_seen = last_seen;
             }
@@ -41,19 +43,29 @@ impl MembershipStorage for LocalStorage {
         Ok(())
     }
 
-    async fn notify_failure(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn set_is_draining(&self, address: &Address, is_draining: bool) -> MembershipUnitResult {
+        let mut guard = self.members.write().await;
+        for i in guard.iter_mut() {
+            if i.address() == address {
+                i.set_draining(is_draining);
+            }
+        }
+        Ok(())
+    }
+
+    async fn notify_failure(&self, address: &Address) -> MembershipUnitResult {
         let now = Utc::now();
         let mut guard = self.failures.write().await;
-        guard.push((ip.to_string(), port.to_string(), now));
+        guard.push((address.clone(), now));
         Ok(())
     }
 
-    async fn member_failures(&self, ip: &str, port: &str) -> MembershipResult<Vec<DateTime<Utc>>> {
+    async fn member_failures(&self, address: &Address) -> MembershipResult<Vec<DateTime<Utc>>> {
         let guard = self.failures.read().await;
         let items = guard
             .iter()
-            .filter(|(ip_, port_, ..)| ip_ == ip && port_ == port)
-            .map(|x| x.2)
+            .filter(|(address_, _)| address_ == address)
+            .map(|x| x.1)
             .collect();
         Ok(items)
     }
@@ -69,16 +81,9 @@ mod test {
 
     async fn storage() -> impl MembershipStorage {
         let storage = LocalStorage::default();
-        for (ip, port) in [
-            ("0.0.0.0", "5000"),
-            ("0.0.0.0", "5001"),
-            ("0.0.0.0", "5002"),
-            ("0.0.0.0", "5003"),
-            ("0.0.0.0", "5004"),
-            ("0.0.0.0", "5005"),
-        ] {
+        for port in 5000..=5005 {
             storage
-                .push(Member::new(ip.to_string(), port.to_string()))
+                .push(Member::new(Address::new("0.0.0.0", port)))
                 .await
                 .unwrap();
         }
@@ -95,7 +100,10 @@ mod test {
         let members = second_storage.members().await.unwrap();
         assert_eq!(members.len(), 6);
 
-        second_storage.remove("0.0.0.0", "5005").await.unwrap();
+        second_storage
+            .remove(&Address::new("0.0.0.0", 5005))
+            .await
+            .unwrap();
 
         let members = storage.members().await.unwrap();
         assert_eq!(members.len(), 5);
@@ -0,0 +1,2 @@
+--  Members that don't take new objects
+ALTER TABLE cluster_provider_members ADD COLUMN draining BOOLEAN NOT NULL DEFAULT FALSE;
@@ -3,6 +3,7 @@ use std::fmt::Debug;
 use async_trait::async_trait;
 use chrono::{DateTime, TimeZone, Utc};
 
+use crate::address::Address;
 use crate::errors::MembershipError;
 
 #[cfg(feature = "http")]
@@ -19,26 +20,28 @@ pub mod sqlite;
 /// Represents a running [Server](crate::server::Server).
 #[derive(Clone, Debug)]
 pub struct Member {
-    ip: String,
-    port: String,
+    address: Address,
     active: bool,
+    /// The member doesn't take new objects, see [DrainHandle](crate::server::DrainHandle)
+    draining: bool,
     last_seen: DateTime<Utc>,
 }
 
 impl Member {
-    pub fn new(ip: String, port: String) -> Member {
+    pub fn new(address: Address) -> Member {
         Member {
-            ip,
-            port,
+            address,
             active: false,
+            draining: false,
             last_seen: Utc.timestamp_opt(0, 0).unwrap(),
         }
     }
-    pub fn ip(&self) -> &str {
-        &self.ip
+    /// IP or DNS name of the member, see [Address::host]
+    pub fn host(&self) -> &str {
+        self.address.host()
     }
-    pub fn port(&self) -> &str {
-        &self.port
+    pub fn port(&self) -> u16 {
+        self.address.port()
     }
     pub fn active(&self) -> bool {
         self.active
@@ -46,6 +49,12 @@ impl Member {
     pub fn set_active(&mut self, active: bool) {
         self.active = active;
     }
+    pub fn draining(&self) -> bool {
+        self.draining
+    }
+    pub fn set_draining(&mut self, draining: bool) {
+        self.draining = draining;
+    }
     pub fn last_seen(&self) -> &DateTime<Utc> {
         &self.last_seen
     }
@@ -53,8 +62,8 @@ impl Member {
         let now = Utc::now();
         self.last_seen = now
     }
-    pub fn address(&self) -> String {
-        format!("{}:{}", self.ip, self.port)
+    pub fn address(&self) -> &Address {
+        &self.address
     }
 }
 
@@ -73,23 +82,28 @@ pub trait MembershipStorage: Send + Sync + Clone + Debug {
     /// Saves a new member to the storage
     async fn push(&self, member: Member) -> MembershipUnitResult;
 
-    /// Remove a member by its public ip + port identification
-    async fn remove(&self, ip: &str, port: &str) -> MembershipUnitResult;
+    /// Remove a member by its public address
+    async fn remove(&self, address: &Address) -> MembershipUnitResult;
 
-    /// Changes status for a given Member (lookup by public ip + port)
-    async fn set_is_active(&self, ip: &str, port: &str, is_active: bool) -> MembershipUnitResult;
+    /// Changes status for a given Member (lookup by public address)
+    async fn set_is_active(&self, address: &Address, is_active: bool) -> MembershipUnitResult;
+
+    /// Changes the draining status for a given Member (lookup by public address)
+    ///
+    /// Draining members are still active, but they don't take new objects
+    async fn set_is_draining(&self, address: &Address, is_draining: bool) -> MembershipUnitResult;
 
     /// List all members in the storage
     async fn members(&self) -> MembershipResult<Vec<Member>>;
 
     /// Flag a failure to a given member. Note this method doesn't change the member's activity
     /// status
-    async fn notify_failure(&self, ip: &str, port: &str) -> MembershipUnitResult;
+    async fn notify_failure(&self, address: &Address) -> MembershipUnitResult;
 
     /// List all failures of a given member
     ///
     /// TODO: Limit
-    async fn member_failures(&self, ip: &str, port: &str) -> MembershipResult<Vec<DateTime<Utc>>>;
+    async fn member_failures(&self, address: &Address) -> MembershipResult<Vec<DateTime<Utc>>>;
 
     /// List of active members only
     async fn active_members(&self) -> MembershipResult<Vec<Member>> {
@@ -98,24 +112,24 @@ pub trait MembershipStorage: Send + Sync + Clone + Debug {
         Ok(members)
     }
 
-    /// Tests a member inactive (loopkup by ip + port)
-    async fn is_active(&self, ip: &str, port: &str) -> MembershipResult<bool> {
+    /// Tests a member inactive (loopkup by address)
+    async fn is_active(&self, address: &Address) -> MembershipResult<bool> {
         let active_members = self.active_members().await?;
         for member in active_members {
-            if member.ip == ip && member.port == port {
+            if &member.address == address {
                 return Ok(true);
             }
         }
         Ok(false)
     }
 
-    /// Sets a member inactive (loopkup by ip + port)
-    async fn set_inactive(&self, ip: &str, port: &str) -> MembershipUnitResult {
-        self.set_is_active(ip, port, false).await
+    /// Sets a member inactive (loopkup by address)
+    async fn set_inactive(&self, address: &Address) -> MembershipUnitResult {
+        self.set_is_active(address, false).await
     }
 
-    /// Sets a member active (loopkup by ip + port)
-    async fn set_active(&self, ip: &str, port: &str) -> MembershipUnitResult {
-        self.set_is_active(ip, port, true).await
+    /// Sets a member active (loopkup by address)
+    async fn set_active(&self, address: &Address) -> MembershipUnitResult {
+        self.set_is_active(address, true).await
     }
 }
@@ -8,6 +8,8 @@ use futures::TryFutureExt;
 use sqlx::postgres::{PgPoolOptions, PgRow};
 use sqlx::{self, PgPool, Row};
 
+use crate::address::Address;
+use crate::errors::MembershipError;
 use crate::sql_migration::SqlMigrations;
 
 use super::{Member, MembershipResult, MembershipStorage, MembershipUnitResult};
@@ -16,10 +18,14 @@ pub struct PgMembershipStorageMigrations {}
 
 impl SqlMigrations for PgMembershipStorageMigrations {
     fn queries() -> Vec<String> {
-        let migrations: Vec<_> = include_str!("./migrations/0001-postgres-init.sql")
-            .split(";")
-            .map(|x| x.to_string())
-            .collect();
+        let migrations: Vec<_> = [
+            include_str!("./migrations/0001-postgres-init.sql"),
+            include_str!("./migrations/0002-postgres-draining.sql"),
+        ]
+        .iter()
+        .flat_map(|migration| migration.split(";"))
+        .map(|x| x.to_string())
+        .collect();
         migrations
     }
 }
@@ -55,6 +61,19 @@ impl PostgresMembershipStorage {
     }
 }
 
+/// Builds a [Member] from a `cluster_provider_members` row
+fn member_from_row(row: &PgRow) -> MembershipResult<Member> {
+    let port: String = row.get("port");
+    let port = port
+        .parse()
+        .map_err(|_| MembershipError::DeserializationError)?;
+    let mut member = Member::new(Address::new(row.get::<String, _>("ip"), port));
+    member.last_seen = row.get("last_seen");
+    member.set_active(row.get("active"));
+    member.set_draining(row.get("draining"));
+    Ok(member)
+}
+
 #[async_trait]
 impl MembershipStorage for PostgresMembershipStorage {
     /// Run the schema/data migrations for this membership storage.
@@ -75,36 +94,37 @@ impl MembershipStorage for PostgresMembershipStorage {
         sqlx::query(
             r#"
             INSERT INTO
-                cluster_provider_members (ip, port, last_seen, active)
-            VALUES ($1, $2, $3, $4)
-            ON CONFLICT(ip, port) DO UPDATE SET last_seen=$3, active=$4
+                cluster_provider_members (ip, port, last_seen, active, draining)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT(ip, port) DO UPDATE SET last_seen=$3, active=$4, draining=$5
             "#,
         )
-        .bind(member.ip)
-        .bind(member.port)
+        .bind(member.host())
+        .bind(member.port().to_string())
         .bind(last_seen)
         .bind(member.active)
+        .bind(member.draining)
         .execute(&self.pool)
         .err_into()
         .await
         .map(|_| ())
     }
 
-    async fn remove(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn remove(&self, address: &Address) -> MembershipUnitResult {
         sqlx::query("DELETE FROM cluster_provider_members WHERE ip = $1 AND port = $2")
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .execute(&self.pool)
             .err_into()
             .await
             .map(|_| ())
     }
 
-    async fn set_is_active(&self, ip: &str, port: &str, is_active: bool) -> MembershipUnitResult {
+    async fn set_is_active(&self, address: &Address, is_active: bool) -> MembershipUnitResult {
         let last_seen = Utc::now();
         sqlx::query("UPDATE cluster_provider_members SET active = $3, last_seen = $4 WHERE ip = $1 and port = $2")
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .bind(is_active)
             .bind(last_seen)
             .execute(&self.pool)
@@ -113,47 +133,43 @@ impl MembershipStorage for PostgresMembershipStorage {
             .map(|_| ())
     }
 
+    async fn set_is_draining(&self, address: &Address, is_draining: bool) -> MembershipUnitResult {
+        sqlx::query("UPDATE cluster_provider_members SET draining = $3 WHERE ip = $1 and port = $2")
+            .bind(address.host())
+            .bind(address.port().to_string())
+            .bind(is_draining)
+            .execute(&self.pool)
+            .err_into()
+            .await
+            .map(|_| ())
+    }
+
     async fn members(&self) -> MembershipResult<Vec<Member>> {
         let items = sqlx::query(
-            "SELECT ip, port, active, last_seen FROM cluster_provider_members ORDER BY last_seen DESC",
+            "SELECT ip, port, active, draining, last_seen FROM cluster_provider_members ORDER BY last_seen DESC",
         )
         .fetch_all(&self.pool)
         .await?;
 
-        let items = items
-            .iter()
-            .map(|x| {
-                let mut new_item = Member::new(x.get("ip"), x.get("port"));
-                new_item.last_seen = x.get("last_seen");
-                new_item.set_active(x.get("active"));
-                new_item
-            })
-            .collect();
-        Ok(items)
+        items.iter().map(member_from_row).collect()
     }
 
     async fn active_members(&self) -> MembershipResult<Vec<Member>> {
-        let items = sqlx::query("SELECT ip, port, active, last_seen FROM cluster_provider_members WHERE active ORDER BY last_seen DESC")
-            .map(|x: PgRow| {
-                let mut new_item = Member::new(x.get("ip"), x.get("port"));
-                new_item.last_seen = x.get("last_seen");
-                new_item.set_active(x.get("active"));
-                new_item
-            })
+        let items = sqlx::query("SELECT ip, port, active, draining, last_seen FROM cluster_provider_members WHERE active ORDER BY last_seen DESC")
             .fetch_all(&self.pool)
             .await?;
-        Ok(items)
+        items.iter().map(member_from_row).collect()
     }
 
-    async fn notify_failure(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn notify_failure(&self, address: &Address) -> MembershipUnitResult {
         let query = r#"
             INSERT INTO
                 cluster_provider_member_failures (ip, port)
             VALUES ($1, $2)
         "#;
         sqlx::query(query)
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .execute(&self.pool)
             .err_into()
             .await
@@ -161,7 +177,7 @@ impl MembershipStorage for PostgresMembershipStorage {
     }
 
     /// TODO configure LIMIT
-    async fn member_failures(&self, ip: &str, port: &str) -> MembershipResult<Vec<DateTime<Utc>>> {
+    async fn member_failures(&self, address: &Address) -> MembershipResult<Vec<DateTime<Utc>>> {
         let query = r#"
             SELECT time FROM
                 cluster_provider_member_failures
@@ -169,8 +185,8 @@ impl MembershipStorage for PostgresMembershipStorage {
             ORDER BY time DESC LIMIT 100
         "#;
         sqlx::query(query)
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .map(|x: PgRow| x.get("time"))
             .fetch_all(&self.pool)
             .err_into()
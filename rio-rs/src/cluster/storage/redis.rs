@@ -1,11 +1,10 @@
-use std::fmt::Display;
-
 use async_trait::async_trait;
 use bb8::Builder;
 use bb8_redis::{RedisConnectionManager, bb8::Pool};
 use chrono::{DateTime, Utc};
 use redis::{AsyncCommands, RedisError};
 
+use crate::address::Address;
 use crate::errors::MembershipError;
 
 use super::{Member, MembershipResult, MembershipStorage, MembershipUnitResult};
@@ -21,8 +20,13 @@ impl RedisMembershipStorage {
         format!("{}members", self.key_prefix)
     }
 
-    fn member_failures_key(&self, ip: impl Display, port: impl Display) -> String {
-        format!("{}member_failures;{};{}", self.key_prefix, ip, port)
+    fn member_failures_key(&self, address: &Address) -> String {
+        format!(
+            "{}member_failures;{};{}",
+            self.key_prefix,
+            address.host(),
+            address.port()
+        )
     }
 }
 
@@ -41,32 +45,33 @@ impl RedisMembershipStorage {
     }
 }
 
-fn member_key(ip: impl Display, port: impl Display) -> String {
-    format!("{}:{}", ip, port)
+fn member_key(address: &Address) -> String {
+    address.to_string()
 }
 
 fn member_to_string(member: &Member) -> String {
     let member = format!(
-        "{};{};{};{}",
-        member.ip,
-        member.port,
+        "{};{};{};{};{}",
+        member.host(),
+        member.port(),
         member.active(),
-        member.last_seen().to_rfc3339()
+        member.last_seen().to_rfc3339(),
+        member.draining()
     );
     member
 }
 
 fn parse_member(member: &str) -> MembershipResult<Member> {
     let mut split_member = member.split(";");
-    let ip = split_member
+    let host = split_member
         .next()
-        .ok_or(MembershipError::DeserializationError)?
-        .to_string();
+        .ok_or(MembershipError::DeserializationError)?;
     let port = split_member
         .next()
         .ok_or(MembershipError::DeserializationError)?
-        .to_string();
-    let mut parsed_member = Member::new(ip, port);
+        .parse()
+        .map_err(|_| MembershipError::DeserializationError)?;
+    let mut parsed_member = Member::new(Address::new(host, port));
     parsed_member.active = split_member
         .next()
         .ok_or(MembershipError::DeserializationError)?
@@ -78,6 +83,13 @@ fn parse_member(member: &str) -> MembershipResult<Member> {
     parsed_member.last_seen = DateTime::parse_from_rfc3339(last_seen)
         .map_err(|_| MembershipError::DeserializationError)?
         .to_utc();
+    // Members stored by older versions don't have the draining status
+    parsed_member.draining = split_member
+        .next()
+        .map(|draining| draining.parse())
+        .transpose()
+        .map_err(|_| MembershipError::DeserializationError)?
+        .unwrap_or_default();
     Ok(parsed_member)
 }
 
@@ -85,7 +97,7 @@ fn parse_member(member: &str) -> MembershipResult<Member> {
 impl MembershipStorage for RedisMembershipStorage {
     async fn push(&self, member: Member) -> MembershipUnitResult {
         let mut client = self.pool.get().await?;
-        let member_key = member_key(&member.ip, &member.port);
+        let member_key = member_key(member.address());
         let member_val = member_to_string(&member);
 
         let key = self.members_key();
@@ -93,24 +105,24 @@ impl MembershipStorage for RedisMembershipStorage {
         Ok(())
     }
 
-    async fn remove(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn remove(&self, address: &Address) -> MembershipUnitResult {
         let mut client = self.pool.get().await?;
-        let member_key = member_key(ip, port);
+        let member_key = member_key(address);
         let key = self.members_key();
         let _: () = client.hdel(&key, member_key).await?;
         Ok(())
     }
 
-    async fn set_is_active(&self, ip: &str, port: &str, is_active: bool) -> MembershipUnitResult {
+    async fn set_is_active(&self, address: &Address, is_active: bool) -> MembershipUnitResult {
         let last_seen = Utc::now();
         let mut client = self.pool.get().await?;
-        let member_key = member_key(ip, port);
+        let member_key = member_key(address);
         let key = self.members_key();
         let raw_member: Option<String> = client.hget(&key, &member_key).await?;
         let mut member = raw_member
             .map(|x| parse_member(&x))
             .transpose()?
-            .unwrap_or_else(|| Member::new(ip.to_string(), port.to_string()));
+            .unwrap_or_else(|| Member::new(address.clone()));
         if is_active {
             member.last_seen = last_seen;
         }
@@ -119,6 +131,19 @@ impl MembershipStorage for RedisMembershipStorage {
         Ok(())
     }
 
+    async fn set_is_draining(&self, address: &Address, is_draining: bool) -> MembershipUnitResult {
+        let mut client = self.pool.get().await?;
+        let member_key = member_key(address);
+        let key = self.members_key();
+        let raw_member: Option<String> = client.hget(&key, &member_key).await?;
+        if let Some(raw_member) = raw_member {
+            let mut member = parse_member(&raw_member)?;
+            member.draining = is_draining;
+            self.push(member).await?;
+        }
+        Ok(())
+    }
+
     async fn members(&self) -> MembershipResult<Vec<Member>> {
         let mut client = self.pool.get().await?;
         let key = self.members_key();
@@ -130,9 +155,9 @@ impl MembershipStorage for RedisMembershipStorage {
         Ok(members)
     }
 
-    async fn notify_failure(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn notify_failure(&self, address: &Address) -> MembershipUnitResult {
         let mut client = self.pool.get().await?;
-        let key = self.member_failures_key(ip, port);
+        let key = self.member_failures_key(address);
         let now = chrono::Local::now().to_utc();
         let ts = now.timestamp();
         let _: () = client.rpush(&key, ts).await?;
@@ -140,9 +165,9 @@ impl MembershipStorage for RedisMembershipStorage {
         Ok(())
     }
 
-    async fn member_failures(&self, ip: &str, port: &str) -> MembershipResult<Vec<DateTime<Utc>>> {
+    async fn member_failures(&self, address: &Address) -> MembershipResult<Vec<DateTime<Utc>>> {
         let mut client = self.pool.get().await?;
-        let key = self.member_failures_key(ip, port);
+        let key = self.member_failures_key(address);
         let values: Vec<String> = client.lrange(&key, 0, -1).await?;
         let parsed_values = values
             .iter()
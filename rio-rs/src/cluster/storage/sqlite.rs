@@ -8,6 +8,8 @@ use futures::TryFutureExt;
 use sqlx::sqlite::{SqlitePoolOptions, SqliteRow};
 use sqlx::{self, Row, SqlitePool};
 
+use crate::address::Address;
+use crate::errors::MembershipError;
 use crate::sql_migration::SqlMigrations;
 
 use super::{Member, MembershipResult, MembershipStorage, MembershipUnitResult};
@@ -55,6 +57,19 @@ impl SqliteMembershipStorage {
     }
 }
 
+/// Builds a [Member] from a `cluster_provider_members` row
+fn member_from_row(row: &SqliteRow) -> MembershipResult<Member> {
+    let port: String = row.get("port");
+    let port = port
+        .parse()
+        .map_err(|_| MembershipError::DeserializationError)?;
+    let mut member = Member::new(Address::new(row.get::<String, _>("ip"), port));
+    member.last_seen = row.get("last_seen");
+    member.set_active(row.get("active"));
+    member.set_draining(row.get("draining"));
+    Ok(member)
+}
+
 #[async_trait]
 impl MembershipStorage for SqliteMembershipStorage {
     /// Run the schema/data migrations for this membership storage.
@@ -68,6 +83,21 @@ impl MembershipStorage for SqliteMembershipStorage {
                 .await
                 .unwrap();
         }
+
+        // SQLite can't add a column only if it doesn't exist yet
+        let has_draining: bool = sqlx::query(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('cluster_provider_members') WHERE name = 'draining'",
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .unwrap()
+        .get(0);
+        if !has_draining {
+            sqlx::query(include_str!("./migrations/0002-sqlite-draining.sql"))
+                .execute(&mut *transaction)
+                .await
+                .unwrap();
+        }
         transaction.commit().await.unwrap();
     }
 
@@ -76,36 +106,37 @@ impl MembershipStorage for SqliteMembershipStorage {
         sqlx::query(
             r#"
             INSERT INTO
-                cluster_provider_members (ip, port, last_seen, active)
-            VALUES ($1, $2, $3, $4)
-            ON CONFLICT(ip, port) DO UPDATE SET last_seen=$3, active=$4
+                cluster_provider_members (ip, port, last_seen, active, draining)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT(ip, port) DO UPDATE SET last_seen=$3, active=$4, draining=$5
             "#,
         )
-        .bind(member.ip)
-        .bind(member.port)
+        .bind(member.host())
+        .bind(member.port().to_string())
         .bind(last_seen)
         .bind(member.active)
+        .bind(member.draining)
         .execute(&self.pool)
         .err_into()
         .await
         .map(|_| ())
     }
 
-    async fn remove(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn remove(&self, address: &Address) -> MembershipUnitResult {
         sqlx::query("DELETE FROM cluster_provider_members WHERE ip = $1 AND port = $2")
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .execute(&self.pool)
             .err_into()
             .await
             .map(|_| ())
     }
 
-    async fn set_is_active(&self, ip: &str, port: &str, is_active: bool) -> MembershipUnitResult {
+    async fn set_is_active(&self, address: &Address, is_active: bool) -> MembershipUnitResult {
         let last_seen = Utc::now();
         sqlx::query("UPDATE cluster_provider_members SET active = $3, last_seen = $4 WHERE ip = $1 and port = $2")
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .bind(is_active)
             .bind(last_seen)
             .execute(&self.pool)
@@ -114,47 +145,43 @@ impl MembershipStorage for SqliteMembershipStorage {
             .map(|_| ())
     }
 
+    async fn set_is_draining(&self, address: &Address, is_draining: bool) -> MembershipUnitResult {
+        sqlx::query("UPDATE cluster_provider_members SET draining = $3 WHERE ip = $1 and port = $2")
+            .bind(address.host())
+            .bind(address.port().to_string())
+            .bind(is_draining)
+            .execute(&self.pool)
+            .err_into()
+            .await
+            .map(|_| ())
+    }
+
     async fn members(&self) -> MembershipResult<Vec<Member>> {
         let items = sqlx::query(
-            "SELECT ip, port, active, last_seen FROM cluster_provider_members ORDER BY last_seen DESC",
+            "SELECT ip, port, active, draining, last_seen FROM cluster_provider_members ORDER BY last_seen DESC",
         )
         .fetch_all(&self.pool)
         .await?;
 
-        let items = items
-            .iter()
-            .map(|x| {
-                let mut new_item = Member::new(x.get("ip"), x.get("port"));
-                new_item.last_seen = x.get("last_seen");
-                new_item.set_active(x.get("active"));
-                new_item
-            })
-            .collect();
-        Ok(items)
+        items.iter().map(member_from_row).collect()
     }
 
     async fn active_members(&self) -> MembershipResult<Vec<Member>> {
-        let items = sqlx::query("SELECT ip, port, active, last_seen FROM cluster_provider_members WHERE active ORDER BY last_seen DESC")
-            .map(|x: SqliteRow| {
-                let mut new_item = Member::new(x.get("ip"), x.get("port"));
-                new_item.last_seen = x.get("last_seen");
-                new_item.set_active(x.get("active"));
-                new_item
-            })
+        let items = sqlx::query("SELECT ip, port, active, draining, last_seen FROM cluster_provider_members WHERE active ORDER BY last_seen DESC")
             .fetch_all(&self.pool)
             .await?;
-        Ok(items)
+        items.iter().map(member_from_row).collect()
     }
 
-    async fn notify_failure(&self, ip: &str, port: &str) -> MembershipUnitResult {
+    async fn notify_failure(&self, address: &Address) -> MembershipUnitResult {
         let query = r#"
             INSERT INTO
                 cluster_provider_member_failures (ip, port)
             VALUES ($1, $2)
         "#;
         sqlx::query(query)
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .execute(&self.pool)
             .err_into()
             .await
@@ -162,7 +189,7 @@ impl MembershipStorage for SqliteMembershipStorage {
     }
 
     /// TODO configure LIMIT
-    async fn member_failures(&self, ip: &str, port: &str) -> MembershipResult<Vec<DateTime<Utc>>> {
+    async fn member_failures(&self, address: &Address) -> MembershipResult<Vec<DateTime<Utc>>> {
         let query = r#"
             SELECT time FROM
                 cluster_provider_member_failures
@@ -170,8 +197,8 @@ impl MembershipStorage for SqliteMembershipStorage {
             ORDER BY time DESC LIMIT 100
         "#;
         sqlx::query(query)
-            .bind(ip)
-            .bind(port)
+            .bind(address.host())
+            .bind(address.port().to_string())
             .map(|x: SqliteRow| x.get("time"))
             .fetch_all(&self.pool)
             .err_into()
@@ -195,11 +222,11 @@ mod test {
 
     async fn members_with_value() -> impl MembershipStorage {
         let members_storage = members_storage().await;
-        let mut active_member = Member::new("0.0.0.0".to_string(), "5000".to_string());
+        let mut active_member = Member::new(Address::new("0.0.0.0", 5000));
         active_member.set_active(true);
         members_storage.push(active_member).await.unwrap();
         members_storage
-            .push(Member::new("0.0.0.0".to_string(), "5001".to_string()))
+            .push(Member::new(Address::new("0.0.0.0", 5001)))
             .await
             .unwrap();
         members_storage
@@ -209,7 +236,7 @@ mod test {
     async fn test_insert() {
         let members_storage = members_storage().await;
         members_storage
-            .push(Member::new("0.0.0.0".to_string(), "5000".to_string()))
+            .push(Member::new(Address::new("0.0.0.0", 5000)))
             .await
             .unwrap();
     }
@@ -224,7 +251,10 @@ mod test {
     #[tokio::test]
     async fn test_remove() {
         let members_storage = members_with_value().await;
-        members_storage.remove("0.0.0.0", "5001").await.unwrap();
+        members_storage
+            .remove(&Address::new("0.0.0.0", 5001))
+            .await
+            .unwrap();
         let members = members_storage.members().await.unwrap();
         assert_eq!(members.len(), 1);
     }
@@ -241,7 +271,7 @@ mod test {
         let members_storage = members_storage().await;
         let t0 = Utc::now();
 
-        let mut active_member = Member::new("0.0.0.0".to_string(), "5000".to_string());
+        let mut active_member = Member::new(Address::new("0.0.0.0", 5000));
         active_member.set_active(true);
         members_storage.push(active_member).await.unwrap();
         let members = members_storage.members().await.unwrap();
@@ -251,4 +281,16 @@ mod test {
         assert!(member.last_seen() > &t0);
         assert!(member.last_seen() < &t1);
     }
+
+    #[tokio::test]
+    async fn test_prepare_is_idempotent() {
+        let members_storage = members_with_value().await;
+        members_storage.prepare().await;
+        members_storage
+            .set_is_draining(&Address::new("0.0.0.0", 5000), true)
+            .await
+            .unwrap();
+        let members = members_storage.active_members().await.unwrap();
+        assert!(members[0].draining());
+    }
 }
@@ -0,0 +1,37 @@
+//! [Codec] using bincode 1.x
+//!
+//! This is the default codec, compatible with `bincode::serialize` and `bincode::deserialize`
+
+use ::bincode::Options;
+
+use super::{Codec, DeserializeFn};
+use crate::errors::CodecError;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn serialize_erased(&self, value: &dyn erased_serde::Serialize) -> Result<Vec<u8>, CodecError> {
+        ::bincode::serialize(value).map_err(|e| CodecError::Serialization(e.to_string()))
+    }
+
+    fn deserialize_erased(
+        &self,
+        bytes: &[u8],
+        visit: &mut DeserializeFn<'_>,
+    ) -> Result<(), CodecError> {
+        // Same options as `bincode::deserialize`
+        let options = ::bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes();
+        let mut deserializer = ::bincode::Deserializer::from_slice(bytes, options);
+        visit(&mut <dyn erased_serde::Deserializer>::erase(
+            &mut deserializer,
+        ))
+        .map_err(|e| CodecError::Deserialization(e.to_string()))
+    }
+}
@@ -0,0 +1,51 @@
+//! [Codec] using JSON
+//!
+//! Handy for consumers that are not written in Rust, although the envelopes around the
+//! payloads are still bincode (see [Codec])
+
+use super::{Codec, DeserializeFn};
+use crate::errors::CodecError;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn serialize_erased(&self, value: &dyn erased_serde::Serialize) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|e| CodecError::Serialization(e.to_string()))
+    }
+
+    fn deserialize_erased(
+        &self,
+        bytes: &[u8],
+        visit: &mut DeserializeFn<'_>,
+    ) -> Result<(), CodecError> {
+        let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+        visit(&mut <dyn erased_serde::Deserializer>::erase(
+            &mut deserializer,
+        ))
+        .map_err(|e| CodecError::Deserialization(e.to_string()))?;
+        // Only whitespace can follow the value
+        deserializer
+            .end()
+            .map_err(|e| CodecError::Deserialization(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_trailing_bytes() {
+        let codec: &dyn Codec = &JsonCodec;
+        let value: Vec<u8> = codec.deserialize(b"[1, 2] ").unwrap();
+        assert_eq!(value, vec![1, 2]);
+
+        let result: Result<Vec<u8>, _> = codec.deserialize(b"[1, 2] [3]");
+        assert!(matches!(result, Err(CodecError::Deserialization(_))));
+    }
+}
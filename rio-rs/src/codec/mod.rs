@@ -0,0 +1,151 @@
+//! Serialization of the messages exchanged between clients and servers
+//!
+//! The [Codec] is used for the application payloads: messages, their responses, the
+//! handlers' errors and the items published to subscriptions. The envelopes around
+//! these payloads are part of the wire protocol, and are always serialized with bincode.
+//!
+//! The codec is picked on the [Registry](crate::registry::Registry) for the server,
+//! and on the [ClientBuilder](crate::client::ClientBuilder) for the clients. Both need to
+//! use the same codec, which is checked when the client connects to a server.
+//!
+//! ```rust
+//! # use rio_rs::codec::{Codec, json::JsonCodec};
+//! let codec: &dyn Codec = &JsonCodec;
+//! let bytes = codec.serialize(&vec![1, 2, 3]).unwrap();
+//! assert_eq!(bytes, b"[1,2,3]");
+//!
+//! let value: Vec<u8> = codec.deserialize(&bytes).unwrap();
+//! assert_eq!(value, vec![1, 2, 3]);
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::errors::CodecError;
+
+pub mod bincode;
+pub mod json;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "postcard")]
+pub mod postcard;
+
+/// Callback that drives a type-erased deserializer
+pub type DeserializeFn<'a> =
+    dyn FnMut(&mut dyn erased_serde::Deserializer<'_>) -> Result<(), erased_serde::Error> + 'a;
+
+/// Serialization format for messages, responses and errors
+///
+/// It works over type-erased values so it can be shared as a trait object
+/// (`Arc<dyn Codec>`). Use [`<dyn Codec>::serialize`](#method.serialize) and
+/// [`<dyn Codec>::deserialize`](#method.deserialize) to work with concrete types.
+///
+/// <div class="warning">
+/// The codec only covers the payloads. The handshake, the envelopes that carry the
+/// payloads and the protocol errors are always bincode 1.x, whatever the codec. A client
+/// written in another language needs to speak bincode for those, even with the JSON or
+/// MessagePack codecs
+/// </div>
+pub trait Codec: Debug + Send + Sync {
+    /// Name of the codec, used to check that clients and servers use the same one
+    fn name(&self) -> &'static str;
+
+    /// Serializes a type-erased value
+    fn serialize_erased(&self, value: &dyn erased_serde::Serialize) -> Result<Vec<u8>, CodecError>;
+
+    /// Builds a deserializer over `bytes` and hands it to `visit`
+    fn deserialize_erased(
+        &self,
+        bytes: &[u8],
+        visit: &mut DeserializeFn<'_>,
+    ) -> Result<(), CodecError>;
+}
+
+impl dyn Codec + '_ {
+    /// Serializes `value`
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        self.serialize_erased(value)
+    }
+
+    /// Deserializes a `T` from `bytes`
+    pub fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        let mut value = None;
+        self.deserialize_erased(bytes, &mut |deserializer| {
+            value = Some(erased_serde::deserialize::<T>(deserializer)?);
+            Ok(())
+        })?;
+        value.ok_or_else(|| CodecError::Deserialization("no value was deserialized".to_string()))
+    }
+}
+
+/// Codec used when none is given
+pub fn default_codec() -> Arc<dyn Codec> {
+    Arc::new(bincode::BincodeCodec)
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        text: String,
+        values: Vec<u32>,
+        nested: Option<Box<Message>>,
+    }
+
+    fn message() -> Message {
+        Message {
+            text: "hi".to_string(),
+            values: vec![1, 2, 3],
+            nested: Some(Box::new(Message {
+                text: "nested".to_string(),
+                values: vec![],
+                nested: None,
+            })),
+        }
+    }
+
+    fn codecs() -> Vec<Arc<dyn Codec>> {
+        vec![
+            Arc::new(bincode::BincodeCodec),
+            Arc::new(json::JsonCodec),
+            #[cfg(feature = "msgpack")]
+            Arc::new(msgpack::MessagePackCodec),
+            #[cfg(feature = "postcard")]
+            Arc::new(postcard::PostcardCodec),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for codec in codecs() {
+            let bytes = codec.serialize(&message()).unwrap();
+            let value: Message = codec.deserialize(&bytes).unwrap();
+            assert_eq!(value, message(), "{}", codec.name());
+        }
+    }
+
+    #[test]
+    fn test_deserialization_error() {
+        for codec in codecs() {
+            let result: Result<Message, _> = codec.deserialize(b"\xff");
+            assert!(
+                matches!(result, Err(CodecError::Deserialization(_))),
+                "{}",
+                codec.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_bincode_compatibility() {
+        let bytes = default_codec().serialize(&message()).unwrap();
+        assert_eq!(bytes, ::bincode::serialize(&message()).unwrap());
+    }
+}
@@ -0,0 +1,32 @@
+//! [Codec] using MessagePack
+//!
+//! Structs are serialized as maps, so fields can be added or reordered without
+//! breaking older readers
+
+use super::{Codec, DeserializeFn};
+use crate::errors::CodecError;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MessagePackCodec;
+
+impl Codec for MessagePackCodec {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn serialize_erased(&self, value: &dyn erased_serde::Serialize) -> Result<Vec<u8>, CodecError> {
+        rmp_serde::to_vec_named(value).map_err(|e| CodecError::Serialization(e.to_string()))
+    }
+
+    fn deserialize_erased(
+        &self,
+        bytes: &[u8],
+        visit: &mut DeserializeFn<'_>,
+    ) -> Result<(), CodecError> {
+        let mut deserializer = rmp_serde::Deserializer::new(bytes);
+        visit(&mut <dyn erased_serde::Deserializer>::erase(
+            &mut deserializer,
+        ))
+        .map_err(|e| CodecError::Deserialization(e.to_string()))
+    }
+}
@@ -0,0 +1,29 @@
+//! [Codec] using postcard
+
+use super::{Codec, DeserializeFn};
+use crate::errors::CodecError;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PostcardCodec;
+
+impl Codec for PostcardCodec {
+    fn name(&self) -> &'static str {
+        "postcard"
+    }
+
+    fn serialize_erased(&self, value: &dyn erased_serde::Serialize) -> Result<Vec<u8>, CodecError> {
+        ::postcard::to_stdvec(value).map_err(|e| CodecError::Serialization(e.to_string()))
+    }
+
+    fn deserialize_erased(
+        &self,
+        bytes: &[u8],
+        visit: &mut DeserializeFn<'_>,
+    ) -> Result<(), CodecError> {
+        let mut deserializer = ::postcard::Deserializer::from_bytes(bytes);
+        visit(&mut <dyn erased_serde::Deserializer>::erase(
+            &mut deserializer,
+        ))
+        .map_err(|e| CodecError::Deserialization(e.to_string()))
+    }
+}
@@ -25,6 +25,12 @@ pub enum HandlerError {
 
     #[error("error caused internally by the application")]
     ApplicationError(Vec<u8>),
+
+    #[error("object mailbox is full")]
+    Overloaded,
+
+    #[error("object is being deactivated")]
+    MailboxClosed,
 }
 
 /// Represents errors that occur in the lifecyle functions of an object.
@@ -139,6 +145,35 @@ pub enum ObjectPlacementError {
 
     #[error("unknown")]
     Unknown(String),
+
+    /// The placement was stored with an address that can't be parsed
+    #[error("invalid address")]
+    InvalidAddress(AddressError),
+
+    /// The [ObjectPlacement](crate::object_placement::ObjectPlacement) doesn't implement
+    /// the operation
+    #[error("unsupported operation `{0}`")]
+    Unsupported(String),
+}
+
+impl From<AddressError> for ObjectPlacementError {
+    fn from(err: AddressError) -> Self {
+        ObjectPlacementError::InvalidAddress(err)
+    }
+}
+
+/// Errors parsing an [Address](crate::address::Address)
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    #[error("missing port in `{0}`")]
+    MissingPort(String),
+
+    #[error("invalid port in `{0}`")]
+    InvalidPort(String),
+
+    /// The host is empty, or an IPv6 address without brackets
+    #[error("invalid host in `{0}`")]
+    InvalidHost(String),
 }
 
 #[cfg(feature = "sql")]
@@ -162,6 +197,52 @@ impl From<bb8::RunError<redis::RedisError>> for ObjectPlacementError {
     }
 }
 
+/// Errors reading the header of a frame
+/// ([crate::protocol::frame])
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    #[error("frame is too short to contain a header")]
+    Truncated,
+
+    #[error("unknown frame kind {0}")]
+    UnknownKind(u8),
+
+    #[error("unsupported protocol version {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("unknown compression {0}")]
+    UnknownCompression(u8),
+
+    #[error("compression error: {0}")]
+    Compression(String),
+}
+
+/// Errors setting up TLS or running its handshake
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum TlsError {
+    #[error("invalid PEM: {0}")]
+    InvalidPem(String),
+
+    #[error("invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    #[error("invalid TLS configuration: {0}")]
+    Config(String),
+
+    #[error("TLS handshake failed: {0}")]
+    Handshake(String),
+}
+
+/// Errors from a [crate::codec::Codec]
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum CodecError {
+    #[error("serialization error: {0}")]
+    Serialization(String),
+
+    #[error("deserialization error: {0}")]
+    Deserialization(String),
+}
+
 /// Error type for service object state management
 #[derive(Error, Debug, PartialEq)]
 pub enum LoadStateError {
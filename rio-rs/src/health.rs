@@ -0,0 +1,248 @@
+//! Liveness and readiness of a [Server](crate::server::Server)
+//!
+//! A [HealthCheck] is obtained through [Server::health_check](crate::server::Server::health_check),
+//! and it can be queried while the server runs. With the `http` feature, the server can also
+//! expose it over HTTP (see `http_health_address` on [Server::builder](crate::server::Server::builder)):
+//!
+//! - `GET /health/live`: `200` while the process is up
+//! - `GET /health/ready`: `200` if the server is ready to take requests, `503` otherwise
+//! - `GET /health`: the full [HealthReport], as JSON
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::ObjectId;
+use crate::cluster::storage::MembershipStorage;
+use crate::object_placement::ObjectPlacement;
+use crate::registry::Registry;
+use crate::server::{DrainHandle, ShutdownHandle};
+
+/// Counters a server updates while it runs, shared with its [HealthCheck]s
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ServerStats {
+    /// Open client connections
+    pub(crate) connections: Arc<AtomicUsize>,
+    /// Whether the [ClusterProvider](crate::cluster::membership_protocol::ClusterProvider)
+    /// is serving
+    pub(crate) cluster_provider_running: Arc<AtomicBool>,
+}
+
+/// Decrements [ServerStats::connections] once the connection is dropped
+pub(crate) struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ServerStats {
+    /// Counts a new connection, until the returned guard is dropped
+    pub(crate) fn connection(&self) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.connections.clone())
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Result of the checks a server needs to pass to be ready
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessChecks {
+    /// The [MembershipStorage] can be reached
+    pub membership_storage: bool,
+    /// The [ObjectPlacement] can be reached
+    pub object_placement: bool,
+    /// The [ClusterProvider](crate::cluster::membership_protocol::ClusterProvider) is running
+    pub cluster_provider: bool,
+    /// The server isn't draining (see [Server::drain](crate::server::Server::drain)) nor
+    /// shutting down
+    pub not_draining: bool,
+}
+
+impl ReadinessChecks {
+    /// Whether all the checks pass
+    pub fn is_ready(&self) -> bool {
+        self.membership_storage
+            && self.object_placement
+            && self.cluster_provider
+            && self.not_draining
+    }
+}
+
+/// Health of a server at a point in time, see [HealthCheck::report]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Always `true`, as long as there is something to answer
+    pub live: bool,
+    /// Whether the server is ready to take requests, see [ReadinessChecks::is_ready]
+    pub ready: bool,
+    pub checks: ReadinessChecks,
+    /// Number of active objects
+    pub activations: usize,
+    /// Number of active objects per type
+    pub activations_by_type: BTreeMap<String, usize>,
+    /// Number of open client connections
+    pub connections: usize,
+}
+
+/// Queries the health of a running server
+///
+/// It can be cloned, and it is obtained through
+/// [Server::health_check](crate::server::Server::health_check)
+#[derive(Clone)]
+pub struct HealthCheck<S: MembershipStorage, P: ObjectPlacement> {
+    pub(crate) members_storage: S,
+    pub(crate) object_placement_provider: Arc<RwLock<P>>,
+    pub(crate) registry: Arc<RwLock<Registry>>,
+    pub(crate) shutdown: ShutdownHandle,
+    pub(crate) draining: DrainHandle,
+    pub(crate) stats: ServerStats,
+}
+
+impl<S: MembershipStorage, P: ObjectPlacement> HealthCheck<S, P> {
+    /// Runs the readiness checks
+    ///
+    /// The storages are checked by querying them, so it might take as long as they take
+    /// to answer
+    pub async fn readiness(&self) -> ReadinessChecks {
+        let membership_storage = self.members_storage.active_members().await.is_ok();
+        // Any lookup will do, it only needs to reach the backend
+        let object_placement = self
+            .object_placement_provider
+            .read()
+            .await
+            .lookup(&ObjectId::new("", ""))
+            .await
+            .is_ok();
+        ReadinessChecks {
+            membership_storage,
+            object_placement,
+            cluster_provider: self.stats.cluster_provider_running.load(Ordering::Relaxed),
+            not_draining: !self.shutdown.is_shutdown() && !self.draining.is_draining(),
+        }
+    }
+
+    /// Runs the readiness checks and collects the server counts
+    pub async fn report(&self) -> HealthReport {
+        let checks = self.readiness().await;
+        let objects = self.registry.read().await.objects();
+        let mut activations_by_type = BTreeMap::new();
+        for (object_kind, _) in objects.iter() {
+            *activations_by_type.entry(object_kind.clone()).or_default() += 1;
+        }
+        HealthReport {
+            live: true,
+            ready: checks.is_ready(),
+            checks,
+            activations: objects.len(),
+            activations_by_type,
+            connections: self.stats.connections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Serves the health endpoints over HTTP, see the [module docs](self)
+#[cfg(feature = "http")]
+pub async fn serve<S, P>(
+    bind: impl tokio::net::ToSocketAddrs,
+    health_check: HealthCheck<S, P>,
+) -> std::io::Result<()>
+where
+    S: MembershipStorage + 'static,
+    P: ObjectPlacement + 'static,
+{
+    use axum::{Json, Router, extract::State, http::StatusCode, routing};
+
+    async fn live() -> StatusCode {
+        StatusCode::OK
+    }
+
+    async fn ready<S, P>(State(health_check): State<HealthCheck<S, P>>) -> StatusCode
+    where
+        S: MembershipStorage + 'static,
+        P: ObjectPlacement + 'static,
+    {
+        if health_check.readiness().await.is_ready() {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+
+    async fn report<S, P>(
+        State(health_check): State<HealthCheck<S, P>>,
+    ) -> (StatusCode, Json<HealthReport>)
+    where
+        S: MembershipStorage + 'static,
+        P: ObjectPlacement + 'static,
+    {
+        let report = health_check.report().await;
+        let status = if report.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status, Json(report))
+    }
+
+    let app = Router::new()
+        .route("/health/live", routing::get(live))
+        .route("/health/ready", routing::get(ready::<S, P>))
+        .route("/health", routing::get(report::<S, P>))
+        .with_state(health_check);
+
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    axum::serve(listener, app).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cluster::storage::local::LocalStorage;
+    use crate::object_placement::local::LocalObjectPlacement;
+
+    fn health_check() -> HealthCheck<LocalStorage, LocalObjectPlacement> {
+        HealthCheck {
+            members_storage: LocalStorage::default(),
+            object_placement_provider: Arc::new(RwLock::new(LocalObjectPlacement::default())),
+            registry: Arc::new(RwLock::new(Registry::new())),
+            shutdown: ShutdownHandle::default(),
+            draining: DrainHandle::default(),
+            stats: ServerStats::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let health_check = health_check();
+        let report = health_check.report().await;
+        assert!(report.live);
+        // The cluster provider isn't running
+        assert!(!report.ready);
+
+        health_check
+            .stats
+            .cluster_provider_running
+            .store(true, Ordering::Relaxed);
+        let connection = health_check.stats.connection();
+        let report = health_check.report().await;
+        assert!(report.ready);
+        assert_eq!(report.connections, 1);
+        assert_eq!(report.activations, 0);
+
+        health_check.draining.drain();
+        let report = health_check.report().await;
+        assert!(!report.ready);
+        health_check.draining.resume();
+
+        drop(connection);
+        health_check.shutdown.shutdown();
+        let report = health_check.report().await;
+        assert!(!report.ready);
+        assert!(!report.checks.not_draining);
+        assert_eq!(report.connections, 0);
+    }
+}
@@ -191,13 +191,20 @@
 // #![warn(missing_docs)]
 #![deny(rustdoc::missing_crate_level_docs)]
 
+pub mod address;
+#[cfg(feature = "http")]
+pub mod admin;
 pub mod app_data;
 pub mod client;
 pub mod cluster;
+pub mod codec;
 pub mod errors;
+pub mod health;
 pub mod message_router;
+pub mod metrics;
 pub mod object_placement;
 pub mod protocol;
+pub mod rebalance;
 pub mod registry;
 pub mod server;
 pub mod service;
@@ -205,6 +212,9 @@ pub mod service_object;
 #[cfg(feature = "sql")]
 pub mod sql_migration;
 pub mod state;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transport;
 
 pub use service_object::*;
 
@@ -218,6 +228,7 @@ pub mod derive {
 }
 
 pub mod prelude {
+    pub use super::address::Address;
     pub use super::app_data::AppData;
     pub use super::client::ClientBuilder;
     pub use super::cluster::membership_protocol::ClusterProvider;
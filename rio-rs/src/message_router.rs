@@ -4,7 +4,7 @@
 //!
 //! # TODO
 //! - [ ] This component might be temporary. It serves as a router between different publishers and subscribers
-//! - [ ] I need a way to remove unused channels (use LRU)
+//! - [x] I need a way to remove unused channels
 //! - [ ] Configure channel limits
 //!
 //! </div>
@@ -34,6 +34,20 @@ impl MessageRouter {
         sender.subscribe()
     }
 
+    /// Removes the channel for `(k1, k2)` if it has no subscribers left
+    ///
+    /// It needs to be called after dropping the receivers returned by
+    /// [MessageRouter::create_subscription]
+    pub fn remove_unused(&self, k1: String, k2: String) {
+        self.inboxes
+            .remove_if(&(k1, k2), |_, sender| sender.receiver_count() == 0);
+    }
+
+    /// Whether there is a channel for `(k1, k2)`
+    pub fn has_subscription(&self, k1: String, k2: String) -> bool {
+        self.inboxes.contains_key(&(k1, k2))
+    }
+
     pub fn publish(&self, k1: String, k2: String, message: SubscriptionResponse) {
         let maybe_sender = self.inboxes.get_mut(&(k1, k2));
         if let Some(sender) = maybe_sender {
@@ -0,0 +1,301 @@
+//! Prometheus metrics for the server, the registry and the client
+//!
+//! The metrics are only collected with the `prometheus` feature, otherwise recording them
+//! is a no-op. They are kept in a registry per process, rendered by [gather], and served by
+//! [serve] (see `http_metrics_address` on [Server::builder](crate::server::Server::builder)).
+//!
+//! | Metric | Type | Labels |
+//! |--------|------|--------|
+//! | `rio_requests_total` | counter | `type`, `message`, `result` |
+//! | `rio_handler_duration_seconds` | histogram | `type`, `message` |
+//! | `rio_mailbox_wait_seconds` | histogram | `type` |
+//! | `rio_mailbox_messages` | gauge | `type` |
+//! | `rio_mailbox_overloads_total` | counter | `type` |
+//! | `rio_active_objects` | gauge | `type` |
+//! | `rio_redirects_total` | counter | |
+//! | `rio_placement_lookups_total` | counter | `result` (`found`, `not_found`, `error`) |
+//! | `rio_membership_checks_total` | counter | `result` (`active`, `inactive`, `error`) |
+//! | `rio_client_retries_total` | counter | `reason` (`redirect`, `retryable`) |
+//! | `rio_migrations_total` | counter | `result` (`ok`, `error`) |
+//!
+//! The `result` of `rio_requests_total` is either `ok`, or the [ErrorCode] of the failure.
+//! Its `type` and `message` are [UNKNOWN] for the requests whose handler isn't registered,
+//! so the clients can't create new series
+
+use std::time::Duration;
+
+use crate::protocol::error_code::ErrorCode;
+
+/// Label of the type and message of the requests whose handler isn't registered
+pub const UNKNOWN: &str = "unknown";
+
+#[cfg(feature = "prometheus")]
+mod collectors {
+    use std::sync::LazyLock;
+
+    use prometheus::{
+        HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    };
+
+    pub(super) struct Collectors {
+        pub(super) registry: Registry,
+        pub(super) requests: IntCounterVec,
+        pub(super) handler_duration: HistogramVec,
+        pub(super) mailbox_wait: HistogramVec,
+        pub(super) mailbox_messages: IntGaugeVec,
+        pub(super) mailbox_overloads: IntCounterVec,
+        pub(super) active_objects: IntGaugeVec,
+        pub(super) redirects: IntCounter,
+        pub(super) placement_lookups: IntCounterVec,
+        pub(super) membership_checks: IntCounterVec,
+        pub(super) client_retries: IntCounterVec,
+        pub(super) migrations: IntCounterVec,
+    }
+
+    impl Collectors {
+        fn new() -> prometheus::Result<Self> {
+            let registry = Registry::new_custom(Some("rio".to_string()), None)?;
+            let requests = IntCounterVec::new(
+                Opts::new("requests_total", "Requests handled by the server"),
+                &["type", "message", "result"],
+            )?;
+            let handler_duration = HistogramVec::new(
+                HistogramOpts::new("handler_duration_seconds", "Time spent in the handlers"),
+                &["type", "message"],
+            )?;
+            let mailbox_wait = HistogramVec::new(
+                HistogramOpts::new(
+                    "mailbox_wait_seconds",
+                    "Time the messages spent in the objects' mailboxes before being handled",
+                ),
+                &["type"],
+            )?;
+            let mailbox_messages = IntGaugeVec::new(
+                Opts::new(
+                    "mailbox_messages",
+                    "Messages waiting in the objects' mailboxes",
+                ),
+                &["type"],
+            )?;
+            let mailbox_overloads = IntCounterVec::new(
+                Opts::new(
+                    "mailbox_overloads_total",
+                    "Messages refused because the object's mailbox was full",
+                ),
+                &["type"],
+            )?;
+            let active_objects = IntGaugeVec::new(
+                Opts::new("active_objects", "Objects active in the registry"),
+                &["type"],
+            )?;
+            let redirects = IntCounter::new(
+                "redirects_total",
+                "Requests redirected to the server holding the object",
+            )?;
+            let placement_lookups = IntCounterVec::new(
+                Opts::new("placement_lookups_total", "Lookups on the object placement"),
+                &["result"],
+            )?;
+            let membership_checks = IntCounterVec::new(
+                Opts::new(
+                    "membership_checks_total",
+                    "Checks on whether a member of the cluster is active",
+                ),
+                &["result"],
+            )?;
+            let client_retries = IntCounterVec::new(
+                Opts::new("client_retries_total", "Requests retried by the client"),
+                &["reason"],
+            )?;
+            let migrations = IntCounterVec::new(
+                Opts::new(
+                    "migrations_total",
+                    "Objects moved to other servers by the rebalancer",
+                ),
+                &["result"],
+            )?;
+
+            registry.register(Box::new(requests.clone()))?;
+            registry.register(Box::new(handler_duration.clone()))?;
+            registry.register(Box::new(mailbox_wait.clone()))?;
+            registry.register(Box::new(mailbox_messages.clone()))?;
+            registry.register(Box::new(mailbox_overloads.clone()))?;
+            registry.register(Box::new(active_objects.clone()))?;
+            registry.register(Box::new(redirects.clone()))?;
+            registry.register(Box::new(placement_lookups.clone()))?;
+            registry.register(Box::new(membership_checks.clone()))?;
+            registry.register(Box::new(client_retries.clone()))?;
+            registry.register(Box::new(migrations.clone()))?;
+
+            Ok(Collectors {
+                registry,
+                requests,
+                handler_duration,
+                mailbox_wait,
+                mailbox_messages,
+                mailbox_overloads,
+                active_objects,
+                redirects,
+                placement_lookups,
+                membership_checks,
+                client_retries,
+                migrations,
+            })
+        }
+    }
+
+    pub(super) static COLLECTORS: LazyLock<Collectors> =
+        LazyLock::new(|| Collectors::new().expect("Invalid metric definitions"));
+}
+
+/// Renders all the metrics in the Prometheus text format
+#[cfg(feature = "prometheus")]
+pub fn gather() -> String {
+    use prometheus::Encoder;
+
+    let metric_families = collectors::COLLECTORS.registry.gather();
+    let mut buffer = vec![];
+    prometheus::TextEncoder::new()
+        .encode(&metric_families, &mut buffer)
+        .expect("Writing to a Vec can't fail");
+    String::from_utf8(buffer).expect("The text format is UTF-8")
+}
+
+/// Serves [gather] on `GET /metrics`
+#[cfg(all(feature = "prometheus", feature = "http"))]
+pub async fn serve(bind: impl tokio::net::ToSocketAddrs) -> std::io::Result<()> {
+    use axum::{Router, http::header, routing};
+
+    async fn metrics() -> ([(header::HeaderName, &'static str); 1], String) {
+        ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], gather())
+    }
+
+    let app = Router::new().route("/metrics", routing::get(metrics));
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    axum::serve(listener, app).await
+}
+
+/// Counts a request handled by the server, see [ErrorCode] for the failures
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn request(handler_type: &str, message_type: &str, result: Result<(), ErrorCode>) {
+    #[cfg(feature = "prometheus")]
+    {
+        let result = match result {
+            Ok(()) => "ok".to_string(),
+            Err(code) => format!("{:?}", code),
+        };
+        collectors::COLLECTORS
+            .requests
+            .with_label_values(&[handler_type, message_type, &result])
+            .inc();
+    }
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn handler_duration(handler_type: &str, message_type: &str, duration: Duration) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .handler_duration
+        .with_label_values(&[handler_type, message_type])
+        .observe(duration.as_secs_f64());
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn mailbox_wait(handler_type: &str, duration: Duration) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .mailbox_wait
+        .with_label_values(&[handler_type])
+        .observe(duration.as_secs_f64());
+}
+
+/// Adds `delta` to the messages waiting in the mailboxes of `handler_type`
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn mailbox_depth(handler_type: &str, delta: i64) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .mailbox_messages
+        .with_label_values(&[handler_type])
+        .add(delta);
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn mailbox_overload(handler_type: &str) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .mailbox_overloads
+        .with_label_values(&[handler_type])
+        .inc();
+}
+
+/// Adds `delta` to the active objects of `handler_type`
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn active_objects(handler_type: &str, delta: i64) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .active_objects
+        .with_label_values(&[handler_type])
+        .add(delta);
+}
+
+pub(crate) fn redirect() {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS.redirects.inc();
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn placement_lookup(result: &'static str) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .placement_lookups
+        .with_label_values(&[result])
+        .inc();
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn membership_check(result: &'static str) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .membership_checks
+        .with_label_values(&[result])
+        .inc();
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn client_retry(reason: &'static str) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .client_retries
+        .with_label_values(&[reason])
+        .inc();
+}
+
+#[cfg_attr(not(feature = "prometheus"), allow(unused_variables))]
+pub(crate) fn migration(result: &'static str) {
+    #[cfg(feature = "prometheus")]
+    collectors::COLLECTORS
+        .migrations
+        .with_label_values(&[result])
+        .inc();
+}
+
+#[cfg(all(test, feature = "prometheus"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gather() {
+        request("MetricsTestType", "Ping", Ok(()));
+        request("MetricsTestType", "Ping", Err(ErrorCode::Redirect));
+        active_objects("MetricsTestType", 1);
+
+        let text = gather();
+        assert!(text.contains(
+            r#"rio_requests_total{message="Ping",result="ok",type="MetricsTestType"} 1"#
+        ));
+        assert!(text.contains(
+            r#"rio_requests_total{message="Ping",result="Redirect",type="MetricsTestType"} 1"#
+        ));
+        assert!(text.contains(r#"rio_active_objects{type="MetricsTestType"} 1"#));
+    }
+}
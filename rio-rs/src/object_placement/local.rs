@@ -6,10 +6,11 @@ use std::sync::{Arc, RwLock};
 use async_trait::async_trait;
 
 use crate::ObjectId;
+use crate::address::Address;
 use crate::errors::ObjectPlacementError;
 use crate::object_placement::{ObjectPlacement, ObjectPlacementItem};
 
-type PlacementMap = Arc<RwLock<HashMap<String, String>>>;
+type PlacementMap = Arc<RwLock<HashMap<ObjectId, Address>>>;
 
 /// In-memory implementation of the trait [ObjectPlacement]
 #[derive(Default, Clone, Debug)]
@@ -23,32 +24,28 @@ impl ObjectPlacement for LocalObjectPlacement {
         &self,
         object_placement: ObjectPlacementItem,
     ) -> Result<(), ObjectPlacementError> {
-        let object_id = format!(
-            "{}.{}",
-            object_placement.object_id.0, object_placement.object_id.1
-        );
+        let object_id = object_placement.object_id;
         let mut placement_guard = self
             .placement
             .write()
             .map_err(|e| ObjectPlacementError::Unknown(e.to_string()))?;
         if let Some(address) = object_placement.server_address {
-            *placement_guard.entry(object_id).or_default() = address;
+            placement_guard.insert(object_id, address);
         } else {
             placement_guard.remove(&object_id);
         }
         Ok(())
     }
 
-    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<String>, ObjectPlacementError> {
-        let object_id = format!("{}.{}", object_id.0, object_id.1);
+    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<Address>, ObjectPlacementError> {
         let placement_guard = self
             .placement
             .read()
             .map_err(|e| ObjectPlacementError::Unknown(e.to_string()))?;
-        Ok(placement_guard.get(&object_id).cloned())
+        Ok(placement_guard.get(object_id).cloned())
     }
 
-    async fn clean_server(&self, address: String) -> Result<(), ObjectPlacementError> {
+    async fn clean_server(&self, address: Address) -> Result<(), ObjectPlacementError> {
         let mut placement_guard = self
             .placement
             .write()
@@ -58,14 +55,29 @@ impl ObjectPlacement for LocalObjectPlacement {
     }
 
     async fn remove(&self, object_id: &ObjectId) -> Result<(), ObjectPlacementError> {
-        let object_id = format!("{}.{}", object_id.0, object_id.1);
         let mut placement_guard = self
             .placement
             .write()
             .map_err(|e| ObjectPlacementError::Unknown(e.to_string()))?;
-        placement_guard.remove(&object_id);
+        placement_guard.remove(object_id);
         Ok(())
     }
+
+    async fn server_objects(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<ObjectId>, ObjectPlacementError> {
+        let placement_guard = self
+            .placement
+            .read()
+            .map_err(|e| ObjectPlacementError::Unknown(e.to_string()))?;
+        let objects = placement_guard
+            .iter()
+            .filter(|(_, v)| *v == address)
+            .map(|(object_id, _)| object_id.clone())
+            .collect();
+        Ok(objects)
+    }
 }
 
 #[cfg(test)]
@@ -80,7 +92,7 @@ mod test {
         provider
             .update(ObjectPlacementItem::new(
                 ObjectId("test".to_string(), "1".to_string()),
-                Some("0.0.0.0:80".to_string()),
+                Some(Address::new("0.0.0.0", 80)),
             ))
             .await
             .unwrap();
@@ -101,7 +113,7 @@ mod test {
         );
 
         cloned_provider
-            .clean_server("0.0.0.0:80".to_string())
+            .clean_server(Address::new("0.0.0.0", 80))
             .await
             .unwrap();
 
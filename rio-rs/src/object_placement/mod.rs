@@ -5,6 +5,7 @@ use std::fmt::Debug;
 use async_trait::async_trait;
 
 use crate::ObjectId;
+use crate::address::Address;
 use crate::errors::ObjectPlacementError;
 
 #[cfg(feature = "local")]
@@ -19,13 +20,13 @@ pub mod sqlite;
 /// Struct providing placement information
 pub struct ObjectPlacementItem {
     pub object_id: ObjectId,
-    pub server_address: Option<String>,
+    pub server_address: Option<Address>,
     // TODO: ttl
     // TODO: last_seen
 }
 
 impl ObjectPlacementItem {
-    pub fn new(object_id: ObjectId, server_address: Option<String>) -> ObjectPlacementItem {
+    pub fn new(object_id: ObjectId, server_address: Option<Address>) -> ObjectPlacementItem {
         ObjectPlacementItem {
             object_id,
             server_address,
@@ -48,9 +49,23 @@ pub trait ObjectPlacement: Send + Sync + Clone + Debug {
         object_placement: ObjectPlacementItem,
     ) -> Result<(), ObjectPlacementError>;
     /// Find the server address for a given object
-    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<String>, ObjectPlacementError>;
+    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<Address>, ObjectPlacementError>;
     /// Unassign all objects for a given server
-    async fn clean_server(&self, address: String) -> Result<(), ObjectPlacementError>;
+    async fn clean_server(&self, address: Address) -> Result<(), ObjectPlacementError>;
     /// Unassign a single object by its ID
     async fn remove(&self, object_id: &ObjectId) -> Result<(), ObjectPlacementError>;
+    /// List the objects assigned to a given server
+    ///
+    /// It is needed to move the objects between the servers, see [crate::rebalance]. By
+    /// default, it fails with [ObjectPlacementError::Unsupported], and the objects are never
+    /// moved
+    async fn server_objects(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<ObjectId>, ObjectPlacementError> {
+        let _ = address;
+        Err(ObjectPlacementError::Unsupported(
+            "server_objects".to_string(),
+        ))
+    }
 }
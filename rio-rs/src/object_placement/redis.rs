@@ -9,6 +9,7 @@ use redis::RedisError;
 
 use super::{ObjectPlacement, ObjectPlacementItem};
 use crate::ObjectId;
+use crate::address::Address;
 use crate::errors::ObjectPlacementError;
 
 #[derive(Clone, Debug)]
@@ -45,7 +46,15 @@ impl ObjectPlacement for RedisObjectPlacement {
         let k1 = format!("{}{}", self.key_prefix, object_id);
         let mut client = self.pool.get().await?;
 
+        // The object leaves the set of the server it was on
+        let previous_address: Option<String> = client.get(&k1).await?;
+        if let Some(previous_address) = previous_address {
+            let k2 = format!("{}{}", self.key_prefix, previous_address);
+            let _: () = client.srem(&k2, &object_id).await?;
+        }
+
         if let Some(server_address) = object_placement.server_address {
+            let server_address = server_address.to_string();
             let k2 = format!("{}{}", self.key_prefix, server_address);
             let mut pipe = redis::pipe();
             pipe.set(&k1, &server_address).sadd(&k2, &object_id);
@@ -58,14 +67,14 @@ impl ObjectPlacement for RedisObjectPlacement {
         Ok(())
     }
 
-    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<String>, ObjectPlacementError> {
+    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<Address>, ObjectPlacementError> {
         let k = format!("{}{}:{}", self.key_prefix, object_id.0, object_id.1);
         let mut client = self.pool.get().await?;
         let placement: Option<String> = client.get(&k).await?;
-        Ok(placement)
+        Ok(placement.map(|address| address.parse()).transpose()?)
     }
 
-    async fn clean_server(&self, address: String) -> Result<(), ObjectPlacementError> {
+    async fn clean_server(&self, address: Address) -> Result<(), ObjectPlacementError> {
         let k = format!("{}{}", self.key_prefix, address);
         let mut client = self.pool.get().await?;
         let objects_in_server: HashSet<String> = client.smembers(&k).await?;
@@ -78,10 +87,22 @@ impl ObjectPlacement for RedisObjectPlacement {
     }
 
     async fn remove(&self, object_id: &ObjectId) -> Result<(), ObjectPlacementError> {
-        let object_id = format!("{}:{}", object_id.0, object_id.1);
-        let k = format!("{}{}", self.key_prefix, object_id);
+        self.update(ObjectPlacementItem::new(object_id.clone(), None))
+            .await
+    }
+
+    async fn server_objects(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<ObjectId>, ObjectPlacementError> {
+        let k = format!("{}{}", self.key_prefix, address);
         let mut client = self.pool.get().await?;
-        let _: () = client.del(&k).await?;
-        Ok(())
+        let objects_in_server: HashSet<String> = client.smembers(&k).await?;
+        let objects = objects_in_server
+            .iter()
+            .filter_map(|object_id| object_id.split_once(':'))
+            .map(|(struct_name, object_id)| ObjectId::new(struct_name, object_id))
+            .collect();
+        Ok(objects)
     }
 }
@@ -8,6 +8,7 @@ use sqlx::{self, Row, SqlitePool};
 
 use super::{ObjectPlacement, ObjectPlacementItem};
 use crate::ObjectId;
+use crate::address::Address;
 use crate::errors::ObjectPlacementError;
 use crate::sql_migration::SqlMigrations;
 
@@ -78,12 +79,17 @@ impl ObjectPlacement for SqliteObjectPlacement {
         )
         .bind(&object_placement.object_id.0)
         .bind(&object_placement.object_id.1)
-        .bind(&object_placement.server_address)
+        .bind(
+            object_placement
+                .server_address
+                .as_ref()
+                .map(|address| address.to_string()),
+        )
         .execute(&self.pool)
         .await?;
         Ok(())
     }
-    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<String>, ObjectPlacementError> {
+    async fn lookup(&self, object_id: &ObjectId) -> Result<Option<Address>, ObjectPlacementError> {
         let row = sqlx::query(
             r#"
             SELECT server_address
@@ -96,21 +102,46 @@ impl ObjectPlacement for SqliteObjectPlacement {
         .fetch_one(&self.pool)
         .await
         .ok();
-        Ok(row.map(|row| row.get("server_address")))
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let address: String = row.get("server_address");
+        Ok(Some(address.parse()?))
     }
-    async fn clean_server(&self, address: String) -> Result<(), ObjectPlacementError> {
+    async fn clean_server(&self, address: Address) -> Result<(), ObjectPlacementError> {
         sqlx::query(
             r#"
             DELETE FROM object_placement
             WHERE server_address = $1
             "#,
         )
-        .bind(&address)
+        .bind(address.to_string())
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    async fn server_objects(
+        &self,
+        address: &Address,
+    ) -> Result<Vec<ObjectId>, ObjectPlacementError> {
+        let rows = sqlx::query(
+            r#"
+            SELECT struct_name, object_id
+            FROM object_placement
+            WHERE server_address = $1
+            "#,
+        )
+        .bind(address.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        let objects = rows
+            .iter()
+            .map(|row| ObjectId(row.get("struct_name"), row.get("object_id")))
+            .collect();
+        Ok(objects)
+    }
+
     async fn remove(&self, object_id: &ObjectId) -> Result<(), ObjectPlacementError> {
         sqlx::query(
             r#"
@@ -155,8 +186,10 @@ mod test {
             .unwrap();
         assert_eq!(placement, None);
 
-        let object_placement =
-            ObjectPlacementItem::new(ObjectId::new("Test", "1"), Some("0.0.0.0:5000".to_string()));
+        let object_placement = ObjectPlacementItem::new(
+            ObjectId::new("Test", "1"),
+            Some("0.0.0.0:5000".parse().unwrap()),
+        );
         object_placement_provider
             .update(object_placement)
             .await
@@ -166,10 +199,12 @@ mod test {
             .await
             .unwrap()
             .unwrap();
-        assert_eq!(placement, "0.0.0.0:5000");
+        assert_eq!(placement.to_string(), "0.0.0.0:5000");
 
-        let object_placement =
-            ObjectPlacementItem::new(ObjectId::new("Test", "1"), Some("0.0.0.0:5001".to_string()));
+        let object_placement = ObjectPlacementItem::new(
+            ObjectId::new("Test", "1"),
+            Some("0.0.0.0:5001".parse().unwrap()),
+        );
         object_placement_provider
             .update(object_placement)
             .await
@@ -179,10 +214,10 @@ mod test {
             .await
             .unwrap()
             .unwrap();
-        assert_eq!(placement, "0.0.0.0:5001");
+        assert_eq!(placement.to_string(), "0.0.0.0:5001");
 
         object_placement_provider
-            .clean_server("0.0.0.0:5001".to_string())
+            .clean_server("0.0.0.0:5001".parse().unwrap())
             .await
             .unwrap();
         let placement = object_placement_provider
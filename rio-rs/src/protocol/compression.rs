@@ -0,0 +1,328 @@
+//! Payload compression
+//!
+//! Clients announce the algorithms they support on the
+//! [Handshake](super::handshake::Handshake), and the server picks one for the connection.
+//! Afterwards, each side compresses the frames it sends once they are larger than its own
+//! [CompressionConfig::threshold]. Compressed frames are flagged on their
+//! [FrameHeader](super::frame::FrameHeader), so small frames still go through uncompressed.
+//!
+//! Each algorithm is behind its own feature flag (`zstd` and `lz4`).
+//!
+//! ```rust
+//! # use rio_rs::protocol::compression::{Compression, CompressionConfig};
+//! let config = CompressionConfig::new(vec![Compression::Zstd, Compression::Lz4])
+//!     .with_threshold(1024);
+//! assert_eq!(config.threshold, 1024);
+//! ```
+
+use log::error;
+use tokio_util::bytes::Bytes;
+
+use super::frame::{self, FrameHeader, FrameKind, PROTOCOL_VERSION};
+use crate::errors::FrameError;
+
+/// Payloads smaller than this are not compressed by default (64KiB)
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 64 * 1024;
+
+/// Compression algorithms a frame can be compressed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Zstandard, requires the `zstd` feature
+    Zstd,
+    /// LZ4 (block format), requires the `lz4` feature
+    Lz4,
+}
+
+impl Compression {
+    /// Name used on the handshake
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    /// Parses a name from the handshake
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name {
+            "zstd" => Some(Compression::Zstd),
+            "lz4" => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+
+    /// Whether the feature flag for this algorithm is enabled
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Compression::Zstd => cfg!(feature = "zstd"),
+            Compression::Lz4 => cfg!(feature = "lz4"),
+        }
+    }
+
+    /// Identifies the algorithm on the frame header (0 is reserved for uncompressed frames)
+    pub(crate) fn flag(&self) -> u8 {
+        match self {
+            Compression::Zstd => 1,
+            Compression::Lz4 => 2,
+        }
+    }
+
+    /// Reads the algorithm from the frame header flag
+    pub(crate) fn from_flag(flag: u8) -> Result<Option<Compression>, FrameError> {
+        match flag {
+            0 => Ok(None),
+            1 => Ok(Some(Compression::Zstd)),
+            2 => Ok(Some(Compression::Lz4)),
+            unknown => Err(FrameError::UnknownCompression(unknown)),
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, FrameError> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .map_err(|e| FrameError::Compression(e.to_string())),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+            #[allow(unreachable_patterns)]
+            unsupported => Err(FrameError::Compression(format!(
+                "{} is not enabled",
+                unsupported.name()
+            ))),
+        }
+    }
+
+    /// Decompresses `data`, which can't grow past [MAX_FRAME_LENGTH](frame::MAX_FRAME_LENGTH)
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, FrameError> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::bulk::decompress(data, frame::MAX_FRAME_LENGTH)
+                .map_err(|e| FrameError::Compression(e.to_string())),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                // The size is checked before anything is allocated for it
+                let (size, _) = lz4_flex::block::uncompressed_size(data)
+                    .map_err(|e| FrameError::Compression(e.to_string()))?;
+                if size > frame::MAX_FRAME_LENGTH {
+                    return Err(FrameError::Compression(format!(
+                        "decompressed size {} is over the limit of {}",
+                        size,
+                        frame::MAX_FRAME_LENGTH
+                    )));
+                }
+                lz4_flex::decompress_size_prepended(data)
+                    .map_err(|e| FrameError::Compression(e.to_string()))
+            }
+            #[allow(unreachable_patterns)]
+            unsupported => Err(FrameError::Compression(format!(
+                "{} is not enabled",
+                unsupported.name()
+            ))),
+        }
+    }
+}
+
+/// Compression settings for one side of the connection
+///
+/// By default there is no compression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Algorithms this side accepts, by order of preference
+    pub algorithms: Vec<Compression>,
+
+    /// Minimum size (in bytes) for a payload to be compressed
+    pub threshold: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            algorithms: vec![],
+            threshold: DEFAULT_COMPRESSION_THRESHOLD,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn new(algorithms: Vec<Compression>) -> CompressionConfig {
+        CompressionConfig {
+            algorithms,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: usize) -> CompressionConfig {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Names of the algorithms to negotiate, skipping the ones whose features are disabled
+    pub fn algorithm_names(&self) -> Vec<String> {
+        self.algorithms
+            .iter()
+            .filter(|algorithm| algorithm.is_supported())
+            .map(|algorithm| algorithm.name().to_string())
+            .collect()
+    }
+
+    /// Encoder for a connection, given the algorithm picked on the handshake
+    pub fn encoder(&self, negotiated: Option<&str>) -> FrameEncoder {
+        FrameEncoder {
+            version: PROTOCOL_VERSION,
+            compression: negotiated.and_then(Compression::from_name),
+            threshold: self.threshold,
+        }
+    }
+}
+
+/// Builds the frames sent on a connection, tagged with the protocol version agreed for it,
+/// and compressing the large ones with the algorithm negotiated for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameEncoder {
+    version: u16,
+    compression: Option<Compression>,
+    threshold: usize,
+}
+
+impl Default for FrameEncoder {
+    fn default() -> Self {
+        CompressionConfig::default().encoder(None)
+    }
+}
+
+impl FrameEncoder {
+    /// Tags the frames with the protocol `version` picked on the handshake, instead of
+    /// [PROTOCOL_VERSION]
+    pub fn with_version(mut self, version: u16) -> FrameEncoder {
+        self.version = version;
+        self
+    }
+
+    /// Builds the frame for `payload`
+    ///
+    /// If the compression fails, the frame is sent uncompressed
+    pub fn encode(&self, kind: FrameKind, payload: &[u8]) -> Bytes {
+        let header = FrameHeader {
+            version: self.version,
+            ..FrameHeader::new(kind)
+        };
+        match self.compression {
+            Some(compression) if payload.len() >= self.threshold => {
+                match compression.compress(payload) {
+                    Ok(compressed) => {
+                        let header = FrameHeader {
+                            compression: Some(compression),
+                            ..header
+                        };
+                        frame::encode_with_header(header, &compressed)
+                    }
+                    Err(err) => {
+                        error!("Sending frame uncompressed: {}", err);
+                        frame::encode_with_header(header, payload)
+                    }
+                }
+            }
+            _ => frame::encode_with_header(header, payload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn supported() -> Vec<Compression> {
+        [Compression::Zstd, Compression::Lz4]
+            .into_iter()
+            .filter(Compression::is_supported)
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = "rio".repeat(1000).into_bytes();
+        for compression in supported() {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(compressed.len() < data.len(), "{:?}", compression);
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_decompress_garbage() {
+        for compression in supported() {
+            let result = compression.decompress(b"not compressed");
+            assert!(
+                matches!(result, Err(FrameError::Compression(_))),
+                "{:?}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_oversized() {
+        let data = vec![0; frame::MAX_FRAME_LENGTH + 1];
+        for compression in supported() {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(
+                compressed.len() < frame::MAX_FRAME_LENGTH,
+                "{:?}",
+                compression
+            );
+            let result = compression.decompress(&compressed);
+            assert!(
+                matches!(result, Err(FrameError::Compression(_))),
+                "{:?}",
+                compression
+            );
+        }
+
+        // A lz4 payload that claims to be huge is rejected before the allocation
+        let mut lying = (u32::MAX).to_le_bytes().to_vec();
+        lying.extend_from_slice(&[0; 8]);
+        if Compression::Lz4.is_supported() {
+            let result = Compression::Lz4.decompress(&lying);
+            assert!(matches!(result, Err(FrameError::Compression(_))));
+        }
+    }
+
+    #[test]
+    fn test_encoder_threshold() {
+        for compression in supported() {
+            let config = CompressionConfig::new(vec![compression]).with_threshold(100);
+            let encoder = config.encoder(Some(compression.name()));
+
+            let small = encoder.encode(FrameKind::Response, &[1; 10]);
+            let (header, payload) = frame::decode(&small).unwrap();
+            assert_eq!(header.compression, None);
+            assert_eq!(payload.as_ref(), &[1; 10]);
+
+            let large = encoder.encode(FrameKind::Response, &[1; 1000]);
+            assert!(large.len() < 1000);
+            let (header, payload) = frame::decode(&large).unwrap();
+            assert_eq!(header.compression, Some(compression));
+            assert_eq!(payload.as_ref(), &[1; 1000]);
+        }
+    }
+
+    #[test]
+    fn test_encoder_not_negotiated() {
+        let config = CompressionConfig::default().with_threshold(0);
+        let frame = config.encoder(None).encode(FrameKind::Response, &[1; 1000]);
+        let (header, _) = frame::decode(&frame).unwrap();
+        assert_eq!(header.compression, None);
+    }
+
+    #[test]
+    fn test_encoder_version() {
+        let encoder = CompressionConfig::default()
+            .encoder(None)
+            .with_version(frame::MIN_PROTOCOL_VERSION);
+        let frame = encoder.encode(FrameKind::Response, &[1; 10]);
+        let (header, _) = frame::decode(&frame).unwrap();
+        assert_eq!(header.version, frame::MIN_PROTOCOL_VERSION);
+    }
+}
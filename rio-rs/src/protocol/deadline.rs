@@ -0,0 +1,55 @@
+//! Request deadlines
+//!
+//! A request can carry a [RequestEnvelope::deadline](super::RequestEnvelope::deadline):
+//! how long the caller is willing to wait for its response. The client gives up once it
+//! passes, and the server cancels the handler, answering with
+//! [ResponseError::DeadlineExceeded](super::ResponseError::DeadlineExceeded).
+//!
+//! The deadline is sent as the time left rather than as a point in time, so it doesn't
+//! depend on the clocks of the client and the server agreeing.
+//!
+//! Requests sent from within a handler with
+//! [ServiceObject::send](crate::ServiceObject::send) inherit what is left of the deadline
+//! of the request being handled.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs `future` with `deadline` as the deadline of the request being handled
+pub async fn scope<F: Future>(deadline: Instant, future: F) -> F::Output {
+    DEADLINE.scope(deadline, future).await
+}
+
+/// Time left until the deadline of the request being handled
+///
+/// It is `None` outside of a handler, or if the request has no deadline
+pub fn remaining() -> Option<Duration> {
+    DEADLINE
+        .try_with(|deadline| deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// Deadline of the request being handled, if it has one
+pub(crate) fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remaining() {
+        assert_eq!(remaining(), None);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let left = scope(deadline, async { remaining() }).await.unwrap();
+        assert!(left > Duration::from_secs(9));
+        assert!(left <= Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,211 @@
+//! Classification of the errors returned by the servers and the client
+//!
+//! Every [ResponseError](super::ResponseError), [ClientError](super::ClientError) and
+//! [RequestError](super::RequestError) maps to a stable [ErrorCode], which tells whether
+//! it is worth retrying the request through [ErrorCode::retryability].
+//!
+//! ```rust
+//! # use rio_rs::protocol::{ClientError, ResponseError};
+//! # use rio_rs::protocol::error_code::{ErrorCode, Retryability};
+//! let error = ResponseError::Redirect("0.0.0.0:5000".parse().unwrap());
+//! assert_eq!(error.code(), ErrorCode::Redirect);
+//! assert_eq!(error.retryability(), Retryability::Retryable);
+//!
+//! // The request might have been handled before the connection was lost
+//! let error = ClientError::Disconnect;
+//! assert_eq!(error.retryability(), Retryability::IfIdempotent);
+//!
+//! let error = ResponseError::ApplicationError(vec![]);
+//! assert!(!error.is_retryable());
+//! ```
+//!
+//! Errors that aren't fully described by their code carry [ErrorDetails]:
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use rio_rs::protocol::ResponseError;
+//! # use rio_rs::protocol::error_code::ErrorDetails;
+//! let details = ErrorDetails::new("activation limit reached")
+//!     .with_object("Counter", "1")
+//!     .with_retry_after(Duration::from_secs(1));
+//! let error = ResponseError::Unavailable(details.into());
+//! let details = error.details().unwrap();
+//! assert_eq!(details.object, Some(("Counter".to_string(), "1".to_string())));
+//! assert_eq!(details.retry_after, Some(Duration::from_secs(1)));
+//! ```
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::address::Address;
+
+/// Stable code for each kind of error
+///
+/// The numeric values are stable, new codes are only ever added at the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u16)]
+pub enum ErrorCode {
+    /// Nothing better is known about the error
+    Unknown = 0,
+    /// The object lives in another server
+    Redirect = 1,
+    /// The object was placed on a server that is no longer active
+    Deallocated = 2,
+    /// The object could not be started
+    AllocationFailed = 3,
+    /// The server doesn't know the object type
+    NotSupported = 4,
+    /// The object type has no handler for the message
+    HandlerNotFound = 5,
+    /// The object is not running on the server
+    ObjectNotFound = 6,
+    /// The handler panicked
+    Panic = 7,
+    /// The object placement could not be read or updated
+    Placement = 8,
+    /// The cluster membership could not be read
+    Membership = 9,
+    /// The server or the client could not serialize a message
+    Serialization = 10,
+    /// The server or the client could not deserialize a message
+    Deserialization = 11,
+    /// The handler returned an error
+    Application = 12,
+    /// The frame could not be decoded
+    InvalidFrame = 13,
+    /// The client and the server can't talk to each other
+    IncompatibleProtocol = 14,
+    /// The request deadline passed before it was handled
+    DeadlineExceeded = 15,
+    /// The server, or the cluster, can't take requests at the moment
+    Unavailable = 16,
+    /// The connection to the server was lost
+    Disconnected = 17,
+    /// The client is misconfigured
+    Configuration = 18,
+    /// The object's mailbox is full, see [crate::registry::Registry::set_mailbox_capacity]
+    Overloaded = 19,
+}
+
+/// Whether a request that failed can be sent again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Retryability {
+    /// The request was not handled, retrying it is always safe
+    Retryable,
+    /// The request might have been handled, it should only be retried if handling it
+    /// twice is harmless
+    IfIdempotent,
+    /// Retrying the request would fail the same way
+    Never,
+}
+
+impl ErrorCode {
+    /// Whether requests that failed with this code can be sent again
+    pub fn retryability(&self) -> Retryability {
+        match self {
+            ErrorCode::Redirect
+            | ErrorCode::Deallocated
+            | ErrorCode::Placement
+            | ErrorCode::Membership
+            | ErrorCode::Unavailable
+            | ErrorCode::Overloaded => Retryability::Retryable,
+            ErrorCode::Disconnected => Retryability::IfIdempotent,
+            ErrorCode::Unknown
+            | ErrorCode::AllocationFailed
+            | ErrorCode::NotSupported
+            | ErrorCode::HandlerNotFound
+            | ErrorCode::ObjectNotFound
+            | ErrorCode::Panic
+            | ErrorCode::Serialization
+            | ErrorCode::Deserialization
+            | ErrorCode::Application
+            | ErrorCode::InvalidFrame
+            | ErrorCode::IncompatibleProtocol
+            | ErrorCode::DeadlineExceeded
+            | ErrorCode::Configuration => Retryability::Never,
+        }
+    }
+
+    /// Stable name of the code, fit for logs and metrics labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Unknown => "unknown",
+            ErrorCode::Redirect => "redirect",
+            ErrorCode::Deallocated => "deallocated",
+            ErrorCode::AllocationFailed => "allocation_failed",
+            ErrorCode::NotSupported => "not_supported",
+            ErrorCode::HandlerNotFound => "handler_not_found",
+            ErrorCode::ObjectNotFound => "object_not_found",
+            ErrorCode::Panic => "panic",
+            ErrorCode::Placement => "placement",
+            ErrorCode::Membership => "membership",
+            ErrorCode::Serialization => "serialization",
+            ErrorCode::Deserialization => "deserialization",
+            ErrorCode::Application => "application",
+            ErrorCode::InvalidFrame => "invalid_frame",
+            ErrorCode::IncompatibleProtocol => "incompatible_protocol",
+            ErrorCode::DeadlineExceeded => "deadline_exceeded",
+            ErrorCode::Unavailable => "unavailable",
+            ErrorCode::Disconnected => "disconnected",
+            ErrorCode::Configuration => "configuration",
+            ErrorCode::Overloaded => "overloaded",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Context of an error, so it can be acted on without parsing its message
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorDetails {
+    /// What went wrong, meant for humans
+    pub message: String,
+    /// Type and id of the object the request was for, if known
+    pub object: Option<(String, String)>,
+    /// Server the error is about, e.g. the one that is no longer active
+    pub server: Option<Address>,
+    /// How long to wait before retrying, if the server can tell
+    pub retry_after: Option<Duration>,
+}
+
+impl ErrorDetails {
+    pub fn new(message: impl Into<String>) -> ErrorDetails {
+        ErrorDetails {
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the object the request was for
+    pub fn with_object(
+        mut self,
+        object_type: impl Into<String>,
+        object_id: impl Into<String>,
+    ) -> ErrorDetails {
+        self.object = Some((object_type.into(), object_id.into()));
+        self
+    }
+
+    /// Sets the server the error is about
+    pub fn with_server(mut self, server: Address) -> ErrorDetails {
+        self.server = Some(server);
+        self
+    }
+
+    /// Sets how long to wait before retrying
+    pub fn with_retry_after(mut self, retry_after: Duration) -> ErrorDetails {
+        self.retry_after = Some(retry_after);
+        self
+    }
+}
+
+impl std::fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
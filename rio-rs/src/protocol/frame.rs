@@ -0,0 +1,250 @@
+//! Wire framing
+//!
+//! Every frame starts with a fixed size header with the protocol version, the
+//! kind of message it carries and how it is compressed, followed by the serialized message.
+//!
+//! ```text
+//! +------------------+---------------+-------------------+-----------------+
+//! | version (u16 BE) | kind (u8)     | compression (u8)  | payload         |
+//! +------------------+---------------+-------------------+-----------------+
+//! ```
+//!
+//! ```rust
+//! # use rio_rs::protocol::frame::*;
+//! let frame = encode(FrameKind::Request, b"payload");
+//! let (header, payload) = decode(&frame).unwrap();
+//! assert_eq!(header.kind, FrameKind::Request);
+//! assert_eq!(header.version, PROTOCOL_VERSION);
+//! assert_eq!(payload.as_ref(), b"payload");
+//! ```
+
+use std::borrow::Cow;
+use std::ops::RangeInclusive;
+
+use tokio_util::bytes::{BufMut, Bytes, BytesMut};
+
+use super::compression::Compression;
+use crate::errors::FrameError;
+
+/// Version of the wire protocol implemented by this crate
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Oldest version of the wire protocol this crate still speaks
+///
+/// Both sides of a connection agree on the highest version they have in common on the
+/// [Handshake](super::handshake::Handshake), so a cluster can be upgraded node by node
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/// Versions of the wire protocol this crate speaks
+pub const SUPPORTED_VERSIONS: RangeInclusive<u16> = MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION;
+
+/// Largest frame accepted on a connection (8MiB). Compressed payloads can't grow past it
+/// either once decompressed
+pub const MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Kind of message carried by a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FrameKind {
+    /// [RequestEnvelope](super::RequestEnvelope), from client to server
+    Request = 1,
+    /// [ResponseEnvelope](super::ResponseEnvelope), from server to client
+    Response = 2,
+    /// [SubscriptionRequest](super::pubsub::SubscriptionRequest), from client to server
+    SubscriptionRequest = 3,
+    /// [SubscriptionEnvelope](super::pubsub::SubscriptionEnvelope), from server to client
+    SubscriptionResponse = 4,
+    /// [ResponseError](super::ResponseError) for frames the server could not make sense of,
+    /// nor tell which request they carry. The client closes the connection on it
+    Error = 5,
+    /// [Handshake](super::handshake::Handshake), the first frame a client sends
+    Handshake = 6,
+    /// [HandshakeResponse](super::handshake::HandshakeResponse), the server's answer to the
+    /// handshake
+    HandshakeResponse = 7,
+    /// [Unsubscribe](super::pubsub::Unsubscribe), from client to server
+    Unsubscribe = 8,
+    /// [SubscriptionClosed](super::pubsub::SubscriptionClosed), from server to client
+    SubscriptionClosed = 9,
+    /// [RequestEnvelope](super::RequestEnvelope) for a message answered with a stream,
+    /// from client to server
+    StreamRequest = 10,
+    /// [StreamItemEnvelope](super::streaming::StreamItemEnvelope), from server to client
+    StreamItem = 11,
+    /// [StreamCredit](super::streaming::StreamCredit), from client to server
+    StreamCredit = 12,
+    /// [StreamCancel](super::streaming::StreamCancel), from client to server
+    StreamCancel = 13,
+    /// [BatchRequestEnvelope](super::BatchRequestEnvelope), from client to server
+    BatchRequest = 14,
+    /// [BatchResponseEnvelope](super::BatchResponseEnvelope), from server to client
+    BatchResponse = 15,
+    /// [RequestEnvelope](super::RequestEnvelope) asking the server to deactivate an
+    /// object, from client to server. It is answered with a
+    /// [ResponseEnvelope](super::ResponseEnvelope)
+    Deactivate = 16,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = FrameError;
+
+    fn try_from(value: u8) -> Result<Self, FrameError> {
+        match value {
+            1 => Ok(FrameKind::Request),
+            2 => Ok(FrameKind::Response),
+            3 => Ok(FrameKind::SubscriptionRequest),
+            4 => Ok(FrameKind::SubscriptionResponse),
+            5 => Ok(FrameKind::Error),
+            6 => Ok(FrameKind::Handshake),
+            7 => Ok(FrameKind::HandshakeResponse),
+            8 => Ok(FrameKind::Unsubscribe),
+            9 => Ok(FrameKind::SubscriptionClosed),
+            10 => Ok(FrameKind::StreamRequest),
+            11 => Ok(FrameKind::StreamItem),
+            12 => Ok(FrameKind::StreamCredit),
+            13 => Ok(FrameKind::StreamCancel),
+            14 => Ok(FrameKind::BatchRequest),
+            15 => Ok(FrameKind::BatchResponse),
+            16 => Ok(FrameKind::Deactivate),
+            unknown => Err(FrameError::UnknownKind(unknown)),
+        }
+    }
+}
+
+/// Header that precedes every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub version: u16,
+    pub kind: FrameKind,
+    /// Algorithm the payload is compressed with, if any
+    pub compression: Option<Compression>,
+}
+
+impl FrameHeader {
+    /// Number of bytes the header takes on the wire
+    pub const SIZE: usize = 4;
+
+    /// New header for the current [PROTOCOL_VERSION]
+    pub fn new(kind: FrameKind) -> FrameHeader {
+        FrameHeader {
+            version: PROTOCOL_VERSION,
+            kind,
+            compression: None,
+        }
+    }
+}
+
+pub(crate) fn encode_with_header(header: FrameHeader, payload: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(FrameHeader::SIZE + payload.len());
+    frame.put_u16(header.version);
+    frame.put_u8(header.kind as u8);
+    frame.put_u8(header.compression.map_or(0, |c| c.flag()));
+    frame.put_slice(payload);
+    frame.freeze()
+}
+
+/// Builds a frame for the current [PROTOCOL_VERSION], prepending the header to `payload`
+pub fn encode(kind: FrameKind, payload: &[u8]) -> Bytes {
+    encode_with_header(FrameHeader::new(kind), payload)
+}
+
+/// Same as [encode], but the payload is compressed with `compression`
+///
+/// Most of the time you want a [FrameEncoder](super::compression::FrameEncoder) instead,
+/// which only compresses large payloads
+pub fn encode_compressed(
+    kind: FrameKind,
+    payload: &[u8],
+    compression: Compression,
+) -> Result<Bytes, FrameError> {
+    let header = FrameHeader {
+        compression: Some(compression),
+        ..FrameHeader::new(kind)
+    };
+    let compressed = compression.compress(payload)?;
+    Ok(encode_with_header(header, &compressed))
+}
+
+/// Splits a frame into its header and payload, decompressing the payload if needed
+///
+/// It fails for frames that are too short, that come from a protocol version out of
+/// [SUPPORTED_VERSIONS], that have an unknown kind or that can't be decompressed. The
+/// handshake frames are read whatever their version, as that is where it gets agreed on
+pub fn decode(frame: &[u8]) -> Result<(FrameHeader, Cow<'_, [u8]>), FrameError> {
+    if frame.len() < FrameHeader::SIZE {
+        return Err(FrameError::Truncated);
+    }
+    let version = u16::from_be_bytes([frame[0], frame[1]]);
+    let kind = FrameKind::try_from(frame[2]);
+    let is_handshake = matches!(
+        kind,
+        Ok(FrameKind::Handshake | FrameKind::HandshakeResponse)
+    );
+    if !is_handshake && !SUPPORTED_VERSIONS.contains(&version) {
+        return Err(FrameError::UnsupportedVersion(version));
+    }
+    let kind = kind?;
+    let compression = Compression::from_flag(frame[3])?;
+    let header = FrameHeader {
+        version,
+        kind,
+        compression,
+    };
+
+    let payload = &frame[FrameHeader::SIZE..];
+    let payload = match compression {
+        Some(compression) => Cow::Owned(compression.decompress(payload)?),
+        None => Cow::Borrowed(payload),
+    };
+    Ok((header, payload))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_truncated() {
+        assert_eq!(decode(&[0, 1, 1]), Err(FrameError::Truncated));
+    }
+
+    #[test]
+    fn test_decode_unknown_kind() {
+        assert_eq!(
+            decode(&[0, 1, 200, 0, 42]),
+            Err(FrameError::UnknownKind(200))
+        );
+    }
+
+    #[test]
+    fn test_decode_unsupported_version() {
+        assert_eq!(
+            decode(&[0, 99, 1, 0, 42]),
+            Err(FrameError::UnsupportedVersion(99))
+        );
+    }
+
+    #[test]
+    fn test_decode_handshake_any_version() {
+        let (header, payload) = decode(&[0, 99, 6, 0, 42]).unwrap();
+        assert_eq!(header.version, 99);
+        assert_eq!(header.kind, FrameKind::Handshake);
+        assert_eq!(payload.as_ref(), &[42]);
+    }
+
+    #[test]
+    fn test_decode_unknown_compression() {
+        assert_eq!(
+            decode(&[0, 1, 1, 200, 42]),
+            Err(FrameError::UnknownCompression(200))
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_empty_payload() {
+        let frame = encode(FrameKind::Error, &[]);
+        let (header, payload) = decode(&frame).unwrap();
+        assert_eq!(header, FrameHeader::new(FrameKind::Error));
+        assert!(payload.is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+//! Connection handshake
+//!
+//! The first frame a client sends on a new connection is a [Handshake], and the server
+//! answers it with a [HandshakeResponse] before processing any other frame.
+//!
+//! It lets servers and clients running different versions of this crate find out
+//! whether they can talk to each other, instead of exchanging messages they can't
+//! understand. Each side speaks a range of protocol versions, and the connection uses the
+//! highest one they have in common, so nodes can be upgraded one at a time.
+//!
+//! <div class="warning">
+//! These structs are always serialized with bincode, so they need to stay compatible
+//! across protocol versions
+//! </div>
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use super::ResponseError;
+use super::frame::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+/// Name of the [default_codec](crate::codec::default_codec)
+pub const DEFAULT_CODEC: &str = "bincode";
+
+/// Sent by the client when it connects to a server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    /// Oldest protocol version the client speaks
+    pub min_protocol_version: u16,
+
+    /// Newest protocol version the client speaks
+    pub max_protocol_version: u16,
+
+    /// Codec the client uses for messages and responses
+    pub codec: String,
+
+    /// Compression algorithms the client supports, by order of preference
+    pub compression: Vec<String>,
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Handshake {
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION,
+            codec: DEFAULT_CODEC.to_string(),
+            compression: vec![],
+        }
+    }
+}
+
+/// Features agreed on by the server for a connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeAccepted {
+    /// Protocol version picked by the server, the frames of the connection are tagged with it
+    pub protocol_version: u16,
+
+    /// Compression picked by the server, if any
+    pub compression: Option<String>,
+}
+
+/// Server's answer to a [Handshake]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeResponse {
+    pub result: Result<HandshakeAccepted, ResponseError>,
+}
+
+impl Handshake {
+    /// Checks whether the features asked by the client are supported by this server
+    ///
+    /// - `versions` are the protocol versions the server speaks, usually
+    ///   [SUPPORTED_VERSIONS](super::frame::SUPPORTED_VERSIONS)
+    /// - `codec` is the codec the server uses
+    /// - `compression` are the compression algorithms the server supports
+    ///
+    /// The highest protocol version both sides speak is picked
+    pub fn negotiate(
+        &self,
+        versions: RangeInclusive<u16>,
+        codec: &str,
+        compression: &[String],
+    ) -> Result<HandshakeAccepted, ResponseError> {
+        let protocol_version = self.max_protocol_version.min(*versions.end());
+        if protocol_version < self.min_protocol_version.max(*versions.start()) {
+            return Err(ResponseError::IncompatibleProtocol(format!(
+                "server speaks protocol versions {} to {}, client speaks {} to {}",
+                versions.start(),
+                versions.end(),
+                self.min_protocol_version,
+                self.max_protocol_version
+            )));
+        }
+
+        if self.codec != codec {
+            return Err(ResponseError::IncompatibleProtocol(format!(
+                "server uses codec {}, client uses {}",
+                codec, self.codec
+            )));
+        }
+
+        let compression = self
+            .compression
+            .iter()
+            .find(|algorithm| compression.contains(algorithm))
+            .cloned();
+
+        Ok(HandshakeAccepted {
+            protocol_version,
+            compression,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::frame::SUPPORTED_VERSIONS;
+
+    #[test]
+    fn test_negotiate_default() {
+        let accepted = Handshake::default()
+            .negotiate(SUPPORTED_VERSIONS, DEFAULT_CODEC, &[])
+            .unwrap();
+        assert_eq!(accepted.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(accepted.compression, None);
+    }
+
+    #[test]
+    fn test_negotiate_overlapping_protocol_versions() {
+        // An upgraded client talking to a server that wasn't upgraded yet
+        let handshake = Handshake {
+            min_protocol_version: 2,
+            max_protocol_version: 4,
+            ..Default::default()
+        };
+        let accepted = handshake.negotiate(1..=3, DEFAULT_CODEC, &[]).unwrap();
+        assert_eq!(accepted.protocol_version, 3);
+
+        // And the other way around
+        let handshake = Handshake {
+            min_protocol_version: 1,
+            max_protocol_version: 3,
+            ..Default::default()
+        };
+        let accepted = handshake.negotiate(2..=4, DEFAULT_CODEC, &[]).unwrap();
+        assert_eq!(accepted.protocol_version, 3);
+    }
+
+    #[test]
+    fn test_negotiate_protocol_version_mismatch() {
+        let handshake = Handshake {
+            min_protocol_version: PROTOCOL_VERSION + 1,
+            max_protocol_version: PROTOCOL_VERSION + 2,
+            ..Default::default()
+        };
+        let result = handshake.negotiate(SUPPORTED_VERSIONS, DEFAULT_CODEC, &[]);
+        assert!(matches!(
+            result,
+            Err(ResponseError::IncompatibleProtocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_negotiate_codec_mismatch() {
+        let handshake = Handshake {
+            codec: "json".to_string(),
+            ..Default::default()
+        };
+        let result = handshake.negotiate(SUPPORTED_VERSIONS, DEFAULT_CODEC, &[]);
+        assert!(matches!(
+            result,
+            Err(ResponseError::IncompatibleProtocol(_))
+        ));
+    }
+
+    #[test]
+    fn test_negotiate_compression_client_preference() {
+        let handshake = Handshake {
+            compression: vec!["zstd".to_string(), "lz4".to_string()],
+            ..Default::default()
+        };
+        let server_compression = vec!["lz4".to_string(), "zstd".to_string()];
+        let accepted = handshake
+            .negotiate(SUPPORTED_VERSIONS, DEFAULT_CODEC, &server_compression)
+            .unwrap();
+        assert_eq!(accepted.compression, Some("zstd".to_string()));
+    }
+}
@@ -0,0 +1,125 @@
+//! Request and response metadata
+//!
+//! Requests carry a string-keyed [Metadata] map alongside their payload (trace context,
+//! tenant, auth tokens, caller identity...). The server makes it available to the handler
+//! while it runs, through [current] and [get].
+//!
+//! Handlers can also attach metadata to their response with [set_response].
+//!
+//! Requests sent from within a handler with
+//! [ServiceObject::send](crate::ServiceObject::send) carry the metadata of the request
+//! being handled.
+//!
+//! <div class="warning">
+//! The metadata is bound to the task running the handler, it is not visible from tasks
+//! the handler spawns
+//! </div>
+//!
+//! ```rust
+//! # use rio_rs::protocol::metadata::{self, Metadata};
+//! # #[tokio::main]
+//! # async fn main() {
+//! let request_metadata = Metadata::from([("tenant".to_string(), "acme".to_string())]);
+//! let (tenant, response_metadata) = metadata::scope(request_metadata, async {
+//!     metadata::set_response("served-by", "node-1");
+//!     metadata::get("tenant")
+//! })
+//! .await;
+//! assert_eq!(tenant, Some("acme".to_string()));
+//! assert_eq!(response_metadata["served-by"], "node-1");
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+
+/// String-keyed metadata attached to requests and responses
+pub type Metadata = HashMap<String, String>;
+
+tokio::task_local! {
+    static REQUEST_METADATA: Metadata;
+    static RESPONSE_METADATA: RefCell<Metadata>;
+}
+
+/// Runs `future` with `metadata` as the metadata of the request being handled
+///
+/// Returns the output of `future` and the metadata it set for the response
+pub async fn scope<F: Future>(metadata: Metadata, future: F) -> (F::Output, Metadata) {
+    RESPONSE_METADATA
+        .scope(RefCell::default(), async move {
+            let output = REQUEST_METADATA.scope(metadata, future).await;
+            let response_metadata = RESPONSE_METADATA.with(|m| m.take());
+            (output, response_metadata)
+        })
+        .await
+}
+
+/// Metadata of the request being handled
+///
+/// It is empty outside of a handler
+pub fn current() -> Metadata {
+    REQUEST_METADATA
+        .try_with(|metadata| metadata.clone())
+        .unwrap_or_default()
+}
+
+/// Metadata of the request being handled, `None` outside of a handler
+pub(crate) fn try_current() -> Option<Metadata> {
+    REQUEST_METADATA.try_with(|metadata| metadata.clone()).ok()
+}
+
+/// Value of `key` in the metadata of the request being handled
+pub fn get(key: &str) -> Option<String> {
+    REQUEST_METADATA
+        .try_with(|metadata| metadata.get(key).cloned())
+        .ok()
+        .flatten()
+}
+
+/// Adds `key` to the metadata sent back with the response
+///
+/// It does nothing outside of a handler
+pub fn set_response(key: impl Into<String>, value: impl Into<String>) {
+    RESPONSE_METADATA
+        .try_with(|metadata| {
+            metadata.borrow_mut().insert(key.into(), value.into());
+        })
+        .ok();
+}
+
+/// Adds all of `metadata` to the metadata sent back with the response
+///
+/// It does nothing outside of a handler
+pub(crate) fn extend_response(metadata: Metadata) {
+    RESPONSE_METADATA
+        .try_with(|response_metadata| response_metadata.borrow_mut().extend(metadata))
+        .ok();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_outside_of_scope() {
+        assert!(current().is_empty());
+        assert_eq!(get("key"), None);
+        set_response("key", "value");
+    }
+
+    #[tokio::test]
+    async fn test_scope() {
+        let metadata = Metadata::from([("key".to_string(), "value".to_string())]);
+        let (output, response_metadata) = scope(metadata.clone(), async {
+            set_response("response-key", "response-value");
+            current()
+        })
+        .await;
+        assert_eq!(output, metadata);
+        assert_eq!(
+            response_metadata,
+            Metadata::from([("response-key".to_string(), "response-value".to_string())])
+        );
+    }
+}
@@ -0,0 +1,596 @@
+//! Client/Server communication protocol
+
+use super::address::Address;
+use super::codec::{Codec, default_codec};
+use super::errors::{FrameError, HandlerError, ObjectPlacementError};
+use error_code::{ErrorCode, ErrorDetails, Retryability};
+use metadata::Metadata;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::time::Duration;
+use thiserror::Error;
+
+pub mod compression;
+pub mod deadline;
+pub mod error_code;
+pub mod frame;
+pub mod handshake;
+pub mod metadata;
+
+/// This is the struct that we serialize and send to the server serialized
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    /// Identifies the request within a connection, so many requests can be in-flight
+    /// at the same time. The server echoes it back on the [ResponseEnvelope]
+    pub request_id: u64,
+    pub handler_type: String,
+    pub handler_id: String,
+    pub message_type: String,
+    pub payload: Vec<u8>,
+    /// Exposed to the handler through [metadata::current]
+    pub metadata: Metadata,
+    /// Time the caller is willing to wait for the response, counted from when the
+    /// request is sent. See [deadline]
+    pub deadline: Option<Duration>,
+    /// Whether handling the request twice is harmless, so the client can send it again
+    /// when the connection is lost before the response arrives. It isn't sent to the server
+    ///
+    /// See [Retryability::IfIdempotent]
+    #[serde(skip)]
+    pub idempotent: bool,
+}
+
+impl RequestEnvelope {
+    pub fn new(
+        handler_type: String,
+        handler_id: String,
+        message_type: String,
+        payload: Vec<u8>,
+    ) -> RequestEnvelope {
+        RequestEnvelope {
+            request_id: 0,
+            handler_type,
+            handler_id,
+            message_type,
+            payload,
+            metadata: Metadata::default(),
+            deadline: None,
+            idempotent: false,
+        }
+    }
+
+    /// Sets the metadata sent along with the request
+    pub fn with_metadata(mut self, metadata: Metadata) -> RequestEnvelope {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets how long the caller is willing to wait for the response
+    pub fn with_deadline(mut self, deadline: Duration) -> RequestEnvelope {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets whether the request can be retried after the connection is lost, see
+    /// [RequestEnvelope::idempotent]
+    pub fn with_idempotent(mut self, idempotent: bool) -> RequestEnvelope {
+        self.idempotent = idempotent;
+        self
+    }
+}
+
+/// This is the struct that we serialize and send back to the client
+///
+/// ```rust
+/// # use rio_rs::protocol::*;
+///
+/// // Success case
+/// let response = ResponseEnvelope::new(vec![1, 2, 3]);
+/// assert!(response.body.is_ok());
+///
+/// // Error case
+/// let response_error = ResponseError::Unknown("something went wrong".to_string());
+/// let response = ResponseEnvelope::err(response_error);
+/// assert!(response.body.is_err());
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+    /// Same as the [RequestEnvelope::request_id] this is a response to
+    pub request_id: u64,
+    pub body: Result<Vec<u8>, ResponseError>,
+    /// Set by the handler with [metadata::set_response]
+    pub metadata: Metadata,
+}
+
+impl ResponseEnvelope {
+    /// New `ResponseEnvelope`. `Ok` Variant
+    pub fn new(body: Vec<u8>) -> ResponseEnvelope {
+        ResponseEnvelope {
+            request_id: 0,
+            body: Ok(body),
+            metadata: Metadata::default(),
+        }
+    }
+
+    /// New `ResponseEnvelope`. `Err` Variant
+    pub fn err(error: ResponseError) -> ResponseEnvelope {
+        ResponseEnvelope {
+            request_id: 0,
+            body: Err(error),
+            metadata: Metadata::default(),
+        }
+    }
+
+    /// Sets the id of the request this envelope responds to
+    pub fn with_request_id(mut self, request_id: u64) -> ResponseEnvelope {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Sets the metadata sent back with the response
+    pub fn with_metadata(mut self, metadata: Metadata) -> ResponseEnvelope {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// Many requests sent on a single frame, possibly for different objects on the same server
+///
+/// The server handles them concurrently, and answers with a single [BatchResponseEnvelope]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequestEnvelope {
+    /// Identifies the batch within a connection, just like [RequestEnvelope::request_id]
+    pub request_id: u64,
+    pub requests: Vec<RequestEnvelope>,
+}
+
+/// Payload of the [RequestEnvelope] sent on a [FrameKind::Deactivate](frame::FrameKind)
+/// frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeactivateRequest {
+    /// The object already ran its [LifecycleMessage::Shutdown](crate::LifecycleMessage)
+    /// hooks, as it deactivated itself with
+    /// [ServiceObject::shutdown](crate::service_object::ServiceObject::shutdown), so the
+    /// server only removes it
+    pub hooks_ran: bool,
+}
+
+/// Responses to a [BatchRequestEnvelope], in the same order as its requests
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResponseEnvelope {
+    /// Same as the [BatchRequestEnvelope::request_id] this is a response to
+    pub request_id: u64,
+    pub responses: Vec<ResponseEnvelope>,
+    /// Set when the batch couldn't be read at all, in which case there are no `responses`
+    /// and all of its requests failed with this error
+    pub error: Option<ResponseError>,
+}
+
+/// Convert a `HandlerError` into a `ResponseEnvelope`.
+///
+/// This is used to convert errors that occur during handler execution
+/// into a response envelope that can be sent back to the client.
+impl From<HandlerError> for ResponseEnvelope {
+    fn from(error: HandlerError) -> Self {
+        let response_err = ResponseError::from(error);
+        ResponseEnvelope::err(response_err)
+    }
+}
+
+/// Error that we serialize back inside of the [ResponseEnvelope]
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum ResponseError {
+    #[error("ServiceObject is in another server")]
+    Redirect(Address),
+
+    #[error("ServiceObject had to be deallocated")]
+    DeallocateServiceObject,
+
+    #[error("ServiceObject could not be allocated")]
+    Allocate,
+
+    #[error("ServiceObject not supported")]
+    NotSupported(String),
+
+    #[error("unknown execution error")]
+    Unknown(String),
+
+    #[error("handler error")]
+    HandlerError(String),
+
+    #[error("error deserializing response")]
+    DeseralizationError(String),
+
+    #[error("error serializing message")]
+    SeralizationError(String),
+
+    #[error("Error caused by the application, serialized with the registry's codec")]
+    ApplicationError(Vec<u8>),
+
+    #[error("invalid frame")]
+    InvalidFrame(String),
+
+    #[error("client and server can't talk to each other")]
+    IncompatibleProtocol(String),
+
+    #[error("request deadline exceeded")]
+    DeadlineExceeded,
+
+    #[error("message handler not found")]
+    HandlerNotFound,
+
+    #[error("ServiceObject not found")]
+    ObjectNotFound,
+
+    #[error("ServiceObject panicked")]
+    Panic(Box<ErrorDetails>),
+
+    #[error("object placement error")]
+    Placement(Box<ErrorDetails>),
+
+    #[error("cluster membership error")]
+    Membership(Box<ErrorDetails>),
+
+    #[error("server unavailable")]
+    Unavailable(Box<ErrorDetails>),
+
+    #[error("ServiceObject has too many messages waiting")]
+    Overloaded,
+}
+
+impl ResponseError {
+    /// Stable code for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ResponseError::Redirect(_) => ErrorCode::Redirect,
+            ResponseError::DeallocateServiceObject => ErrorCode::Deallocated,
+            ResponseError::Allocate => ErrorCode::AllocationFailed,
+            ResponseError::NotSupported(_) => ErrorCode::NotSupported,
+            ResponseError::Unknown(_) | ResponseError::HandlerError(_) => ErrorCode::Unknown,
+            ResponseError::DeseralizationError(_) => ErrorCode::Deserialization,
+            ResponseError::SeralizationError(_) => ErrorCode::Serialization,
+            ResponseError::ApplicationError(_) => ErrorCode::Application,
+            ResponseError::InvalidFrame(_) => ErrorCode::InvalidFrame,
+            ResponseError::IncompatibleProtocol(_) => ErrorCode::IncompatibleProtocol,
+            ResponseError::DeadlineExceeded => ErrorCode::DeadlineExceeded,
+            ResponseError::HandlerNotFound => ErrorCode::HandlerNotFound,
+            ResponseError::ObjectNotFound => ErrorCode::ObjectNotFound,
+            ResponseError::Panic(_) => ErrorCode::Panic,
+            ResponseError::Placement(_) => ErrorCode::Placement,
+            ResponseError::Membership(_) => ErrorCode::Membership,
+            ResponseError::Unavailable(_) => ErrorCode::Unavailable,
+            ResponseError::Overloaded => ErrorCode::Overloaded,
+        }
+    }
+
+    /// Whether the request that failed with this error can be sent again
+    pub fn retryability(&self) -> Retryability {
+        self.code().retryability()
+    }
+
+    /// Whether the request that failed with this error can be sent again, regardless
+    /// of it being idempotent
+    pub fn is_retryable(&self) -> bool {
+        self.retryability() == Retryability::Retryable
+    }
+
+    /// Context of the error, for the errors that carry it
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        match self {
+            ResponseError::Panic(details)
+            | ResponseError::Placement(details)
+            | ResponseError::Membership(details)
+            | ResponseError::Unavailable(details) => Some(details),
+            _ => None,
+        }
+    }
+}
+
+/// Convert a `HandlerError` into a `ResponseError`.
+///
+/// This is used to convert errors that occur during handler execution
+/// into a response error that can be serialized and sent back to the client.
+impl From<HandlerError> for ResponseError {
+    fn from(error: HandlerError) -> Self {
+        match error {
+            HandlerError::ApplicationError(v) => ResponseError::ApplicationError(v),
+            HandlerError::HandlerNotFound => ResponseError::HandlerNotFound,
+            HandlerError::ObjectNotFound => ResponseError::ObjectNotFound,
+            HandlerError::Overloaded => ResponseError::Overloaded,
+            // The object is moving or shutting down, it can be activated again afterwards
+            HandlerError::MailboxClosed => {
+                ResponseError::Unavailable(ErrorDetails::new(error.to_string()).into())
+            }
+            HandlerError::MessageSerializationError => {
+                ResponseError::DeseralizationError(error.to_string())
+            }
+            HandlerError::ResponseSerializationError => {
+                ResponseError::SeralizationError(error.to_string())
+            }
+            inner_err => ResponseError::Unknown(inner_err.to_string()),
+        }
+    }
+}
+
+impl From<FrameError> for ResponseError {
+    fn from(error: FrameError) -> Self {
+        ResponseError::InvalidFrame(error.to_string())
+    }
+}
+
+impl From<ObjectPlacementError> for ResponseError {
+    fn from(error: ObjectPlacementError) -> Self {
+        ResponseError::Placement(ErrorDetails::new(error.to_string()).into())
+    }
+}
+
+/// Errors that might occur while building or using the client,
+/// but that are not related to any behaviour on the server
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    #[error("no servers available")]
+    NoServersAvailable,
+
+    #[error("the requested server is not available")]
+    ServerNotAvailable(String),
+
+    #[error("client was disconnected from the server (no more items on the TCP stream)")]
+    Disconnect,
+
+    #[error("rendenvouz is not available")]
+    RendevouzUnavailable,
+
+    #[error("connectivity error")]
+    Connectivity,
+
+    #[error("unknown client error")]
+    Unknown(String),
+
+    #[error("unknown PlacementLock error")]
+    PlacementLock,
+
+    #[error("error deserializing response")]
+    DeseralizationError(String),
+
+    #[error("error serializing message")]
+    SeralizationError(String),
+
+    #[error("std::io::Error")]
+    IoError(String),
+
+    #[error("client and server can't talk to each other")]
+    IncompatibleProtocol(String),
+
+    #[error("TLS error")]
+    Tls(String),
+}
+
+impl ClientError {
+    /// Stable code for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ClientError::NoServersAvailable
+            | ClientError::ServerNotAvailable(_)
+            | ClientError::RendevouzUnavailable
+            | ClientError::Connectivity => ErrorCode::Unavailable,
+            ClientError::Disconnect | ClientError::IoError(_) => ErrorCode::Disconnected,
+            ClientError::Unknown(_) | ClientError::PlacementLock => ErrorCode::Unknown,
+            ClientError::DeseralizationError(_) => ErrorCode::Deserialization,
+            ClientError::SeralizationError(_) => ErrorCode::Serialization,
+            ClientError::IncompatibleProtocol(_) => ErrorCode::IncompatibleProtocol,
+            ClientError::Tls(_) => ErrorCode::Configuration,
+        }
+    }
+
+    /// Whether the request that failed with this error can be sent again
+    pub fn retryability(&self) -> Retryability {
+        self.code().retryability()
+    }
+
+    /// Whether the request that failed with this error can be sent again, regardless
+    /// of it being idempotent
+    pub fn is_retryable(&self) -> bool {
+        self.retryability() == Retryability::Retryable
+    }
+}
+
+impl From<::std::io::Error> for ClientError {
+    fn from(error: ::std::io::Error) -> Self {
+        ClientError::IoError(error.to_string())
+    }
+}
+
+/// This error can be used with [RequestError] when you don't care
+/// about the possible application errors
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum NoopError {}
+
+/// Union of types for actions that bundle client logic and response handling
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RequestError<E: std::error::Error> {
+    #[error("error in the service response")]
+    ResponseError(ResponseError),
+
+    #[error("client error")]
+    ClientError(ClientError),
+
+    #[error("message serialization error")]
+    SerializationError,
+
+    #[error("application error")]
+    ApplicationError(E),
+}
+
+impl<E: std::error::Error> RequestError<E> {
+    /// Stable code for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            RequestError::ResponseError(err) => err.code(),
+            RequestError::ClientError(err) => err.code(),
+            RequestError::SerializationError => ErrorCode::Serialization,
+            RequestError::ApplicationError(_) => ErrorCode::Application,
+        }
+    }
+
+    /// Whether the request that failed with this error can be sent again
+    pub fn retryability(&self) -> Retryability {
+        self.code().retryability()
+    }
+
+    /// Whether the request that failed with this error can be sent again, regardless
+    /// of it being idempotent
+    pub fn is_retryable(&self) -> bool {
+        self.retryability() == Retryability::Retryable
+    }
+}
+
+impl<E: std::error::Error> From<::std::io::Error> for RequestError<E> {
+    fn from(error: ::std::io::Error) -> Self {
+        Into::<ClientError>::into(error).into()
+    }
+}
+
+/// Convert a `ClientError` into a `RequestError`.
+///
+/// This is useful because client-side operations can fail for reasons that are not
+/// related to the server's response (e.g., network connectivity issues,
+/// deserialization errors on the client-side, etc.).
+///
+/// By implementing `From<ClientError> for RequestError`, we can easily propagate
+/// client-side errors up to the request handling layer, where they can be
+/// treated as part of the overall request error scenario, without needing to
+/// explicitly convert them everywhere they might occur.
+impl<E: std::error::Error> From<ClientError> for RequestError<E> {
+    fn from(err: ClientError) -> Self {
+        RequestError::ClientError(err)
+    }
+}
+
+impl<E: std::error::Error + DeserializeOwned> RequestError<E> {
+    /// Convert a `ResponseError` into a `RequestError`, deserializing
+    /// application errors with `codec`
+    pub fn from_response_error(err: ResponseError, codec: &dyn Codec) -> Self {
+        match err {
+            ResponseError::ApplicationError(ser_error) => {
+                let des_result: Result<E, _> = codec.deserialize(&ser_error);
+                match des_result {
+                    Ok(err) => Self::ApplicationError(err),
+                    Err(codec_err) => {
+                        let error_message =
+                            format!("Application error deserialization issue: {}", codec_err);
+                        let des_error = ResponseError::DeseralizationError(error_message);
+                        Self::ResponseError(des_error)
+                    }
+                }
+            }
+            rest => RequestError::ResponseError(rest),
+        }
+    }
+}
+
+/// Convert a `ResponseError` into a `RequestError` using the [default_codec]
+///
+/// Use [RequestError::from_response_error] when the server uses another codec
+impl<E: std::error::Error + DeserializeOwned> From<ResponseError> for RequestError<E> {
+    fn from(err: ResponseError) -> Self {
+        Self::from_response_error(err, default_codec().as_ref())
+    }
+}
+
+pub mod pubsub {
+    use super::*;
+
+    /// This is the struct that we serialize and send to the server serialized to request a new
+    /// subscription
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SubscriptionRequest {
+        /// Identifies the subscription within a connection, so a connection can carry many
+        /// of them. The server tags every [SubscriptionEnvelope] with it
+        pub subscription_id: u64,
+        pub handler_type: String,
+        pub handler_id: String,
+    }
+
+    /// Asks the server to stop a subscription. The server answers with [SubscriptionClosed]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Unsubscribe {
+        pub subscription_id: u64,
+    }
+
+    /// Sent by the server once a subscription ends, either because the client unsubscribed
+    /// or because the server stopped it. No more messages are sent for it afterwards
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SubscriptionClosed {
+        pub subscription_id: u64,
+    }
+
+    /// [SubscriptionResponse] tagged with the subscription it belongs to
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct SubscriptionEnvelope {
+        pub subscription_id: u64,
+        pub response: SubscriptionResponse,
+    }
+
+    /// Item that is streamed serialized from the server to the client
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SubscriptionResponse {
+        pub body: Result<Vec<u8>, ResponseError>,
+    }
+
+    impl SubscriptionResponse {
+        /// New `Ok` variant
+        pub fn new(body: Vec<u8>) -> Self {
+            SubscriptionResponse { body: Ok(body) }
+        }
+
+        /// New `Err` Variant
+        pub fn err(error: ResponseError) -> Self {
+            SubscriptionResponse { body: Err(error) }
+        }
+    }
+}
+
+/// Server-streaming responses, see [StreamHandler](crate::registry::StreamHandler)
+///
+/// The client sends a [RequestEnvelope] on a [StreamRequest](frame::FrameKind::StreamRequest)
+/// frame, and the server answers with many [StreamItemEnvelope]s tagged with its
+/// [RequestEnvelope::request_id], the last one being [StreamItem::End].
+///
+/// The server sends at most [INITIAL_CREDITS] items ahead of what the client consumed.
+/// The client grants more with [StreamCredit] as it consumes them.
+pub mod streaming {
+    use super::*;
+
+    /// Number of items the server sends before waiting for a [StreamCredit]
+    pub const INITIAL_CREDITS: u32 = 32;
+
+    /// [StreamItem] tagged with the request it belongs to
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct StreamItemEnvelope {
+        pub request_id: u64,
+        pub item: StreamItem,
+    }
+
+    /// Part of a streamed response
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum StreamItem {
+        /// Item serialized with the registry's codec
+        Item(Vec<u8>),
+        /// Error produced by the handler's stream, or why the stream couldn't be created
+        Error(ResponseError),
+        /// No more items will be sent
+        End,
+    }
+
+    /// Allows the server to send `credits` more items for `request_id`
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct StreamCredit {
+        pub request_id: u64,
+        pub credits: u32,
+    }
+
+    /// Asks the server to stop streaming the response for `request_id`
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct StreamCancel {
+        pub request_id: u64,
+    }
+}
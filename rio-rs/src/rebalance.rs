@@ -0,0 +1,456 @@
+//! Moves the objects between the servers of the cluster
+//!
+//! Objects stay on the server they were placed on, so the servers joining the cluster only
+//! get the new objects. A [Rebalancer] (see `rebalancer` on
+//! [Server::builder](crate::server::Server::builder)) periodically moves some of the objects
+//! of its server to the other members of the cluster, as planned by a [RebalancePolicy]:
+//!
+//! - The object stops taking requests, and runs its
+//!   [LifecycleMessage::Shutdown](crate::LifecycleMessage::Shutdown) hooks, so it can save
+//!   its state. If they fail, the object stays where it is
+//! - Its placement points to the new server, so the requests are redirected there
+//! - The next request activates it on the new server
+//!
+//! The objects are only moved if the [ObjectPlacement] can list the objects of each server,
+//! see [ObjectPlacement::server_objects]
+//!
+//! Each server only moves its own objects. A draining server (see
+//! [Server::drain](crate::server::Server::drain)) hands all of them over to the servers
+//! that aren't draining, regardless of the policy
+//!
+//! # Example
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use rio_rs::rebalance::{EvenCount, Rebalancer};
+//! let rebalancer = Rebalancer::builder()
+//!     .policy(EvenCount)
+//!     .interval(Duration::from_secs(30))
+//!     .max_migrations_per_round(5)
+//!     .build();
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bon::Builder;
+use log::{debug, error};
+use tokio::time::{Instant, MissedTickBehavior};
+
+use crate::ObjectId;
+use crate::address::Address;
+use crate::cluster::storage::MembershipStorage;
+use crate::errors::ObjectPlacementError;
+use crate::metrics;
+use crate::object_placement::ObjectPlacement;
+use crate::protocol::ResponseError;
+use crate::protocol::error_code::ErrorDetails;
+use crate::service::Service;
+
+/// Objects placed on a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerLoad {
+    pub address: Address,
+    pub objects: Vec<ObjectId>,
+}
+
+/// Move of an object to the server at `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub object_id: ObjectId,
+    pub to: Address,
+}
+
+/// Decides which objects a server moves to the other servers
+pub trait RebalancePolicy: Debug + Send + Sync + 'static {
+    /// Plans the migrations of the objects of the server at `local`
+    ///
+    /// `cluster` has the load of every server that can take objects, including `local`.
+    /// Only the first migrations might be run, see [Rebalancer]
+    fn plan(&self, local: &Address, cluster: &[ServerLoad]) -> Vec<Migration>;
+}
+
+/// Evens out the number of objects of each server, regardless of their types
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvenCount;
+
+impl RebalancePolicy for EvenCount {
+    fn plan(&self, local: &Address, cluster: &[ServerLoad]) -> Vec<Migration> {
+        even_out(local, cluster, |_| true)
+    }
+}
+
+/// Evens out the number of objects of each type on each server
+///
+/// All the types are balanced by default, use [PerType::for_types] to only balance some
+#[derive(Debug, Clone, Default)]
+pub struct PerType {
+    types: Option<HashSet<String>>,
+}
+
+impl PerType {
+    /// Only moves the objects of `types`
+    pub fn for_types(types: impl IntoIterator<Item = impl Into<String>>) -> PerType {
+        PerType {
+            types: Some(types.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl RebalancePolicy for PerType {
+    fn plan(&self, local: &Address, cluster: &[ServerLoad]) -> Vec<Migration> {
+        let mut types: Vec<_> = cluster
+            .iter()
+            .filter(|load| &load.address == local)
+            .flat_map(|load| load.objects.iter().map(|object_id| &object_id.0))
+            .filter(|object_type| {
+                self.types
+                    .as_ref()
+                    .is_none_or(|types| types.contains(*object_type))
+            })
+            .collect();
+        types.sort();
+        types.dedup();
+        types
+            .into_iter()
+            .flat_map(|object_type| {
+                even_out(local, cluster, |object_id| &object_id.0 == object_type)
+            })
+            .collect()
+    }
+}
+
+/// Moves the objects of `local` matching `filter` to the least loaded servers, until `local`
+/// has at most one object more than any of them
+fn even_out(
+    local: &Address,
+    cluster: &[ServerLoad],
+    filter: impl Fn(&ObjectId) -> bool,
+) -> Vec<Migration> {
+    let Some(local_load) = cluster.iter().find(|load| &load.address == local) else {
+        return vec![];
+    };
+    let mut local_objects: Vec<_> = local_load.objects.iter().filter(|o| filter(o)).collect();
+    local_objects.sort();
+    let mut counts: Vec<_> = cluster
+        .iter()
+        .filter(|load| &load.address != local)
+        .map(|load| {
+            let count = load.objects.iter().filter(|o| filter(o)).count();
+            (&load.address, count)
+        })
+        .collect();
+
+    let mut migrations = vec![];
+    while let Some((to, count)) = counts
+        .iter_mut()
+        .min_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(a.cmp(b)))
+        && local_objects.len() > *count + 1
+        && let Some(object_id) = local_objects.pop()
+    {
+        *count += 1;
+        migrations.push(Migration {
+            object_id: object_id.clone(),
+            to: to.clone(),
+        });
+    }
+    migrations
+}
+
+/// Moves all the `objects` to the least loaded servers of `cluster`
+fn hand_off(objects: Vec<ObjectId>, cluster: &[ServerLoad]) -> Vec<Migration> {
+    let mut counts: Vec<_> = cluster
+        .iter()
+        .map(|load| (&load.address, load.objects.len()))
+        .collect();
+    let mut migrations = vec![];
+    for object_id in objects {
+        let Some((to, count)) = counts
+            .iter_mut()
+            .min_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(a.cmp(b)))
+        else {
+            break;
+        };
+        *count += 1;
+        migrations.push(Migration {
+            object_id,
+            to: to.clone(),
+        });
+    }
+    migrations
+}
+
+/// Periodically moves objects from its server to the others, following a [RebalancePolicy]
+///
+/// The migrations are rate limited: at most `max_migrations_per_round` objects are moved
+/// every `interval`
+#[derive(Debug, Clone, Builder)]
+pub struct Rebalancer {
+    #[builder(with = |policy: impl RebalancePolicy| Arc::new(policy) as Arc<dyn RebalancePolicy>)]
+    policy: Arc<dyn RebalancePolicy>,
+
+    /// How often the server looks for objects to move
+    #[builder(default = Duration::from_secs(60))]
+    interval: Duration,
+
+    /// Maximum number of objects moved every `interval`
+    #[builder(default = 10)]
+    max_migrations_per_round: usize,
+}
+
+impl Rebalancer {
+    /// Moves the objects of the server every `interval`, forever
+    ///
+    /// `hooks_timeout` bounds the shutdown hooks of each object being moved
+    pub(crate) async fn run<S, P>(self, service: Service<S, P>, hooks_timeout: Duration)
+    where
+        S: MembershipStorage + 'static,
+        P: ObjectPlacement + 'static,
+    {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick is immediate, and the server has just started
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            self.run_round(&service, hooks_timeout).await;
+        }
+    }
+
+    /// Plans the migrations, and runs up to `max_migrations_per_round` of them
+    async fn run_round<S, P>(&self, service: &Service<S, P>, hooks_timeout: Duration)
+    where
+        S: MembershipStorage + 'static,
+        P: ObjectPlacement + 'static,
+    {
+        let migrations = match self.plan(service).await {
+            Ok(migrations) => migrations,
+            Err(err) => {
+                error!("Can't plan the rebalancing: {}", err);
+                return;
+            }
+        };
+        for migration in migrations.into_iter().take(self.max_migrations_per_round) {
+            let ObjectId(object_kind, object_id) = &migration.object_id;
+            debug!("Moving {}/{} to {}", object_kind, object_id, migration.to);
+            let deadline = Instant::now() + hooks_timeout;
+            let result = service
+                .migrate_object(object_kind, object_id, migration.to.clone(), deadline)
+                .await;
+            match result {
+                Ok(()) => metrics::migration("ok"),
+                Err(err) => {
+                    metrics::migration("error");
+                    error!(
+                        "Can't move {}/{} to {}: {}",
+                        object_kind, object_id, migration.to, err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Collects the load of the servers that can take objects, and plans the migrations
+    /// of the local one
+    async fn plan<S, P>(&self, service: &Service<S, P>) -> Result<Vec<Migration>, ResponseError>
+    where
+        S: MembershipStorage + 'static,
+        P: ObjectPlacement + 'static,
+    {
+        let members = service
+            .members_storage
+            .active_members()
+            .await
+            .map_err(|e| ResponseError::Membership(ErrorDetails::new(e.to_string()).into()))?;
+        let placement = service.object_placement_provider.read().await;
+        let local_objects = match placement.server_objects(&service.address).await {
+            Err(ObjectPlacementError::Unsupported(_)) => {
+                debug!("The object placement can't list the objects of a server, skipping");
+                return Ok(vec![]);
+            }
+            result => result?,
+        };
+        let mut cluster = vec![];
+        for member in members {
+            if member.draining() || member.address() == &service.address {
+                continue;
+            }
+            let objects = placement.server_objects(member.address()).await?;
+            cluster.push(ServerLoad {
+                address: member.address().clone(),
+                objects,
+            });
+        }
+        drop(placement);
+
+        if service.draining.is_draining() {
+            return Ok(hand_off(local_objects, &cluster));
+        }
+        cluster.push(ServerLoad {
+            address: service.address.clone(),
+            objects: local_objects,
+        });
+        Ok(self.policy.plan(&service.address, &cluster))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load(port: u16, objects: &[(&str, &str)]) -> ServerLoad {
+        ServerLoad {
+            address: Address::new("0.0.0.0", port),
+            objects: objects
+                .iter()
+                .map(|&(object_kind, object_id)| ObjectId::new(object_kind, object_id))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_even_count() {
+        let local = Address::new("0.0.0.0", 5000);
+        let cluster = [
+            load(5000, &[("A", "1"), ("A", "2"), ("A", "3"), ("B", "1")]),
+            load(5001, &[]),
+            load(5002, &[("A", "4")]),
+        ];
+        let migrations = EvenCount.plan(&local, &cluster);
+        assert_eq!(
+            migrations,
+            vec![
+                Migration {
+                    object_id: ObjectId::new("B", "1"),
+                    to: Address::new("0.0.0.0", 5001),
+                },
+                Migration {
+                    object_id: ObjectId::new("A", "3"),
+                    to: Address::new("0.0.0.0", 5001),
+                },
+            ]
+        );
+
+        // Already balanced
+        let cluster = [
+            load(5000, &[("A", "1"), ("A", "2")]),
+            load(5001, &[("A", "3")]),
+        ];
+        assert!(EvenCount.plan(&local, &cluster).is_empty());
+        // Alone in the cluster
+        assert!(EvenCount.plan(&local, &cluster[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_per_type() {
+        let local = Address::new("0.0.0.0", 5000);
+        let cluster = [
+            load(5000, &[("A", "1"), ("A", "2"), ("B", "1"), ("B", "2")]),
+            load(5001, &[("C", "1"), ("C", "2"), ("C", "3")]),
+        ];
+        // The counts are even, but not the types
+        assert!(EvenCount.plan(&local, &cluster).is_empty());
+        let migrations = PerType::default().plan(&local, &cluster);
+        assert_eq!(
+            migrations,
+            vec![
+                Migration {
+                    object_id: ObjectId::new("A", "2"),
+                    to: Address::new("0.0.0.0", 5001),
+                },
+                Migration {
+                    object_id: ObjectId::new("B", "2"),
+                    to: Address::new("0.0.0.0", 5001),
+                },
+            ]
+        );
+
+        let migrations = PerType::for_types(["B"]).plan(&local, &cluster);
+        assert_eq!(
+            migrations,
+            vec![Migration {
+                object_id: ObjectId::new("B", "2"),
+                to: Address::new("0.0.0.0", 5001),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_hand_off() {
+        let objects = vec![ObjectId::new("A", "1"), ObjectId::new("A", "2")];
+        let cluster = [load(5001, &[("A", "3")]), load(5002, &[])];
+        let migrations = hand_off(objects.clone(), &cluster);
+        assert_eq!(
+            migrations,
+            vec![
+                Migration {
+                    object_id: ObjectId::new("A", "1"),
+                    to: Address::new("0.0.0.0", 5002),
+                },
+                Migration {
+                    object_id: ObjectId::new("A", "2"),
+                    to: Address::new("0.0.0.0", 5001),
+                },
+            ]
+        );
+        assert!(hand_off(objects, &[]).is_empty());
+    }
+
+    /// Placement that can't list the objects of a server
+    #[derive(Debug, Clone, Default)]
+    struct ListlessPlacement;
+
+    #[async_trait::async_trait]
+    impl ObjectPlacement for ListlessPlacement {
+        async fn update(
+            &self,
+            _: crate::object_placement::ObjectPlacementItem,
+        ) -> Result<(), ObjectPlacementError> {
+            Ok(())
+        }
+        async fn lookup(&self, _: &ObjectId) -> Result<Option<Address>, ObjectPlacementError> {
+            Ok(None)
+        }
+        async fn clean_server(&self, _: Address) -> Result<(), ObjectPlacementError> {
+            Ok(())
+        }
+        async fn remove(&self, _: &ObjectId) -> Result<(), ObjectPlacementError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_without_server_objects() {
+        use crate::app_data::AppData;
+        use crate::cluster::storage::local::LocalStorage;
+        use crate::protocol::compression::CompressionConfig;
+        use crate::registry::Registry;
+        use crate::server::DrainHandle;
+        use crate::service::ActivationLimits;
+        use tokio::sync::RwLock;
+        use tokio_util::task::TaskTracker;
+
+        let service = Service {
+            address: Address::new("0.0.0.0", 5000),
+            registry: Arc::new(RwLock::new(Registry::new())),
+            members_storage: LocalStorage::default(),
+            object_placement_provider: Arc::new(RwLock::new(ListlessPlacement)),
+            app_data: Arc::new(AppData::new()),
+            compression: CompressionConfig::default(),
+            in_flight: TaskTracker::new(),
+            draining: DrainHandle::default(),
+            activation_limits: ActivationLimits::default(),
+            layered: None,
+        };
+        let rebalancer = Rebalancer::builder()
+            .policy(EvenCount)
+            .interval(Duration::from_secs(30))
+            .max_migrations_per_round(5)
+            .build();
+
+        // The round is skipped
+        assert_eq!(rebalancer.plan(&service).await, Ok(vec![]));
+    }
+}
@@ -5,6 +5,7 @@
 use crate::app_data::AppData;
 
 use async_trait::async_trait;
+use futures::stream::BoxStream;
 use serde::{Serialize, de::DeserializeOwned};
 use std::sync::Arc;
 
@@ -20,4 +21,59 @@ pub trait Handler<M>: Send + Sync {
     ) -> Result<Self::Returns, Self::Error>;
 }
 
+/// Stream of items returned by a [StreamHandler]
+pub type HandlerStream<T, E> = BoxStream<'static, Result<T, E>>;
+
+/// Handler that answers a message with a stream of items, which are delivered only to
+/// the caller, as they are produced
+///
+/// The object is locked only while `handle_stream` runs, so the returned stream can't
+/// borrow from it. Anything it needs from the object has to be cloned or moved into it.
+///
+/// The items are sent to the client as it consumes them, so a stream that produces
+/// items faster than the client can read waits for it to catch up.
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use async_trait::async_trait;
+/// # use futures::StreamExt;
+/// # use rio_rs::prelude::*;
+/// # use rio_rs::registry::{HandlerStream, StreamHandler};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Default, WithId, TypeName)]
+/// struct Table {
+///     id: String,
+///     rows: Vec<String>,
+/// }
+///
+/// #[derive(TypeName, Message, Deserialize, Serialize)]
+/// struct Export {}
+///
+/// #[async_trait]
+/// impl StreamHandler<Export> for Table {
+///     type Item = String;
+///     type Error = NoopError;
+///
+///     async fn handle_stream(
+///         &mut self,
+///         _message: Export,
+///         _context: Arc<AppData>,
+///     ) -> Result<HandlerStream<Self::Item, Self::Error>, Self::Error> {
+///         let rows = self.rows.clone();
+///         Ok(futures::stream::iter(rows).map(Ok).boxed())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait StreamHandler<M>: Send + Sync {
+    type Item: Serialize + Send;
+    type Error: Serialize + Send;
+
+    async fn handle_stream(
+        &mut self,
+        message: M,
+        context: Arc<AppData>,
+    ) -> Result<HandlerStream<Self::Item, Self::Error>, Self::Error>;
+}
+
 pub trait Message: Serialize + DeserializeOwned {}
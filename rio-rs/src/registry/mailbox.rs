@@ -0,0 +1,345 @@
+//! Mailboxes of the objects in the [Registry](super::Registry)
+//!
+//! The messages of each object wait in a bounded queue, the mailbox, and they are refused
+//! with [HandlerError::Overloaded] once it is full. A single task handles them, one at a
+//! time and in the order they arrived. It starts once a message arrives, and it stops once
+//! the mailbox is empty, so idle objects don't hold on to a task
+//!
+//! While the task runs, it owns the object. The handlers still see the metadata and the
+//! deadline of the request they handle, see [crate::protocol::metadata]
+//!
+//! A mailbox can be closed while the object is being deactivated. It then refuses the new
+//! messages with [HandlerError::MailboxClosed], except for the
+//! [LifecycleMessage](crate::LifecycleMessage)s
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use crate::errors::HandlerError;
+use crate::metrics;
+use crate::protocol::{deadline, metadata};
+
+/// Number of messages that can wait on each mailbox, unless set with
+/// [Registry::set_default_mailbox_capacity](super::Registry::set_default_mailbox_capacity)
+pub const DEFAULT_MAILBOX_CAPACITY: usize = 1024;
+
+pub(crate) type BoxedObject = Box<dyn Any + Send + Sync>;
+type Job = Box<dyn for<'a> FnOnce(&'a mut BoxedObject) -> BoxFuture<'a, ()> + Send>;
+
+/// Message waiting in a mailbox
+struct Queued {
+    job: Job,
+    enqueued_at: Instant,
+}
+
+struct MailboxState {
+    queue: VecDeque<Queued>,
+    /// The object, while no task is handling its messages
+    object: Option<BoxedObject>,
+    /// Whether new messages are refused, see [Mailbox::close]
+    closed: bool,
+    /// Number of streams the object serves that are still open, see [Mailbox::open_stream]
+    open_streams: usize,
+}
+
+/// Queues the messages of an object, see the [module docs](self)
+#[derive(Clone)]
+pub(crate) struct Mailbox {
+    type_id: Arc<str>,
+    capacity: usize,
+    state: Arc<Mutex<MailboxState>>,
+}
+
+/// Keeps the object busy while a stream it serves is open, see [Mailbox::open_stream]
+pub(crate) struct OpenStream {
+    mailbox: Mailbox,
+}
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        self.mailbox.state().open_streams -= 1;
+    }
+}
+
+impl Mailbox {
+    /// Mailbox of `object`, with room for `capacity` messages (at least one)
+    pub(crate) fn new(type_id: &str, object: BoxedObject, capacity: usize) -> Mailbox {
+        Mailbox {
+            type_id: Arc::from(type_id),
+            capacity: capacity.max(1),
+            state: Arc::new(Mutex::new(MailboxState {
+                queue: VecDeque::new(),
+                object: Some(object),
+                closed: false,
+                open_streams: 0,
+            })),
+        }
+    }
+
+    /// The lock is never held across `.await`s, nor while running the jobs, so it can't be
+    /// poisoned
+    fn state(&self) -> MutexGuard<'_, MailboxState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Number of messages waiting to be handled
+    pub(crate) fn depth(&self) -> usize {
+        self.state().queue.len()
+    }
+
+    /// Whether the object is handling a message, has messages waiting, or serves a stream
+    /// that is still open
+    pub(crate) fn is_busy(&self) -> bool {
+        let state = self.state();
+        state.object.is_none() || !state.queue.is_empty() || state.open_streams > 0
+    }
+
+    /// Counts a stream served by the object as open until the returned guard is dropped
+    pub(crate) fn open_stream(&self) -> OpenStream {
+        self.state().open_streams += 1;
+        OpenStream {
+            mailbox: self.clone(),
+        }
+    }
+
+    /// Refuses the new messages, the ones already waiting are still handled
+    pub(crate) fn close(&self) {
+        self.state().closed = true;
+    }
+
+    /// Takes new messages again, after [Mailbox::close]
+    pub(crate) fn reopen(&self) {
+        self.state().closed = false;
+    }
+
+    /// Runs `f` on the object once the messages ahead of it are handled
+    ///
+    /// It fails with [HandlerError::Overloaded] if the mailbox is full, and with
+    /// [HandlerError::MailboxClosed] if it is closed. If the returned future is dropped, `f`
+    /// is skipped, or cancelled if it is already running. Panics in `f` are resumed here, so
+    /// the caller can tell them apart from errors
+    pub(crate) async fn call<F, R>(&self, f: F) -> Result<R, HandlerError>
+    where
+        F: for<'a> FnOnce(&'a mut BoxedObject) -> BoxFuture<'a, R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.enqueue(f, false).await
+    }
+
+    /// Same as [Mailbox::call], but `f` is queued even if the mailbox is closed when
+    /// `lifecycle` is set
+    pub(crate) async fn enqueue<F, R>(&self, f: F, lifecycle: bool) -> Result<R, HandlerError>
+    where
+        F: for<'a> FnOnce(&'a mut BoxedObject) -> BoxFuture<'a, R> + Send + 'static,
+        R: Send + 'static,
+    {
+        // The task-locals of the caller are carried over to the task handling the message
+        let request_metadata = metadata::try_current();
+        let request_deadline = deadline::current();
+        let span = tracing::Span::current();
+
+        let (mut result_sender, result_receiver) = oneshot::channel();
+        let job: Job = Box::new(move |object| {
+            Box::pin(async move {
+                // Nobody is waiting for it anymore
+                if result_sender.is_closed() {
+                    return;
+                }
+                let handled = async move {
+                    let mut handled = f(object);
+                    if let Some(request_deadline) = request_deadline {
+                        handled = Box::pin(deadline::scope(request_deadline, handled));
+                    }
+                    match request_metadata {
+                        Some(request_metadata) => {
+                            let (result, response_metadata) =
+                                metadata::scope(request_metadata, handled).await;
+                            (result, Some(response_metadata))
+                        }
+                        None => (handled.await, None),
+                    }
+                };
+                let handled = AssertUnwindSafe(handled.instrument(span)).catch_unwind();
+                tokio::select! {
+                    result = handled => {
+                        let _ = result_sender.send(result);
+                    }
+                    _ = result_sender.closed() => {}
+                }
+            })
+        });
+        self.push(job, lifecycle)?;
+        match result_receiver.await {
+            Ok(Ok((result, response_metadata))) => {
+                if let Some(response_metadata) = response_metadata {
+                    metadata::extend_response(response_metadata);
+                }
+                Ok(result)
+            }
+            Ok(Err(panic)) => std::panic::resume_unwind(panic),
+            // The task is gone, e.g. the runtime is shutting down
+            Err(_) => Err(HandlerError::Unknown),
+        }
+    }
+
+    /// Queues `job`, starting the task that handles the messages if it isn't running
+    fn push(&self, job: Job, lifecycle: bool) -> Result<(), HandlerError> {
+        let mut state = self.state();
+        if state.closed && !lifecycle {
+            return Err(HandlerError::MailboxClosed);
+        }
+        if state.queue.len() >= self.capacity {
+            metrics::mailbox_overload(&self.type_id);
+            return Err(HandlerError::Overloaded);
+        }
+        state.queue.push_back(Queued {
+            job,
+            enqueued_at: Instant::now(),
+        });
+        metrics::mailbox_depth(&self.type_id, 1);
+        if let Some(object) = state.object.take() {
+            tokio::spawn(self.clone().run(object));
+        }
+        Ok(())
+    }
+
+    /// Handles the messages until the mailbox is empty, then gives the object back to it
+    async fn run(self, mut object: BoxedObject) {
+        loop {
+            let queued = {
+                let mut state = self.state();
+                match state.queue.pop_front() {
+                    Some(queued) => queued,
+                    None => {
+                        state.object = Some(object);
+                        return;
+                    }
+                }
+            };
+            metrics::mailbox_depth(&self.type_id, -1);
+            metrics::mailbox_wait(&self.type_id, queued.enqueued_at.elapsed());
+            (queued.job)(&mut object).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fifo() {
+        let mailbox = Mailbox::new("Counter", Box::new(Vec::<usize>::new()), 16);
+        let mut calls = vec![];
+        for i in 0..10 {
+            calls.push(mailbox.call(move |object| {
+                Box::pin(async move {
+                    let values: &mut Vec<usize> = object.downcast_mut().unwrap();
+                    values.push(i);
+                })
+            }));
+        }
+        futures::future::try_join_all(calls).await.unwrap();
+        let values = mailbox
+            .call(|object| {
+                Box::pin(async move { object.downcast_ref::<Vec<usize>>().unwrap().clone() })
+            })
+            .await
+            .unwrap();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+        assert_eq!(mailbox.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_overloaded() {
+        let mailbox = Mailbox::new("Counter", Box::new(()), 1);
+        let release = Arc::new(Notify::new());
+
+        // Keeps the object busy
+        let inner_release = release.clone();
+        let busy = tokio::spawn({
+            let mailbox = mailbox.clone();
+            async move {
+                mailbox
+                    .call(move |_| Box::pin(async move { inner_release.notified().await }))
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let waiting = tokio::spawn({
+            let mailbox = mailbox.clone();
+            async move { mailbox.call(|_| Box::pin(async {})).await }
+        });
+        while mailbox.depth() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let overloaded = mailbox.call(|_| Box::pin(async {})).await;
+        assert_eq!(overloaded, Err(HandlerError::Overloaded));
+        assert_eq!(mailbox.depth(), 1);
+
+        release.notify_one();
+        busy.await.unwrap().unwrap();
+        waiting.await.unwrap().unwrap();
+        assert_eq!(mailbox.depth(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_metadata() {
+        let mailbox = Mailbox::new("Counter", Box::new(()), 1);
+        let request_metadata = metadata::Metadata::from([("tenant".into(), "acme".into())]);
+        let (tenant, response_metadata) = metadata::scope(request_metadata, async {
+            mailbox
+                .call(|_| {
+                    Box::pin(async {
+                        metadata::set_response("served-by", "node-1");
+                        metadata::get("tenant")
+                    })
+                })
+                .await
+                .unwrap()
+        })
+        .await;
+        assert_eq!(tenant, Some("acme".to_string()));
+        assert_eq!(response_metadata["served-by"], "node-1");
+    }
+
+    #[tokio::test]
+    async fn test_closed() {
+        let mailbox = Mailbox::new("Counter", Box::new(()), 1);
+        mailbox.close();
+        let refused = mailbox.call(|_| Box::pin(async {})).await;
+        assert_eq!(refused, Err(HandlerError::MailboxClosed));
+        mailbox.enqueue(|_| Box::pin(async {}), true).await.unwrap();
+
+        mailbox.reopen();
+        mailbox.call(|_| Box::pin(async {})).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_panic() {
+        let mailbox = Mailbox::new("Counter", Box::new(()), 1);
+        let result = AssertUnwindSafe(mailbox.call(|_| Box::pin(async { panic!("boom") })))
+            .catch_unwind()
+            .await;
+        let panic = result.unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"boom"));
+
+        // The object keeps taking messages
+        mailbox.call(|_| Box::pin(async {})).await.unwrap();
+    }
+}
@@ -1,9 +1,20 @@
 //! Trait object registry
 //!
 //! Provides storage for objects and maps their callables to handle registered message types
-
-use crate::{WithId, app_data::AppData, errors::HandlerError};
+//!
+//! Each object is owned by a task, which handles the object's messages one at a time from a
+//! bounded mailbox (see [Registry::set_mailbox_capacity])
+
+use crate::{
+    LifecycleMessage, WithId,
+    app_data::AppData,
+    codec::{Codec, default_codec},
+    errors::HandlerError,
+    metrics,
+};
 use dashmap::DashMap;
+use futures::StreamExt;
+use futures::stream::BoxStream;
 use log::warn;
 use std::{
     any::{Any, TypeId},
@@ -12,51 +23,105 @@ use std::{
     future::Future,
     pin::Pin,
     sync::Arc,
+    time::Duration,
 };
-use tokio::sync::RwLock;
+use tokio::time::Instant;
 use tracing::Instrument;
 
 mod handler;
 mod identifiable_type;
+mod mailbox;
 
-pub use handler::{Handler, Message};
+pub use handler::{Handler, HandlerStream, Message, StreamHandler};
 pub use identifiable_type::IdentifiableType;
+pub use mailbox::DEFAULT_MAILBOX_CAPACITY;
+
+use mailbox::{BoxedObject, Mailbox};
 
-type LockHashMap<K, V> = Arc<DashMap<K, RwLock<V>>>;
 type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 type AsyncRet = BoxFuture<Result<Vec<u8>, HandlerError>>;
-type BoxedCallback = Box<dyn Fn(&str, &str, &[u8], Arc<AppData>) -> AsyncRet + Send + Sync>;
+type BoxedCallback =
+    Box<dyn Fn(&str, &str, &[u8], Arc<AppData>, Arc<dyn Codec>) -> AsyncRet + Send + Sync>;
+/// Serialized items of a [StreamHandler]'s response
+pub type ItemStream = BoxStream<'static, Result<Vec<u8>, HandlerError>>;
+type AsyncStreamRet = BoxFuture<Result<ItemStream, HandlerError>>;
+type BoxedStreamCallback =
+    Box<dyn Fn(&str, &str, &[u8], Arc<AppData>, Arc<dyn Codec>) -> AsyncStreamRet + Send + Sync>;
 type BoxedDefaultWithId = Box<dyn Fn(String) -> Box<dyn Any + Send + Sync> + Send + Sync>;
 
 /// Store objects dynamically, registering handlers for different message types
 ///
 /// The registry also offers the possibility of registering loose functions unique by its argument
 /// and return type
-#[derive(Default)]
 pub struct Registry {
     /// Object allocation map
-    /// `(ObjectTypeName, ObjectId)` -> `Box<Obj>`
-    object_map: LockHashMap<(String, String), Box<dyn Any + Send + Sync>>,
+    /// `(ObjectTypeName, ObjectId)` -> Mailbox of the task owning `Box<Obj>`
+    object_map: Arc<DashMap<(String, String), Mailbox>>,
+
+    /// When each object in the object map last got a message
+    /// `(ObjectTypeName, ObjectId)` -> `Instant`
+    last_used: DashMap<(String, String), Instant>,
+
+    /// How long objects of each type can go without messages before they are collected
+    /// ObjectTypeName -> `None` if they are never collected
+    idle_timeouts: HashMap<String, Option<Duration>>,
+
+    /// How many objects of each type can be active at the same time
+    /// ObjectTypeName -> Maximum
+    max_activations: HashMap<String, usize>,
+
+    /// How many messages can wait on the mailboxes of each type
+    /// ObjectTypeName -> Capacity
+    mailbox_capacities: HashMap<String, usize>,
+
+    /// Mailbox capacity of the types without their own
+    default_mailbox_capacity: usize,
 
     /// Maps the objects types and messages to their handler functions
     /// (ObjectTypeName, MessageTypeName) -> Result<SerializedResult, Error>
     handler_map_: papaya::HashMap<(String, String), BoxedCallback>,
 
+    /// Maps the objects types and messages to their [StreamHandler] functions
+    /// (ObjectTypeName, MessageTypeName) -> Result<Stream<SerializedItem>, Error>
+    stream_handler_map: papaya::HashMap<(String, String), BoxedStreamCallback>,
+
     /// Maps the types to the object constructors
     type_map: HashMap<String, BoxedDefaultWithId>,
 
     /// Internal control for duplicate type ids
     supported_types: HashMap<String, TypeId>,
+
+    /// Serialization for the messages, their responses and errors
+    codec: Arc<dyn Codec>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry {
+            object_map: Default::default(),
+            last_used: Default::default(),
+            idle_timeouts: Default::default(),
+            max_activations: Default::default(),
+            mailbox_capacities: Default::default(),
+            default_mailbox_capacity: DEFAULT_MAILBOX_CAPACITY,
+            handler_map_: Default::default(),
+            stream_handler_map: Default::default(),
+            type_map: Default::default(),
+            supported_types: Default::default(),
+            codec: default_codec(),
+        }
+    }
 }
 
 impl Debug for Registry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Registry")
-            .field("object_map", &"DashMap<(String, String), Box<dyn Any + Send + Sync>>")
+            .field("object_map", &"DashMap<(String, String), Mailbox>")
             .field(
                 "handler_map_",
-                &"papaya::HashMap<(String, String), Box<dyn Fn(&str, &str, &[u8], Arc<AppData>) -> AsyncRet + Send + Sync>>",
+                &"papaya::HashMap<(String, String), Box<dyn Fn(&str, &str, &[u8], Arc<AppData>, Arc<dyn Codec>) -> AsyncRet + Send + Sync>>",
             )
+            .field("codec", &self.codec)
             .finish()
     }
 }
@@ -66,14 +131,34 @@ impl Registry {
         Registry::default()
     }
 
+    /// Registry that uses `codec` instead of the [default_codec]
+    pub fn with_codec(codec: Arc<dyn Codec>) -> Registry {
+        Registry {
+            codec,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the codec used for the messages, their responses and errors
+    ///
+    /// Clients need to use the same codec, otherwise they will be refused
+    /// when connecting to the server
+    pub fn set_codec(&mut self, codec: Arc<dyn Codec>) {
+        self.codec = codec;
+    }
+
+    /// Codec used for the messages, their responses and errors
+    pub fn codec(&self) -> Arc<dyn Codec> {
+        self.codec.clone()
+    }
+
     /// Add a trait object of type `T` to the object map
     pub async fn add<T>(&mut self, k: String, v: T)
     where
         T: 'static + IdentifiableType + Send + Sync,
     {
         let type_id = T::user_defined_type_id().to_string();
-        self.object_map
-            .insert((type_id, k), RwLock::new(Box::new(v)));
+        self.insert_boxed_object(type_id, k, Box::new(v)).await;
     }
 
     /// Add new types to the contructor map
@@ -128,50 +213,75 @@ impl Registry {
         let object_map = self.object_map.clone();
         let type_id = T::user_defined_type_id().to_string();
         let message_type_id = M::user_defined_type_id().to_string();
+        let handler_message_type_id = message_type_id.clone();
 
         let callable = move |type_id: &str,
                              object_id: &str,
                              encoded_message: &[u8],
-                             context: Arc<AppData>|
+                             context: Arc<AppData>,
+                             codec: Arc<dyn Codec>|
               -> AsyncRet {
-            let message: M = match bincode::deserialize(encoded_message) {
+            let message: M = match codec.deserialize(encoded_message) {
                 Ok(val) => val,
                 Err(_) => return Box::pin(async { Err(HandlerError::MessageSerializationError) }),
             };
 
             let inner_object_map = object_map.clone();
             let object_key = (type_id.to_string(), object_id.to_string());
+            let message_type_id = handler_message_type_id.clone();
             Box::pin(
                 async move {
-                    let boxed_object_guard = inner_object_map
+                    // The map's guard is released right away, holding it while the message
+                    // waits would block the whole shard
+                    let mailbox = inner_object_map
                         .get(&object_key)
+                        .map(|entry| entry.value().clone())
                         .ok_or(HandlerError::ObjectNotFound)?;
-                    let mut boxed_object = boxed_object_guard
-                        .write()
-                        .instrument(tracing::info_span!("handler_lock_acquire"))
-                        .await;
-
-                    let object: &mut T =
-                        boxed_object.downcast_mut().ok_or(HandlerError::Unknown)?;
-
-                    let handler_result = object
-                        .handle(message, context)
-                        .instrument(tracing::info_span!("handler_handle"))
-                        .await;
-
-                    // Serializes the error into a binary variant
-                    // We do this to support 'custom' error types for each one of the Handler's
-                    // implementation
-                    let ret = handler_result.map_err(|err| {
-                        let ser_err = bincode::serialize(&err).unwrap_or_else(|_| {
-                            tracing::error!("Error to serialize handler error");
-                            vec![]
-                        });
-                        HandlerError::ApplicationError(ser_err)
-                    })?;
-
-                    // Serialize the whole result back to the caller
-                    bincode::serialize(&ret).or(Err(HandlerError::ResponseSerializationError))
+
+                    let handler_type = object_key.0.clone();
+                    let handler_codec = codec.clone();
+                    // The shutdown hooks still run once the mailbox is closed
+                    let lifecycle = message_type_id == LifecycleMessage::user_defined_type_id();
+                    mailbox
+                        .enqueue(
+                            move |boxed_object: &mut BoxedObject| {
+                                Box::pin(async move {
+                                    let object: &mut T =
+                                        boxed_object.downcast_mut().ok_or(HandlerError::Unknown)?;
+
+                                    let handle_start = Instant::now();
+                                    let handler_result = object
+                                        .handle(message, context)
+                                        .instrument(tracing::info_span!("handler_handle"))
+                                        .await;
+                                    metrics::handler_duration(
+                                        &handler_type,
+                                        &message_type_id,
+                                        handle_start.elapsed(),
+                                    );
+
+                                    // Serializes the error into a binary variant
+                                    // We do this to support 'custom' error types for each one of
+                                    // the Handler's implementation
+                                    let ret = handler_result.map_err(|err| {
+                                        let ser_err =
+                                            handler_codec.serialize(&err).unwrap_or_else(|_| {
+                                                tracing::error!("Error to serialize handler error");
+                                                vec![]
+                                            });
+                                        HandlerError::ApplicationError(ser_err)
+                                    })?;
+
+                                    // Serialize the whole result back to the caller
+                                    handler_codec
+                                        .serialize(&ret)
+                                        .or(Err(HandlerError::ResponseSerializationError))
+                                })
+                            },
+                            lifecycle,
+                        )
+                        .instrument(tracing::info_span!("handler_mailbox"))
+                        .await?
                 }
                 .instrument(tracing::info_span!("handler_get_and_handle")),
             )
@@ -181,6 +291,85 @@ impl Registry {
         self.handler_map_.pin().insert(callable_key, boxed_callable);
     }
 
+    /// Adds a [StreamHandler] for messages of type `M` to the type `T`
+    pub fn add_stream_handler<T, M>(&mut self)
+    where
+        T: 'static + StreamHandler<M> + IdentifiableType + Send + Sync,
+        M: 'static + IdentifiableType + Message + Send + Sync,
+    {
+        let object_map = self.object_map.clone();
+        let type_id = T::user_defined_type_id().to_string();
+        let message_type_id = M::user_defined_type_id().to_string();
+
+        let callable = move |type_id: &str,
+                             object_id: &str,
+                             encoded_message: &[u8],
+                             context: Arc<AppData>,
+                             codec: Arc<dyn Codec>|
+              -> AsyncStreamRet {
+            let message: M = match codec.deserialize(encoded_message) {
+                Ok(val) => val,
+                Err(_) => return Box::pin(async { Err(HandlerError::MessageSerializationError) }),
+            };
+
+            let inner_object_map = object_map.clone();
+            let object_key = (type_id.to_string(), object_id.to_string());
+            Box::pin(
+                async move {
+                    let mailbox = inner_object_map
+                        .get(&object_key)
+                        .map(|entry| entry.value().clone())
+                        .ok_or(HandlerError::ObjectNotFound)?;
+
+                    // The object is only busy while the stream is created
+                    let stream_result = mailbox
+                        .call(move |boxed_object: &mut BoxedObject| {
+                            Box::pin(async move {
+                                let object: &mut T =
+                                    boxed_object.downcast_mut().ok_or(HandlerError::Unknown)?;
+                                Ok(object
+                                    .handle_stream(message, context)
+                                    .instrument(tracing::info_span!("handler_handle_stream"))
+                                    .await)
+                            })
+                        })
+                        .instrument(tracing::info_span!("handler_mailbox"))
+                        .await??;
+
+                    let serialize_error = {
+                        let codec = codec.clone();
+                        move |err: T::Error| {
+                            let ser_err = codec.serialize(&err).unwrap_or_else(|_| {
+                                tracing::error!("Error to serialize handler error");
+                                vec![]
+                            });
+                            HandlerError::ApplicationError(ser_err)
+                        }
+                    };
+                    let stream = stream_result.map_err(&serialize_error)?;
+
+                    // Each item is serialized as it is produced, the object is busy until the
+                    // stream is dropped
+                    let open_stream = mailbox.open_stream();
+                    let item_stream = stream.map(move |item| {
+                        let _open_stream = &open_stream;
+                        let item = item.map_err(&serialize_error)?;
+                        codec
+                            .serialize(&item)
+                            .or(Err(HandlerError::ResponseSerializationError))
+                    });
+                    Ok(item_stream.boxed())
+                }
+                .instrument(tracing::info_span!("handler_get_and_handle_stream")),
+            )
+        };
+        let boxed_callable: BoxedStreamCallback = Box::new(callable);
+        let callable_key = (type_id, message_type_id);
+        self.stream_handler_map
+            .pin()
+            .insert(callable_key, boxed_callable);
+    }
+
     pub async fn send(
         &self,
         type_id: &str,
@@ -189,6 +378,7 @@ impl Registry {
         message: &[u8],
         context: Arc<AppData>,
     ) -> Result<Vec<u8>, HandlerError> {
+        self.touch(type_id, object_id);
         let callable_key = (type_id.to_string(), message_type_id.to_string());
 
         let future_result = {
@@ -197,11 +387,241 @@ impl Registry {
                 .handler_map_
                 .get(&callable_key, &handler_map_pin)
                 .ok_or(HandlerError::HandlerNotFound)?;
-            message_handler(type_id, object_id, message, context)
+            message_handler(type_id, object_id, message, context, self.codec.clone())
+        };
+        let result = future_result.await;
+        // The idle time counts from the end of the last message, the object is busy (never
+        // idle) while it handles it
+        self.touch(type_id, object_id);
+        result
+    }
+
+    /// Whether messages of `message_type_id` can be sent to the objects of `type_id`, see
+    /// [Registry::add_handler]
+    pub fn has_handler(&self, type_id: &str, message_type_id: &str) -> bool {
+        let callable_key = (type_id.to_string(), message_type_id.to_string());
+        self.handler_map_.pin().contains_key(&callable_key)
+    }
+
+    /// Same as [Registry::send], but for messages handled by a [StreamHandler]
+    pub async fn send_stream(
+        &self,
+        type_id: &str,
+        object_id: &str,
+        message_type_id: &str,
+        message: &[u8],
+        context: Arc<AppData>,
+    ) -> Result<ItemStream, HandlerError> {
+        self.touch(type_id, object_id);
+        let callable_key = (type_id.to_string(), message_type_id.to_string());
+
+        let future_result = {
+            let handler_map_pin = self.stream_handler_map.guard();
+            let message_handler = self
+                .stream_handler_map
+                .get(&callable_key, &handler_map_pin)
+                .ok_or(HandlerError::HandlerNotFound)?;
+            message_handler(type_id, object_id, message, context, self.codec.clone())
         };
         future_result.await
     }
 
+    /// Marks the object as used now, if it is in the registry
+    fn touch(&self, type_id: &str, object_id: &str) {
+        let object_key = (type_id.to_string(), object_id.to_string());
+        if let Some(mut last_used) = self.last_used.get_mut(&object_key) {
+            *last_used = Instant::now();
+        }
+    }
+
+    /// Sets how long objects of type `T` can go without messages before the server
+    /// deactivates them, overriding the server's default
+    ///
+    /// With `None`, the objects of type `T` are never deactivated for being idle
+    pub fn set_idle_timeout<T: IdentifiableType>(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeouts
+            .insert(T::user_defined_type_id().to_string(), idle_timeout);
+    }
+
+    /// How long objects of `type_id` can go without messages before they are collected
+    ///
+    /// Types without their own timeout use `default_idle_timeout`
+    pub fn idle_timeout(
+        &self,
+        type_id: &str,
+        default_idle_timeout: Option<Duration>,
+    ) -> Option<Duration> {
+        self.idle_timeouts
+            .get(type_id)
+            .copied()
+            .unwrap_or(default_idle_timeout)
+    }
+
+    /// `(ObjectTypeName, ObjectId)` of the objects that haven't got any message for longer
+    /// than their [Registry::idle_timeout]
+    ///
+    /// Busy objects are never idle: the ones handling a message, with messages waiting on
+    /// their mailbox, or serving a stream that is still open
+    pub fn idle_objects(&self, default_idle_timeout: Option<Duration>) -> Vec<(String, String)> {
+        let now = Instant::now();
+        self.last_used
+            .iter()
+            .filter(|item| self.is_idle_at(item.key(), *item.value(), now, default_idle_timeout))
+            .map(|item| item.key().clone())
+            .collect()
+    }
+
+    /// Whether the object hasn't got any message for longer than its
+    /// [Registry::idle_timeout], and isn't busy, see [Registry::idle_objects]
+    pub fn is_idle(
+        &self,
+        type_id: &str,
+        object_id: &str,
+        default_idle_timeout: Option<Duration>,
+    ) -> bool {
+        let object_key = (type_id.to_string(), object_id.to_string());
+        let last_used = self.last_used.get(&object_key).map(|item| *item.value());
+        last_used.is_some_and(|last_used| {
+            self.is_idle_at(&object_key, last_used, Instant::now(), default_idle_timeout)
+        })
+    }
+
+    fn is_idle_at(
+        &self,
+        object_key: &(String, String),
+        last_used: Instant,
+        now: Instant,
+        default_idle_timeout: Option<Duration>,
+    ) -> bool {
+        let timed_out = self
+            .idle_timeout(&object_key.0, default_idle_timeout)
+            .is_some_and(|idle_timeout| now.duration_since(last_used) >= idle_timeout);
+        timed_out
+            && !self
+                .object_map
+                .get(object_key)
+                .is_some_and(|mailbox| mailbox.is_busy())
+    }
+
+    /// Limits how many objects of type `T` the server keeps active at the same time
+    ///
+    /// Once the limit is reached, the server either deactivates the least recently used
+    /// objects of type `T`, or rejects the new ones, see
+    /// [ActivationLimitPolicy](crate::server::ActivationLimitPolicy)
+    pub fn set_max_activations<T: IdentifiableType>(&mut self, max_activations: Option<usize>) {
+        let type_id = T::user_defined_type_id().to_string();
+        match max_activations {
+            Some(max_activations) => self.max_activations.insert(type_id, max_activations),
+            None => self.max_activations.remove(&type_id),
+        };
+    }
+
+    /// How many objects of `type_id` can be active at the same time, if limited
+    pub fn max_activations(&self, type_id: &str) -> Option<usize> {
+        self.max_activations.get(type_id).copied()
+    }
+
+    /// Sets how many messages can wait on the mailbox of each object of type `T`,
+    /// overriding the registry's default
+    ///
+    /// Once an object's mailbox is full, its new messages fail with
+    /// [HandlerError::Overloaded]. It only applies to the objects activated afterwards, and
+    /// the capacity is at least one
+    pub fn set_mailbox_capacity<T: IdentifiableType>(&mut self, capacity: usize) {
+        self.mailbox_capacities
+            .insert(T::user_defined_type_id().to_string(), capacity);
+    }
+
+    /// Sets how many messages can wait on the mailboxes of the types without their own
+    /// capacity, see [Registry::set_mailbox_capacity]. It is [DEFAULT_MAILBOX_CAPACITY]
+    /// otherwise
+    pub fn set_default_mailbox_capacity(&mut self, capacity: usize) {
+        self.default_mailbox_capacity = capacity;
+    }
+
+    /// How many messages can wait on the mailbox of each object of `type_id`
+    pub fn mailbox_capacity(&self, type_id: &str) -> usize {
+        self.mailbox_capacities
+            .get(type_id)
+            .copied()
+            .unwrap_or(self.default_mailbox_capacity)
+    }
+
+    /// Number of messages waiting on the object's mailbox, if it is in the registry
+    pub fn mailbox_depth(&self, type_id: &str, object_id: &str) -> Option<usize> {
+        let object_key = (type_id.to_string(), object_id.to_string());
+        self.object_map
+            .get(&object_key)
+            .map(|mailbox| mailbox.depth())
+    }
+
+    /// Refuses the new messages of the object with [HandlerError::MailboxClosed], other than
+    /// the [LifecycleMessage]s, until [Registry::reopen_mailbox]
+    ///
+    /// The messages already waiting are still handled. It returns whether the object is in
+    /// the registry
+    pub fn close_mailbox(&self, type_id: &str, object_id: &str) -> bool {
+        let object_key = (type_id.to_string(), object_id.to_string());
+        self.object_map
+            .get(&object_key)
+            .map(|mailbox| mailbox.close())
+            .is_some()
+    }
+
+    /// Takes the new messages of the object again, see [Registry::close_mailbox]
+    pub fn reopen_mailbox(&self, type_id: &str, object_id: &str) {
+        let object_key = (type_id.to_string(), object_id.to_string());
+        if let Some(mailbox) = self.object_map.get(&object_key) {
+            mailbox.reopen();
+        }
+    }
+
+    /// Number of objects in the registry, only counting the ones of `type_id` if given
+    pub fn count_objects(&self, type_id: Option<&str>) -> usize {
+        match type_id {
+            Some(type_id) => self
+                .last_used
+                .iter()
+                .filter(|item| item.key().0 == type_id)
+                .count(),
+            None => self.last_used.len(),
+        }
+    }
+
+    /// `(ObjectTypeName, ObjectId)` of the object that got a message the longest time ago,
+    /// only considering the ones of `type_id` if given
+    pub fn least_recently_used(&self, type_id: Option<&str>) -> Option<(String, String)> {
+        self.last_used
+            .iter()
+            .filter(|item| type_id.is_none_or(|type_id| item.key().0 == type_id))
+            .min_by_key(|item| *item.value())
+            .map(|item| item.key().clone())
+    }
+
+    /// Registered object types, see [Registry::add_type]
+    pub fn types(&self) -> Vec<String> {
+        self.type_map.keys().cloned().collect()
+    }
+
+    /// Registered handlers, as `(ObjectTypeName, MessageTypeName)`, see [Registry::add_handler]
+    pub fn handlers(&self) -> Vec<(String, String)> {
+        self.handler_map_.pin().keys().cloned().collect()
+    }
+
+    /// Registered stream handlers, as `(ObjectTypeName, MessageTypeName)`, see
+    /// [Registry::add_stream_handler]
+    pub fn stream_handlers(&self) -> Vec<(String, String)> {
+        self.stream_handler_map.pin().keys().cloned().collect()
+    }
+
+    /// `(ObjectTypeName, ObjectId)` of every object in the registry
+    pub fn objects(&self) -> Vec<(String, String)> {
+        self.object_map
+            .iter()
+            .map(|item| item.key().clone())
+            .collect()
+    }
+
     pub async fn has(&self, type_id: &str, object_id: &str) -> bool {
         let object_key = (type_id.to_string(), object_id.to_string());
         self.object_map.get(&object_key).is_some()
@@ -214,15 +634,25 @@ impl Registry {
         object_id: String,
         object: Box<dyn Any + 'static + Send + Sync>,
     ) {
-        self.object_map
-            .insert((type_id, object_id), RwLock::new(object));
+        self.last_used
+            .insert((type_id.clone(), object_id.clone()), Instant::now());
+        let mailbox = Mailbox::new(&type_id, object, self.mailbox_capacity(&type_id));
+        let replaced = self
+            .object_map
+            .insert((type_id.clone(), object_id), mailbox);
+        if replaced.is_none() {
+            metrics::active_objects(&type_id, 1);
+        }
     }
 
     /// remove object from registry
     pub async fn remove(&self, type_id: String, object_id: String) {
         let key = (type_id, object_id);
 
-        if self.object_map.remove(&key).is_none() {
+        self.last_used.remove(&key);
+        if self.object_map.remove(&key).is_some() {
+            metrics::active_objects(&key.0, -1);
+        } else {
             warn!(
                 "Failed to remove object from mapping ({:?} not present)",
                 key
@@ -330,6 +760,29 @@ mod test {
         }
     }
 
+    #[derive(Serialize, Deserialize)]
+    struct SleepMessage {
+        pub millis: u64,
+    }
+
+    impl IdentifiableType for SleepMessage {
+        fn user_defined_type_id() -> &'static str {
+            "SleepMessage"
+        }
+    }
+    impl Message for SleepMessage {}
+
+    #[async_trait]
+    impl Handler<SleepMessage> for Human {
+        type Returns = ();
+        type Error = String;
+
+        async fn handle(&mut self, message: SleepMessage, _: Arc<AppData>) -> Result<(), String> {
+            tokio::time::sleep(Duration::from_millis(message.millis)).await;
+            Ok(())
+        }
+    }
+
     #[async_trait]
     impl Handler<HiMessage> for Proxy {
         type Returns = String;
@@ -387,6 +840,36 @@ mod test {
         }
     }
 
+    #[async_trait]
+    impl StreamHandler<HiMessage> for Human {
+        type Item = String;
+        type Error = String;
+
+        async fn handle_stream(
+            &mut self,
+            _message: HiMessage,
+            _: Arc<AppData>,
+        ) -> Result<HandlerStream<String, String>, String> {
+            let id = self.id.clone();
+            let items = vec![Ok(format!("hi {}", id)), Err("err".to_string())];
+            Ok(futures::stream::iter(items).boxed())
+        }
+    }
+
+    #[async_trait]
+    impl StreamHandler<ErrorMessage> for Human {
+        type Item = String;
+        type Error = String;
+
+        async fn handle_stream(
+            &mut self,
+            message: ErrorMessage,
+            _: Arc<AppData>,
+        ) -> Result<HandlerStream<String, String>, String> {
+            Err(message.value)
+        }
+    }
+
     #[tokio::test]
     async fn sanity_check() {
         fn is_sync<T: Sync>(_t: T) {}
@@ -407,6 +890,8 @@ mod test {
         registry.add("john".to_string(), obj).await;
         registry.add_handler::<Human, HiMessage>();
         registry.add_handler::<Human, GoodbyeMessage>();
+        assert!(registry.has_handler("Human", "HiMessage"));
+        assert!(!registry.has_handler("Human", "ErrorMessage"));
         registry
             .send(
                 "Human",
@@ -464,6 +949,24 @@ mod test {
         assert_eq!(result, "hi")
     }
 
+    #[tokio::test]
+    async fn test_return_with_codec() {
+        let codec: Arc<dyn Codec> = Arc::new(crate::codec::json::JsonCodec);
+        let mut registry = Registry::with_codec(codec.clone());
+        registry.add("john".to_string(), Human::default()).await;
+        registry.add_handler::<Human, HiMessage>();
+        let ret = registry
+            .send(
+                "Human",
+                "john",
+                "HiMessage",
+                b"{}",
+                Arc::new(AppData::new()),
+            )
+            .await;
+        assert_eq!(ret.unwrap(), b"\"hi\"");
+    }
+
     #[tokio::test]
     async fn test_return_error() {
         let mut registry = Registry::new();
@@ -564,6 +1067,8 @@ mod test {
         let registry = Arc::new(RwLock::new(registry));
 
         registry.write().await.add_handler::<Proxy, HiMessage>();
+        // Every proxy forwards its message to the same object at once
+        registry.write().await.set_mailbox_capacity::<Proxy>(count);
 
         let mut join_set = tokio::task::JoinSet::new();
         let local_reg = registry.clone();
@@ -682,6 +1187,161 @@ mod test {
         assert!(!registry.has("Human", "not john").await);
     }
 
+    #[tokio::test]
+    async fn test_objects() {
+        let mut registry = Registry::new();
+        assert!(registry.objects().is_empty());
+        registry.add("john".to_string(), Human::default()).await;
+        assert_eq!(
+            registry.objects(),
+            vec![("Human".to_string(), "john".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_objects() {
+        let mut registry = Registry::new();
+        registry.add_handler::<Human, HiMessage>();
+        registry.add("john".to_string(), Human::default()).await;
+        registry.add("mary".to_string(), Human::default()).await;
+        let idle_timeout = Some(Duration::from_millis(50));
+
+        // Nothing is collected without a timeout
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(registry.idle_objects(None).is_empty());
+
+        let mut idle_objects = registry.idle_objects(idle_timeout);
+        idle_objects.sort();
+        assert_eq!(
+            idle_objects,
+            vec![
+                ("Human".to_string(), "john".to_string()),
+                ("Human".to_string(), "mary".to_string()),
+            ]
+        );
+
+        // Messages keep the objects active
+        registry
+            .send(
+                "Human",
+                "john",
+                "HiMessage",
+                &bincode::serialize(&HiMessage {}).unwrap(),
+                Arc::new(AppData::new()),
+            )
+            .await
+            .unwrap();
+        assert!(!registry.is_idle("Human", "john", idle_timeout));
+        assert!(registry.is_idle("Human", "mary", idle_timeout));
+
+        // The type's timeout overrides the default one
+        registry.set_idle_timeout::<Human>(None);
+        assert!(registry.idle_objects(idle_timeout).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_busy_objects_are_not_idle() {
+        let mut registry = Registry::new();
+        registry.add_handler::<Human, SleepMessage>();
+        registry.add_stream_handler::<Human, HiMessage>();
+        registry.add("john".to_string(), Human::default()).await;
+        let registry = Arc::new(registry);
+        let idle_timeout = Some(Duration::from_millis(50));
+
+        // The handler runs for longer than the idle timeout
+        let sleeping = tokio::spawn({
+            let registry = registry.clone();
+            async move {
+                registry
+                    .send(
+                        "Human",
+                        "john",
+                        "SleepMessage",
+                        &bincode::serialize(&SleepMessage { millis: 150 }).unwrap(),
+                        Arc::new(AppData::new()),
+                    )
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!registry.is_idle("Human", "john", idle_timeout));
+        assert!(registry.idle_objects(idle_timeout).is_empty());
+        sleeping.await.unwrap().unwrap();
+
+        // Open streams keep the object busy too
+        let stream = registry
+            .send_stream(
+                "Human",
+                "john",
+                "HiMessage",
+                &bincode::serialize(&HiMessage {}).unwrap(),
+                Arc::new(AppData::new()),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(!registry.is_idle("Human", "john", idle_timeout));
+
+        drop(stream);
+        assert!(registry.is_idle("Human", "john", idle_timeout));
+        assert_eq!(
+            registry.idle_objects(idle_timeout),
+            vec![("Human".to_string(), "john".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used() {
+        let mut registry = Registry::new();
+        registry.add_handler::<Human, HiMessage>();
+        assert_eq!(registry.least_recently_used(None), None);
+
+        for id in ["john", "mary", "bob"] {
+            registry.add(id.to_string(), Human::default()).await;
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        registry.set_max_activations::<Human>(Some(3));
+        assert_eq!(registry.max_activations("Human"), Some(3));
+        assert_eq!(registry.count_objects(None), 3);
+        assert_eq!(registry.count_objects(Some("Human")), 3);
+        assert_eq!(registry.count_objects(Some("Animal")), 0);
+        assert_eq!(
+            registry.least_recently_used(Some("Human")),
+            Some(("Human".to_string(), "john".to_string()))
+        );
+        assert_eq!(registry.least_recently_used(Some("Animal")), None);
+
+        // Messages make the objects recently used
+        registry
+            .send(
+                "Human",
+                "john",
+                "HiMessage",
+                &bincode::serialize(&HiMessage {}).unwrap(),
+                Arc::new(AppData::new()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            registry.least_recently_used(None),
+            Some(("Human".to_string(), "mary".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_capacity() {
+        let mut registry = Registry::new();
+        assert_eq!(registry.mailbox_capacity("Human"), DEFAULT_MAILBOX_CAPACITY);
+        registry.set_default_mailbox_capacity(10);
+        registry.set_mailbox_capacity::<Human>(1);
+        assert_eq!(registry.mailbox_capacity("Human"), 1);
+        assert_eq!(registry.mailbox_capacity("Proxy"), 10);
+
+        assert_eq!(registry.mailbox_depth("Human", "john"), None);
+        registry.add("john".to_string(), Human::default()).await;
+        assert_eq!(registry.mailbox_depth("Human", "john"), Some(0));
+    }
+
     #[tokio::test]
     async fn test_insert_object() {
         let mut registry = Registry::new();
@@ -705,4 +1365,75 @@ mod test {
         let human = boxed_human.downcast::<Human>().unwrap();
         assert_eq!(human.id(), "1");
     }
+
+    #[tokio::test]
+    async fn test_send_stream() {
+        let mut registry = Registry::new();
+        registry
+            .add(
+                "john".to_string(),
+                Human {
+                    id: "john".to_string(),
+                },
+            )
+            .await;
+        registry.add_stream_handler::<Human, HiMessage>();
+        let stream = registry
+            .send_stream(
+                "Human",
+                "john",
+                "HiMessage",
+                &bincode::serialize(&HiMessage {}).unwrap(),
+                Arc::new(AppData::new()),
+            )
+            .await
+            .unwrap();
+        let items: Vec<_> = stream.collect().await;
+        assert_eq!(items.len(), 2);
+        let first: String = bincode::deserialize(items[0].as_ref().unwrap()).unwrap();
+        assert_eq!(first, "hi john");
+        assert_eq!(
+            items[1],
+            Err(HandlerError::ApplicationError(
+                bincode::serialize("err").unwrap()
+            ))
+        );
+
+        // Regular handlers and stream handlers are registered apart
+        let ret = registry
+            .send(
+                "Human",
+                "john",
+                "HiMessage",
+                &bincode::serialize(&HiMessage {}).unwrap(),
+                Arc::new(AppData::new()),
+            )
+            .await;
+        assert_eq!(ret, Err(HandlerError::HandlerNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_send_stream_error() {
+        let mut registry = Registry::new();
+        registry.add("john".to_string(), Human::default()).await;
+        registry.add_stream_handler::<Human, ErrorMessage>();
+        let ret = registry
+            .send_stream(
+                "Human",
+                "john",
+                "ErrorMessage",
+                &bincode::serialize(&ErrorMessage {
+                    value: "Test".to_string(),
+                })
+                .unwrap(),
+                Arc::new(AppData::new()),
+            )
+            .await;
+        assert_eq!(
+            ret.err(),
+            Some(HandlerError::ApplicationError(
+                bincode::serialize("Test").unwrap()
+            ))
+        );
+    }
 }
@@ -4,35 +4,65 @@ use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use bon::Builder;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use netwatch::ip::LocalAddresses;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::task::JoinSet;
+use tokio::time::{Instant, MissedTickBehavior};
 use tokio::{net::TcpListener, sync::RwLock};
-use tower::{Service as TowerService, ServiceExt};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tower::ServiceExt;
 
-use crate::ObjectId;
+use crate::address::Address;
 use crate::app_data::AppData;
+use crate::client::{Client, ClientBuilder};
 use crate::cluster::membership_protocol::ClusterProvider;
 use crate::cluster::storage::MembershipStorage;
 use crate::errors::ServerError;
+use crate::health::{HealthCheck, ServerStats};
 use crate::object_placement::ObjectPlacement;
-use crate::protocol::ResponseError;
+use crate::protocol::compression::CompressionConfig;
+use crate::protocol::error_code::ErrorDetails;
 use crate::protocol::pubsub::SubscriptionRequest;
 use crate::protocol::{RequestEnvelope, ResponseEnvelope};
+use crate::protocol::{RequestError, ResponseError};
+use crate::rebalance::Rebalancer;
 use crate::registry::Registry;
-use crate::service::Service;
+use crate::service::{ActivationLimits, RequestLayer, Service};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsClientConfig, TlsServerConfig};
+use crate::transport::Stream;
 
 /// Internal commands, e.g., shutdown a service object
 #[derive(Debug)]
 pub enum AdminCommands {
+    /// Shuts the server down gracefully, see [Server::shutdown]
     ServerExit,
-    // Shutdown(hander_type, handler_id)
+    /// Puts the server in draining mode, see [Server::drain]
+    Drain,
+    /// Takes the server out of draining mode, see [DrainHandle::resume]
+    Resume,
+    /// Deactivates an object wherever it lives in the cluster
+    ///
+    /// `Shutdown(handler_type, handler_id)`
     Shutdown(String, String),
+    /// Same as [AdminCommands::Shutdown], but the result is sent back on the channel once
+    /// the server holding the object acknowledges it
+    ShutdownWithAck(String, String, oneshot::Sender<ShutdownResult>),
+    /// Same as [AdminCommands::ShutdownWithAck], for an object that already ran its
+    /// shutdown hooks, so it is only removed. Sent by
+    /// [ServiceObject::shutdown](crate::service_object::ServiceObject::shutdown)
+    ShutdownAfterHooks(String, String, oneshot::Sender<ShutdownResult>),
 }
 
+/// Result of a [AdminCommands::ShutdownWithAck]
+pub type ShutdownResult = Result<(), ResponseError>;
+
 /// Channel for [AdminCommands]
 pub type AdminReceiver = mpsc::UnboundedReceiver<AdminCommands>;
 
@@ -72,6 +102,76 @@ pub type InternalClientReceiver = mpsc::UnboundedReceiver<SendCommand>;
 /// Channels for internal client
 pub type InternalClientSender = mpsc::UnboundedSender<SendCommand>;
 
+/// What a [Server] does with new activations once it reaches its activation limits, see
+/// [Server::builder] and [Registry::set_max_activations]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActivationLimitPolicy {
+    /// Deactivates the least recently used objects to make room for the new ones
+    #[default]
+    EvictLeastRecentlyUsed,
+    /// Rejects the new activations with [ResponseError::Unavailable], so the clients try
+    /// them on another server
+    Reject,
+}
+
+/// Triggers the graceful shutdown of a [Server] from anywhere, e.g. from another task
+/// while the server is running
+///
+/// It can be cloned, and it is obtained through [Server::shutdown_handle]
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle(CancellationToken);
+
+impl ShutdownHandle {
+    /// Starts the graceful shutdown of the server, see [Server::shutdown]
+    pub fn shutdown(&self) {
+        self.0.cancel();
+    }
+
+    /// Whether the shutdown has been started
+    pub fn is_shutdown(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    /// Completes once the shutdown is started
+    pub async fn wait(&self) {
+        self.0.cancelled().await;
+    }
+}
+
+/// Puts a [Server] in draining mode, or takes it out of it, from anywhere
+///
+/// It can be cloned, and it is obtained through [Server::drain_handle]
+#[derive(Debug, Clone)]
+pub struct DrainHandle(Arc<watch::Sender<bool>>);
+
+impl Default for DrainHandle {
+    fn default() -> Self {
+        DrainHandle(Arc::new(watch::Sender::new(false)))
+    }
+}
+
+impl DrainHandle {
+    /// Starts draining the server, see [Server::drain]
+    pub fn drain(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Stops draining the server, so it takes new objects again
+    pub fn resume(&self) {
+        self.0.send_replace(false);
+    }
+
+    /// Whether the server is draining
+    pub fn is_draining(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Gets notified of the changes on the draining status
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.subscribe()
+    }
+}
+
 /// Application Server. It handles object registration ([Registry]),
 /// clustering (through [ClusterProvider]s), server state (via [AppData]),
 /// and more.
@@ -93,10 +193,33 @@ where
     #[builder(default = "0.0.0.0:0".to_string())]
     address: String,
 
+    /// Address the other servers and the clients use to reach this server
+    ///
+    /// It is the address registered in the [MembershipStorage], stored in the object
+    /// placements and sent in the redirects. Set it whenever the bind address isn't
+    /// reachable by the peers, e.g. in containers or on hosts with many interfaces
+    ///
+    /// It can be a DNS name, e.g. the name of a pod behind a headless service
+    ///
+    /// Defaults to [Server::try_local_addr]
+    advertised_address: Option<Address>,
+
     /// Address given by the user
     #[cfg(feature = "http")]
     http_members_storage_address: Option<String>,
 
+    /// Serves the health endpoints on this address, see [crate::health]
+    #[cfg(feature = "http")]
+    http_health_address: Option<String>,
+
+    /// Serves the admin API on this address, see [crate::admin]
+    #[cfg(feature = "http")]
+    http_admin_address: Option<String>,
+
+    /// Serves the Prometheus metrics on this address, see [crate::metrics]
+    #[cfg(all(feature = "http", feature = "prometheus"))]
+    http_metrics_address: Option<String>,
+
     #[builder(with = |registry: Registry| Arc::new(RwLock::new(registry)))]
     registry: Arc<RwLock<Registry>>,
     cluster_provider: C,
@@ -105,6 +228,70 @@ where
     #[builder(with = |app_data: AppData| Arc::new(app_data), default = Arc::new(AppData::new()))]
     app_data: Arc<AppData>,
 
+    /// Compression algorithms offered to clients, and the size from which
+    /// responses are compressed
+    #[builder(default)]
+    compression: CompressionConfig,
+
+    /// Accepts only TLS connections if set
+    #[cfg(feature = "tls")]
+    tls: Option<TlsServerConfig>,
+
+    /// Used to connect to the other servers in the cluster, e.g. to deactivate the
+    /// objects living there. Needed if they only accept TLS connections
+    #[cfg(feature = "tls")]
+    cluster_tls: Option<TlsClientConfig>,
+
+    /// How long the server waits for the in-flight requests once it starts
+    /// shutting down, see [Server::shutdown]. It also bounds the shutdown hooks of the
+    /// objects it deactivates
+    #[builder(default = Duration::from_secs(30))]
+    drain_timeout: Duration,
+
+    /// Objects that go without messages for longer than this are deactivated, unless
+    /// their type sets its own timeout with [Registry::set_idle_timeout]
+    ///
+    /// Objects are only deactivated for being idle if this, or their type's timeout, is set
+    idle_timeout: Option<Duration>,
+
+    /// How often the server looks for idle objects
+    #[builder(default = Duration::from_secs(60))]
+    collection_interval: Duration,
+
+    /// Maximum number of objects, of any type, the server keeps active at the same time
+    ///
+    /// Types can also have their own limits, see [Registry::set_max_activations]
+    max_activations: Option<usize>,
+
+    /// What happens to new activations once a limit is reached
+    #[builder(default)]
+    activation_limit_policy: ActivationLimitPolicy,
+
+    /// Tower layer around the service handling the requests, from the clients and from
+    /// the internal client (see [crate::service_object::ServiceObject::send])
+    ///
+    /// Many layers can be combined with [tower::ServiceBuilder], see [RequestLayer]
+    #[builder(with = |layer: impl RequestLayer| Arc::new(layer) as Arc<dyn RequestLayer>)]
+    layer: Option<Arc<dyn RequestLayer>>,
+
+    /// Moves the objects of this server to the other members of the cluster, e.g. once
+    /// new servers join it. See [crate::rebalance]
+    ///
+    /// Without it, the objects stay where they were placed until they are deactivated
+    rebalancer: Option<Rebalancer>,
+
+    #[builder(skip)]
+    shutdown: ShutdownHandle,
+
+    #[builder(skip)]
+    draining: DrainHandle,
+
+    #[builder(skip)]
+    in_flight: TaskTracker,
+
+    #[builder(skip)]
+    stats: ServerStats,
+
     #[builder(skip = PhantomData {})]
     _marker: PhantomData<S>,
 }
@@ -131,6 +318,58 @@ where
         self.app_data.set(data);
     }
 
+    /// Handle to shut the server down while it runs
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Handle to drain the server while it runs
+    pub fn drain_handle(&self) -> DrainHandle {
+        self.draining.clone()
+    }
+
+    /// Queries the liveness and readiness of the server while it runs, see [crate::health]
+    pub fn health_check(&self) -> HealthCheck<S, P> {
+        HealthCheck {
+            members_storage: self.cluster_provider.members_storage().clone(),
+            object_placement_provider: self.object_placement_provider.clone(),
+            registry: self.registry.clone(),
+            shutdown: self.shutdown.clone(),
+            draining: self.draining.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+
+    /// Puts the server in draining mode, e.g. before taking it out of the cluster
+    ///
+    /// While draining, the server:
+    /// - Keeps handling the requests for the objects that are already active here
+    /// - Places the new objects on the other members of the cluster, redirecting the
+    ///   clients there
+    /// - Advertises its draining status in the [MembershipStorage], so the clients stop
+    ///   picking it for new objects
+    /// - Reports itself as not ready, see [crate::health]
+    ///
+    /// Use a [DrainHandle] to trigger it while the server runs, or to resume taking new
+    /// objects
+    pub fn drain(&self) {
+        self.draining.drain();
+    }
+
+    /// Starts the graceful shutdown of the server
+    ///
+    /// Once it starts, [Server::run]:
+    /// - Stops accepting new connections, and closes the existing ones
+    /// - Waits for the in-flight requests, up to `drain_timeout`
+    /// - Deactivates every object, running their [LifecycleMessage::Shutdown] hooks
+    /// - Removes the placements pointing to this server
+    /// - Marks this server as inactive in the [MembershipStorage]
+    ///
+    /// Use a [ShutdownHandle] to trigger it while the server runs
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown();
+    }
+
     /// Setup the server for running it
     pub async fn bind(&mut self) -> ServerResult<TcpListener> {
         let listener = TcpListener::bind(&self.address)
@@ -145,12 +384,11 @@ where
     /// and fallback to the address given by tokio's listener
     ///
     /// <div class="warning">
-    /// **TODO**
     ///
-    /// It potentially won't work on machine with multiple interfaces. So we need to
-    /// add support to address mapping per node before merging this.
+    /// It potentially won't work on machine with multiple interfaces, nor when the bind
+    /// address isn't the one the peers can reach.
     ///
-    /// For now, if that is your case, you need to specify the IP for binding
+    /// If that is your case, set `advertised_address` on [Server::builder]
     /// </div>
     pub fn try_local_addr(listener: &TcpListener) -> ServerResult<SocketAddr> {
         let addr_result = listener.local_addr();
@@ -173,34 +411,104 @@ where
     /// - New TCP connections from clients
     /// - [AdminCommands] messages from running objects
     /// - [ClusterProvider] server loop
+    /// - Deactivation of idle objects, see [Registry::set_idle_timeout]
+    /// - Migration of objects to other servers, if there is a [Rebalancer]
     ///
     /// If any of these fails, the server stops running with a [ServerError]
+    ///
+    /// It also stops once it is shut down, through [Server::shutdown], a [ShutdownHandle]
+    /// or [AdminCommands::ServerExit], in which case it only returns after leaving the
+    /// cluster gracefully
     pub async fn run(&mut self, listener: TcpListener) -> ServerResult<()> {
         let (admin_sender, admin_receiver) = mpsc::unbounded_channel::<AdminCommands>();
+        #[cfg(feature = "http")]
+        let admin_http_server_task = self.http_admin_address.clone().map(|addr| {
+            let api = crate::admin::AdminApi {
+                registry: self.registry.clone(),
+                object_placement_provider: self.object_placement_provider.clone(),
+                admin_sender: admin_sender.clone(),
+                shutdown: self.shutdown.clone(),
+                draining: self.draining.clone(),
+            };
+            tokio::spawn(async move {
+                crate::admin::serve(addr, api)
+                    .await
+                    .inspect_err(|err| error!("Admin endpoint error: {}", err))
+                    .ok();
+            })
+        });
         self.app_data(admin_sender);
 
         let (internal_client_sender, internal_client_receiver) =
             mpsc::unbounded_channel::<SendCommand>();
         self.app_data(internal_client_sender);
 
-        let local_addr = Self::try_local_addr(&listener)?.to_string();
+        // Used by the service objects to talk to each other, see [crate::service_object::ServiceObject::send]
+        let codec = self.registry.read().await.codec();
+        self.app_data(codec);
+
+        let local_addr = match self.advertised_address.clone() {
+            Some(advertised_address) => advertised_address,
+            None => Self::try_local_addr(&listener)?.into(),
+        };
 
         let mut service = Service::<S, P>::try_from(&*self)?;
         service.address = local_addr.clone();
-        let mut accept_task = tokio::spawn(Self::accept(listener, service));
+        // Built once, so the layers see the requests of every connection and of the
+        // internal client
+        let service = self.with_layer(service);
+        let internal_client_service = service.clone();
+        let drain_status_task = tokio::spawn(Self::advertise_drain_status(
+            self.cluster_provider.members_storage().clone(),
+            local_addr.clone(),
+            self.draining.subscribe(),
+        ));
+
+        let mut accept_task = tokio::spawn(Self::accept(
+            listener,
+            service,
+            self.stats.clone(),
+            #[cfg(feature = "tls")]
+            self.tls.clone(),
+        ));
 
         let cluster_provider = self.cluster_provider.clone();
         let inner_local_addr = local_addr.clone();
-        let mut cluster_provider_task =
-            tokio::spawn(async move { cluster_provider.serve(&inner_local_addr).await });
+        let cluster_provider_running = self.stats.cluster_provider_running.clone();
+        let draining = self.draining.clone();
+        let mut cluster_provider_task = tokio::spawn(async move {
+            cluster_provider_running.store(true, Ordering::Relaxed);
+            let result = cluster_provider
+                .serve_with_drain_handle(&inner_local_addr, draining)
+                .await;
+            cluster_provider_running.store(false, Ordering::Relaxed);
+            result
+        });
 
-        let mut service = Service::<S, P>::try_from(&*self)?;
-        service.address = local_addr.clone();
         let mut internal_client_task = tokio::spawn(async move {
-            Self::consume_internal_client_commands(internal_client_receiver, service).await
+            Self::consume_internal_client_commands(
+                internal_client_receiver,
+                internal_client_service,
+            )
+            .await
         });
 
-        let admin_commands_fut = self.consume_admin_commands(admin_receiver);
+        let mut service = Service::<S, P>::try_from(&*self)?;
+        service.address = local_addr.clone();
+        let admin_commands_fut = self.consume_admin_commands(admin_receiver, service);
+
+        let mut service = Service::<S, P>::try_from(&*self)?;
+        service.address = local_addr.clone();
+        let collector_task = tokio::spawn(Self::collect_idle_objects(
+            service.clone(),
+            self.idle_timeout,
+            self.collection_interval,
+            self.drain_timeout,
+        ));
+        let rebalancer_task = self
+            .rebalancer
+            .clone()
+            .map(|rebalancer| tokio::spawn(rebalancer.run(service.clone(), self.drain_timeout)));
 
         #[cfg(feature = "http")]
         let mut cluster_storage_http_server_task =
@@ -220,6 +528,28 @@ where
                 })
             };
 
+        // Keeps serving while the server drains, so it is reported as not ready
+        #[cfg(feature = "http")]
+        let health_http_server_task = self.http_health_address.clone().map(|addr| {
+            let health_check = self.health_check();
+            tokio::spawn(async move {
+                crate::health::serve(addr, health_check)
+                    .await
+                    .inspect_err(|err| error!("Health endpoint error: {}", err))
+                    .ok();
+            })
+        });
+
+        #[cfg(all(feature = "http", feature = "prometheus"))]
+        let metrics_http_server_task = self.http_metrics_address.clone().map(|addr| {
+            tokio::spawn(async move {
+                crate::metrics::serve(addr)
+                    .await
+                    .inspect_err(|err| error!("Metrics endpoint error: {}", err))
+                    .ok();
+            })
+        });
+
         #[cfg(not(feature = "http"))]
         let mut cluster_storage_http_server_task = tokio::spawn(async move {
             warn!("HTTP Members Storage not enabled");
@@ -269,20 +599,189 @@ where
             _ = &mut cluster_storage_http_server_task => {
                 warn!("Http Server for Cluster Storage finished earlier");
             }
-
+            _ = self.shutdown.wait() => {
+                info!("Shutting down");
+            }
         }
 
         info!("Stoping server");
+        // Stops accepting connections, and drops the existing ones. The requests that
+        // were already read from them keep running
         accept_task.abort();
+        collector_task.abort();
+        drain_status_task.abort();
+        if let Some(task) = rebalancer_task {
+            task.abort();
+        }
+
+        let graceful = self.shutdown.is_shutdown();
+        if graceful {
+            self.wait_in_flight().await;
+            // The objects might still talk to each other through the internal client
+            self.deactivate_objects(&service).await;
+        }
+
         cluster_provider_task.abort();
         internal_client_task.abort();
         cluster_storage_http_server_task.abort();
+
+        let leave_result = if graceful {
+            self.leave_cluster(&local_addr).await
+        } else {
+            Ok(())
+        };
+        #[cfg(feature = "http")]
+        for task in [health_http_server_task, admin_http_server_task]
+            .into_iter()
+            .flatten()
+        {
+            task.abort();
+        }
+        #[cfg(all(feature = "http", feature = "prometheus"))]
+        if let Some(task) = metrics_http_server_task {
+            task.abort();
+        }
+        leave_result?;
         info!("Server stopped");
 
         Ok(())
     }
 
-    async fn accept(listener: TcpListener, service: Service<S, P>) -> ServerResult<()> {
+    /// Same as [Server::run], but it also shuts the server down gracefully once the
+    /// process receives a `SIGINT` (Ctrl+C) or, on Unix, a `SIGTERM`
+    pub async fn run_until_signal(&mut self, listener: TcpListener) -> ServerResult<()> {
+        let shutdown = self.shutdown_handle();
+        let signal_task = tokio::spawn(async move {
+            shutdown_signal().await;
+            info!("Received a shutdown signal");
+            shutdown.shutdown();
+        });
+        let result = self.run(listener).await;
+        signal_task.abort();
+        result
+    }
+
+    /// Waits for the in-flight requests, up to `drain_timeout`
+    async fn wait_in_flight(&self) {
+        self.in_flight.close();
+        let drained = tokio::time::timeout(self.drain_timeout, self.in_flight.wait()).await;
+        if drained.is_err() {
+            warn!(
+                "{} requests still running after the drain timeout",
+                self.in_flight.len()
+            );
+        }
+    }
+
+    /// Sends [LifecycleMessage::Shutdown] to every object and removes them from the
+    /// registry
+    ///
+    /// The objects that don't finish their hooks within `drain_timeout` are removed anyway
+    async fn deactivate_objects(&self, service: &Service<S, P>) {
+        let deadline = Instant::now() + self.drain_timeout;
+        let objects = self.registry.read().await.objects();
+        for (object_kind, object_id) in objects {
+            service
+                .shutdown_object(&object_kind, &object_id, deadline)
+                .await
+                .inspect_err(|err| error!("Can't shutdown {}/{}: {}", object_kind, object_id, err))
+                .ok();
+        }
+    }
+
+    /// Deactivates the objects that go without messages for longer than their idle
+    /// timeout, every `collection_interval`
+    ///
+    /// See [Registry::set_idle_timeout]
+    async fn collect_idle_objects(
+        service: Service<S, P>,
+        idle_timeout: Option<Duration>,
+        collection_interval: Duration,
+        hooks_timeout: Duration,
+    ) {
+        let mut interval = tokio::time::interval(collection_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let idle_objects = service.registry.read().await.idle_objects(idle_timeout);
+            for (object_kind, object_id) in idle_objects {
+                // It might have got a message, or be serving a stream, since
+                let is_idle =
+                    service
+                        .registry
+                        .read()
+                        .await
+                        .is_idle(&object_kind, &object_id, idle_timeout);
+                if !is_idle {
+                    continue;
+                }
+                debug!("Collecting idle object {}/{}", object_kind, object_id);
+                let deadline = Instant::now() + hooks_timeout;
+                service
+                    .shutdown_object(&object_kind, &object_id, deadline)
+                    .await
+                    .inspect_err(|err| {
+                        error!("Can't collect {}/{}: {}", object_kind, object_id, err)
+                    })
+                    .ok();
+            }
+        }
+    }
+
+    /// Keeps the draining status of this server up to date in the [MembershipStorage]
+    async fn advertise_drain_status(
+        members_storage: S,
+        local_addr: Address,
+        mut draining: watch::Receiver<bool>,
+    ) {
+        // It might have been drained before running
+        if *draining.borrow() {
+            draining.mark_changed();
+        }
+        while draining.changed().await.is_ok() {
+            let is_draining = *draining.borrow_and_update();
+            info!("Draining: {}", is_draining);
+            members_storage
+                .set_is_draining(&local_addr, is_draining)
+                .await
+                .inspect_err(|err| error!("Can't advertise the draining status: {}", err))
+                .ok();
+        }
+    }
+
+    /// Removes the placements pointing to this server, and marks it as inactive
+    ///
+    /// The server is marked as inactive even if the placements can't be removed
+    async fn leave_cluster(&self, local_addr: &Address) -> ServerResult<()> {
+        let placement_result = self
+            .object_placement_provider
+            .read()
+            .await
+            .clean_server(local_addr.clone())
+            .await;
+        self.cluster_provider
+            .members_storage()
+            .set_inactive(local_addr)
+            .await
+            .map_err(|err| ServerError::ClusterProviderServe(err.into()))?;
+        placement_result?;
+        Ok(())
+    }
+
+    /// Wraps `service` in the user supplied [RequestLayer], if there is one
+    fn with_layer(&self, service: Service<S, P>) -> Service<S, P> {
+        match &self.layer {
+            Some(layer) => service.with_layer(layer.as_ref()),
+            None => service,
+        }
+    }
+
+    async fn accept(
+        listener: TcpListener,
+        service: Service<S, P>,
+        stats: ServerStats,
+        #[cfg(feature = "tls")] tls: Option<TlsServerConfig>,
+    ) -> ServerResult<()> {
         let local_addr = listener.local_addr().map_err(|_| {
             ServerError::Bind("Cannot get the local address for the listener".to_string())
         })?;
@@ -300,7 +799,27 @@ where
                 .await
                 .map_err(|_| ServerError::Run)?;
 
-            joinset.spawn(async move { service.run(stream).await });
+            #[cfg(feature = "tls")]
+            let tls = tls.clone();
+            let connection = stats.connection();
+            joinset.spawn(async move {
+                let _connection = connection;
+                #[cfg(feature = "tls")]
+                let stream = match tls {
+                    Some(tls) => match tls.accept(stream).await {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("Dropping connection: {}", err);
+                            return;
+                        }
+                    },
+                    None => Stream::from(stream),
+                };
+                #[cfg(not(feature = "tls"))]
+                let stream = Stream::from(stream);
+
+                service.run(stream).await
+            });
         }
     }
 
@@ -315,7 +834,7 @@ where
             let mut inner_service = service.clone();
             joinset.spawn(async move {
                 let resp = inner_service
-                    .call(message.request)
+                    .handle_request(message.request)
                     .await
                     .unwrap_or_else(ResponseEnvelope::err);
                 message
@@ -335,32 +854,148 @@ where
     ///
     /// These are operations that need to be protected from external access, and only
     /// ServiceObjects have access to this channel - the channel is in the AppData
-    async fn consume_admin_commands(&self, mut admin_receiver: AdminReceiver) -> ServerResult<()> {
+    async fn consume_admin_commands(
+        &self,
+        mut admin_receiver: AdminReceiver,
+        service: Service<S, P>,
+    ) -> ServerResult<()> {
+        // Talks to the other servers, for the objects that live there
+        let codec = self.registry.read().await.codec();
+        let client_builder = ClientBuilder::new()
+            .members_storage(self.cluster_provider.members_storage().clone())
+            .codec(codec);
+        #[cfg(feature = "tls")]
+        let client_builder = match self.cluster_tls.clone() {
+            Some(tls) => client_builder.tls(tls),
+            None => client_builder,
+        };
+        let client = client_builder.build().map_err(|err| {
+            error!("Can't build the client for the cluster: {}", err);
+            ServerError::Run
+        })?;
+
         while let Some(message) = admin_receiver.recv().await {
             match message {
-                // TODO I think this only works for the current server,
-                //      meaning, if the service object is running on another
-                //      node, it won't shutdown
                 AdminCommands::Shutdown(object_kind, object_id) => {
-                    let registry = self.registry.write().await;
-                    registry
-                        .remove(object_kind.clone(), object_id.clone())
-                        .await;
-                    self.object_placement_provider
-                        .write()
+                    let service = service.clone();
+                    let mut client = client.clone();
+                    self.in_flight.spawn(async move {
+                        Self::deactivate_object(
+                            &service,
+                            &mut client,
+                            &object_kind,
+                            &object_id,
+                            false,
+                        )
                         .await
-                        .remove(&ObjectId(object_kind, object_id))
-                        .await?;
+                        .inspect_err(|err| {
+                            error!("Can't shutdown {}/{}: {}", object_kind, object_id, err)
+                        })
+                        .ok();
+                    });
+                }
+                AdminCommands::ShutdownWithAck(object_kind, object_id, ack) => {
+                    let service = service.clone();
+                    let mut client = client.clone();
+                    self.in_flight.spawn(async move {
+                        let result = Self::deactivate_object(
+                            &service,
+                            &mut client,
+                            &object_kind,
+                            &object_id,
+                            false,
+                        )
+                        .await;
+                        ack.send(result).ok();
+                    });
+                }
+                AdminCommands::ShutdownAfterHooks(object_kind, object_id, ack) => {
+                    let service = service.clone();
+                    let mut client = client.clone();
+                    self.in_flight.spawn(async move {
+                        let result = Self::deactivate_object(
+                            &service,
+                            &mut client,
+                            &object_kind,
+                            &object_id,
+                            true,
+                        )
+                        .await;
+                        ack.send(result).ok();
+                    });
+                }
+                AdminCommands::Drain => {
+                    info!("Got a request to drain the server");
+                    self.drain();
+                }
+                AdminCommands::Resume => {
+                    info!("Got a request to stop draining the server");
+                    self.draining.resume();
                 }
                 AdminCommands::ServerExit => {
-                    // Exists `while` to terminate the server
-                    println!("I got a message to terminate this thing here. So Ill try");
-                    return Ok(());
+                    // Keeps consuming the commands, as the objects might still send
+                    // some while the server drains
+                    info!("Got a request to shut down the server");
+                    self.shutdown();
                 }
             }
         }
         Ok(())
     }
+
+    /// Deactivates an object wherever it lives in the cluster
+    ///
+    /// If it doesn't live in this server, the server holding it is asked to deactivate it.
+    /// See [Service::deactivate] for `hooks_ran`
+    async fn deactivate_object(
+        service: &Service<S, P>,
+        client: &mut Client<S>,
+        object_kind: &str,
+        object_id: &str,
+        hooks_ran: bool,
+    ) -> ShutdownResult {
+        match service.deactivate(object_kind, object_id, hooks_ran).await {
+            Err(ResponseError::Redirect(_)) => client
+                .send_deactivate(object_kind, object_id, hooks_ran)
+                .await
+                .map_err(|err| match err {
+                    RequestError::ResponseError(err) => err,
+                    err => ResponseError::Unavailable(ErrorDetails::new(err.to_string()).into()),
+                }),
+            result => result,
+        }
+    }
+}
+
+/// Completes once the process receives a `SIGINT` or, on Unix, a `SIGTERM`
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("Can't listen to Ctrl+C: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                error!("Can't listen to SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
 
 /// Transforms a [Server] into a [Service]
@@ -375,11 +1010,20 @@ where
 {
     type Error = ServerError;
     fn try_from(server: &Server<S, C, P>) -> Result<Self, Self::Error> {
-        let address = "".to_string();
+        // Replaced once the server knows its own address
+        let address = Address::new("0.0.0.0", 0);
         let registry = server.registry.clone();
         let object_placement_provider = server.object_placement_provider.clone();
         let app_data = server.app_data.clone();
         let members_storage = server.cluster_provider.members_storage().clone();
+        let compression = server.compression.clone();
+        let in_flight = server.in_flight.clone();
+        let draining = server.draining.clone();
+        let activation_limits = ActivationLimits {
+            max_activations: server.max_activations,
+            policy: server.activation_limit_policy,
+            hooks_timeout: server.drain_timeout,
+        };
 
         Ok(Service {
             address,
@@ -387,6 +1031,11 @@ where
             members_storage,
             object_placement_provider,
             app_data,
+            compression,
+            in_flight,
+            draining,
+            activation_limits,
+            layered: None,
         })
     }
 }
@@ -3,34 +3,154 @@
 use futures::future::BoxFuture;
 use futures::sink::SinkExt;
 use futures::{FutureExt, Stream, StreamExt};
-use log::error;
+use log::{error, warn};
+use rand::seq::IndexedRandom;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{Instrument, info_span};
 
-use tokio::net::TcpStream;
-use tokio::sync::RwLock;
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
-use tower::Service as TowerService;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore, mpsc};
+use tokio::task::{AbortHandle, JoinSet};
+use tokio::time::{Instant, timeout_at};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_util::bytes::Bytes;
+use tokio_util::task::TaskTracker;
+use tower::buffer::Buffer;
+use tower::util::{BoxCloneService, BoxService};
+use tower::{Layer, Service as TowerService, ServiceExt};
 
+use crate::address::Address;
 use crate::app_data::{AppData, AppDataExt};
-use crate::cluster::storage::MembershipStorage;
+use crate::cluster::storage::{MembershipResult, MembershipStorage};
+use crate::errors::{FrameError, HandlerError, ObjectPlacementError};
 use crate::message_router::MessageRouter;
+use crate::metrics;
 use crate::object_placement::{ObjectPlacement, ObjectPlacementItem};
-use crate::protocol::pubsub::{SubscriptionRequest, SubscriptionResponse};
-use crate::protocol::{RequestEnvelope, ResponseEnvelope, ResponseError};
-use crate::registry::Registry;
+use crate::protocol::compression::{CompressionConfig, FrameEncoder};
+use crate::protocol::error_code::ErrorDetails;
+use crate::protocol::frame::{self, FrameKind};
+use crate::protocol::handshake::{Handshake, HandshakeAccepted, HandshakeResponse};
+use crate::protocol::pubsub::{
+    SubscriptionClosed, SubscriptionEnvelope, SubscriptionRequest, SubscriptionResponse,
+    Unsubscribe,
+};
+use crate::protocol::streaming::{
+    INITIAL_CREDITS, StreamCancel, StreamCredit, StreamItem, StreamItemEnvelope,
+};
+use crate::protocol::{
+    BatchRequestEnvelope, BatchResponseEnvelope, DeactivateRequest, RequestEnvelope,
+    ResponseEnvelope, ResponseError,
+};
+use crate::protocol::{deadline, metadata};
+use crate::registry::{ItemStream, Registry};
+use crate::server::{ActivationLimitPolicy, DrainHandle};
+use crate::transport::{self, FramedStream};
 use crate::{LifecycleMessage, ObjectId};
 
 /// Service to respond to Requests from [crate::client::Client]
 #[derive(Clone, Debug)]
 pub struct Service<S: MembershipStorage, P: ObjectPlacement> {
-    pub(crate) address: String,
+    pub(crate) address: Address,
     pub(crate) registry: Arc<RwLock<Registry>>,
     pub(crate) members_storage: S,
     pub(crate) object_placement_provider: Arc<RwLock<P>>,
     pub(crate) app_data: Arc<AppData>,
+    pub(crate) compression: CompressionConfig,
+    /// Requests being handled, so the server can wait for them before shutting down
+    pub(crate) in_flight: TaskTracker,
+    /// Whether the server is draining, in which case new objects are placed elsewhere
+    pub(crate) draining: DrainHandle,
+    pub(crate) activation_limits: ActivationLimits,
+    /// The [RequestLayer]s around this service, see [Service::with_layer]
+    pub(crate) layered: Option<LayeredService>,
+}
+
+/// Type erased service handling [RequestEnvelope]s, wrapped by the [RequestLayer]s
+pub type BoxRequestService = BoxCloneService<RequestEnvelope, ResponseEnvelope, ResponseError>;
+
+/// Type erased service returned by the [RequestLayer]s
+///
+/// It doesn't need to be [Clone], as a single instance of it handles all the requests
+pub type BoxLayeredService = BoxService<RequestEnvelope, ResponseEnvelope, ResponseError>;
+
+/// The single instance of the [BoxLayeredService], shared by the clones of the [Service]
+#[derive(Clone)]
+pub(crate) struct LayeredService(
+    Buffer<RequestEnvelope, BoxFuture<'static, Result<ResponseEnvelope, ResponseError>>>,
+);
+
+impl Debug for LayeredService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("LayeredService").finish()
+    }
+}
+
+impl LayeredService {
+    fn handle(
+        &self,
+        req: RequestEnvelope,
+    ) -> BoxFuture<'static, Result<ResponseEnvelope, ResponseError>> {
+        // The errors of the layers come back boxed, the buffer's own errors mean the
+        // layered service failed or is gone
+        let response = self.0.clone().oneshot(req).map(|result| {
+            result.map_err(|err| match err.downcast::<ResponseError>() {
+                Ok(err) => *err,
+                Err(err) => ResponseError::Unavailable(ErrorDetails::new(err.to_string()).into()),
+            })
+        });
+        Box::pin(response)
+    }
+}
+
+/// Tower layers around the [Service] handling the [RequestEnvelope]s, see
+/// [Server::builder](crate::server::Server::builder)
+///
+/// It is implemented for every [tower::Layer] whose service handles [RequestEnvelope]s
+/// and fails with [ResponseError]. Layers with other error types, like the ones from
+/// [tower::timeout] or [tower::load_shed], need their errors mapped to a [ResponseError]
+/// (e.g. with [tower::util::MapErrLayer])
+///
+/// The layered service is built once, and every request, from any connection, goes through
+/// it. So stateful layers, like [tower::limit::ConcurrencyLimit] or
+/// [tower::limit::RateLimit], limit the requests of the whole server
+pub trait RequestLayer: Send + Sync + 'static {
+    fn layer_service(&self, inner: BoxRequestService) -> BoxLayeredService;
+}
+
+impl<L> RequestLayer for L
+where
+    L: Layer<BoxRequestService> + Send + Sync + 'static,
+    L::Service: TowerService<RequestEnvelope, Response = ResponseEnvelope, Error = ResponseError>
+        + Send
+        + 'static,
+    <L::Service as TowerService<RequestEnvelope>>::Future: Send + 'static,
+{
+    fn layer_service(&self, inner: BoxRequestService) -> BoxLayeredService {
+        BoxService::new(self.layer(inner))
+    }
+}
+
+/// Bounds how many objects a [Service] keeps active
+#[derive(Clone, Debug)]
+pub(crate) struct ActivationLimits {
+    /// Maximum number of active objects, of any type
+    pub(crate) max_activations: Option<usize>,
+    pub(crate) policy: ActivationLimitPolicy,
+    /// How long the shutdown hooks of the objects it evicts or deactivates can take
+    pub(crate) hooks_timeout: Duration,
+}
+
+impl Default for ActivationLimits {
+    fn default() -> Self {
+        ActivationLimits {
+            max_activations: None,
+            policy: ActivationLimitPolicy::default(),
+            hooks_timeout: Duration::from_secs(30),
+        }
+    }
 }
 
 /// Service implementation to handle [RequestEnvelope] request
@@ -51,76 +171,132 @@ impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> TowerService<
     /// Call a service locally, or return an error that will
     /// indicate whether this service is allocated somewhere
     /// else
+    ///
+    /// Requests with a deadline fail with [ResponseError::DeadlineExceeded] once it
+    /// passes, cancelling whatever is running: the placement lookup, the activation of
+    /// the object or its handler
     fn call(&mut self, req: RequestEnvelope) -> Self::Future {
         let this = self.clone();
+        let deadline = req.deadline.map(|deadline| Instant::now() + deadline);
+        let handler_type = req.handler_type.clone();
+        let message_type = req.message_type.clone();
         let result = async move {
-            // Test if this object is in fact allocated in this instance
-            let server_address = this
-                .get_or_create_placement(req.handler_type.clone(), req.handler_id.clone())
-                .await?;
-            this.check_address_mismatch(server_address).await?;
+            // Don't bother with requests the caller has already given up on
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(ResponseError::DeadlineExceeded);
+            }
 
-            // Ensure the object is started in the registry
-            this.start_service_object(&req.handler_type, &req.handler_id)
-                .await
-                .map_err(|err| {
-                    // Transform some internal error types into better user facing errors
-                    // while retaining other error types
-                    match err {
-                        ResponseError::Unknown(_) => ResponseError::Allocate,
-                        e => e,
-                    }
-                })?;
-
-            // Req + Response to registry
-            let guard = this.registry.read().await;
-            let fut = guard.send(
-                &req.handler_type,
-                &req.handler_id,
-                &req.message_type,
-                &req.payload,
-                this.app_data.clone(),
-            );
-            // TODO review the use of `catch_unwind` and `AssertUnwindSafe`
-            let fut = AssertUnwindSafe(fut);
-            let response = fut.catch_unwind().await;
+            let handled = async {
+                this.prepare_object(&req.handler_type, &req.handler_id)
+                    .await?;
+
+                // Req + Response to registry
+                let guard = this.registry.read().await;
+                let fut = guard.send(
+                    &req.handler_type,
+                    &req.handler_id,
+                    &req.message_type,
+                    &req.payload,
+                    this.app_data.clone(),
+                );
+                // The request metadata is visible to the handler while it runs
+                let fut = metadata::scope(req.metadata.clone(), fut);
+                // TODO review the use of `catch_unwind` and `AssertUnwindSafe`
+                Ok::<_, ResponseError>(AssertUnwindSafe(fut).catch_unwind().await)
+            };
+            let response = match deadline {
+                // The placement, the activation and the handler are all cancelled (their
+                // futures are dropped) once the deadline passes
+                Some(deadline) => timeout_at(deadline, deadline::scope(deadline, handled))
+                    .await
+                    .map_err(|_| ResponseError::DeadlineExceeded)??,
+                None => handled.await?,
+            };
 
             // Handle result, 'translating' it to the protocol
             match response {
-                Ok(Ok(body)) => Ok(ResponseEnvelope::new(body)),
-                Ok(Err(err)) => Err(ResponseError::from(err)),
-                Err(_) => {
+                Ok((Ok(body), metadata)) => Ok(ResponseEnvelope::new(body).with_metadata(metadata)),
+                Ok((Err(err), _)) => Err(ResponseError::from(err)),
+                Err(panic) => {
                     // When there is a panic, we will 'remove' the service object
                     // from both the registry and the ObjectPlacement
-                    this.registry
-                        .read()
-                        .await
-                        .remove(req.handler_type.clone(), req.handler_id.clone())
-                        .await;
-                    this.object_placement_provider
-                        .read()
-                        .await
-                        .remove(&ObjectId(req.handler_type.clone(), req.handler_id.clone()))
+                    this.remove_object(&req.handler_type, &req.handler_id)
                         .await?;
-                    Err(ResponseError::Unknown("Panic".to_string()))
+                    let details = ErrorDetails::new(panic_message(panic.as_ref()))
+                        .with_object(&req.handler_type, &req.handler_id);
+                    Err(ResponseError::Panic(details.into()))
                 }
             }
         };
-        Box::pin(result)
+        let registry = self.registry.clone();
+        Box::pin(async move {
+            let result = result.await;
+            let code = result.as_ref().map(|_| ()).map_err(ResponseError::code);
+            // The names come from the clients, so only the registered ones are used as
+            // labels, otherwise anyone could create new series
+            let registered = registry
+                .read()
+                .await
+                .has_handler(&handler_type, &message_type);
+            match registered {
+                true => metrics::request(&handler_type, &message_type, code),
+                false => metrics::request(metrics::UNKNOWN, metrics::UNKNOWN, code),
+            }
+            result
+        })
     }
 }
 
 /// This is a iterator to be used on the server to stream
 /// messages back to the client
+///
+/// It ends if the client falls too far behind the messages published to the object
 #[derive(Debug)]
 pub struct SubscriptionResponseIter {
     receiver_stream: tokio_stream::wrappers::BroadcastStream<SubscriptionResponse>,
+    // Declared after `receiver_stream`, so it is dropped after the receiver
+    router_cleanup: Option<RouterCleanup>,
 }
 
 impl SubscriptionResponseIter {
     pub fn new(channel: tokio::sync::broadcast::Receiver<SubscriptionResponse>) -> Self {
         let receiver_stream = tokio_stream::wrappers::BroadcastStream::new(channel);
-        Self { receiver_stream }
+        Self {
+            receiver_stream,
+            router_cleanup: None,
+        }
+    }
+
+    /// Removes the object's channel from the [MessageRouter] in `app_data` once this
+    /// is dropped, if there are no other subscribers to it
+    pub fn with_router_cleanup(
+        mut self,
+        app_data: Arc<AppData>,
+        handler_type: String,
+        handler_id: String,
+    ) -> Self {
+        self.router_cleanup = Some(RouterCleanup {
+            app_data,
+            handler_type,
+            handler_id,
+        });
+        self
+    }
+}
+
+/// Calls [MessageRouter::remove_unused] on drop
+#[derive(Debug)]
+struct RouterCleanup {
+    app_data: Arc<AppData>,
+    handler_type: String,
+    handler_id: String,
+}
+
+impl Drop for RouterCleanup {
+    fn drop(&mut self) {
+        self.app_data
+            .get_or_default::<MessageRouter>()
+            .remove_unused(self.handler_type.clone(), self.handler_id.clone());
     }
 }
 
@@ -131,16 +307,17 @@ impl Stream for SubscriptionResponseIter {
         _cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
         let this = self.get_mut();
-        this.receiver_stream.poll_next_unpin(_cx).map(|i| {
-            if let Some(result) = i {
-                if result.is_err() {
-                    error!("Error on stream recv {:?}", result);
-                }
-                // TODO error handling
+        this.receiver_stream.poll_next_unpin(_cx).map(|i| match i? {
+            Ok(response) => Some(response),
+            // The subscriber fell too far behind, so its subscription is closed instead
+            // of buffering the messages it missed
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!(
+                    "Subscription lagged behind by {} messages, closing it",
+                    skipped
+                );
                 // TODO deal with redirect
                 // TODO deal with objects being removed from the current host!
-                result.ok()
-            } else {
                 None
             }
         })
@@ -179,69 +356,69 @@ where
                 .app_data
                 .get_or_default::<MessageRouter>()
                 .create_subscription(req.handler_type.clone(), req.handler_id.clone());
-            Ok(SubscriptionResponseIter::new(receiver))
+            Ok(SubscriptionResponseIter::new(receiver).with_router_cleanup(
+                this.app_data.clone(),
+                req.handler_type,
+                req.handler_id,
+            ))
         };
         Box::pin(result)
     }
 }
 
 impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> Service<S, P> {
-    /// Returns the ip:port for where this object is placed
+    /// Returns the address of the server where this object is placed
     ///
-    /// If the object is not instantiated anywhere, it will allocate locally
+    /// If the object is not instantiated anywhere, it will allocate locally, unless the
+    /// server is draining (see [Service::member_for_new_object])
     #[tracing::instrument]
     async fn get_or_create_placement(
         &self,
         handler_type: String,
         handler_id: String,
-    ) -> Result<String, ResponseError> {
+    ) -> Result<Address, ResponseError> {
         let object_id = ObjectId(handler_type, handler_id);
         let placement_guard = self.object_placement_provider.read().await;
-        let mut maybe_server_address = placement_guard.lookup(&object_id).await?.take();
+        let lookup = placement_guard.lookup(&object_id).await;
         drop(placement_guard);
-
-        // Ensures the placement is on an active server
-        if let Some(server_address) = maybe_server_address.as_ref() {
-            let mut addr_split = server_address.splitn(2, ":");
-            let ip = addr_split.next().unwrap_or_default();
-            let port = addr_split.next().unwrap_or_default();
-
+        metrics::placement_lookup(match &lookup {
+            Ok(Some(_)) => "found",
+            Ok(None) => "not_found",
+            Err(_) => "error",
+        });
+        let mut maybe_server_address = match lookup {
+            Ok(maybe_server_address) => maybe_server_address,
             // This case should never happen, but writing it here to be handled gracefuly.
             // It means the placement was stored with bad data, so we remove the record and
-            // take `maybe_server_address` so it picks up a new placement at the end of this
-            // function
-            if ip.is_empty() || port.is_empty() {
+            // pick up a new placement at the end of this function
+            Err(ObjectPlacementError::InvalidAddress(err)) => {
                 error!(object_id:? = object_id,
-                       address:%  = server_address,
-                       ip:%  = ip,
-                       port:%  = port;
+                       err:% = err;
                        "The object's placement is in a bad state. This is likely a bug on the object placement code");
 
                 let placement_guard = self.object_placement_provider.read().await;
                 placement_guard.remove(&object_id).await?;
-                maybe_server_address.take();
-            }
-            // In case the server in which this object is allocated is inactive/unavailable,
-            // we clean up the server (disassociate all the objects from it), and take
-            // `maybe_server_address` so it picks up a new placement at the end of this function
-            else if !self
-                .members_storage
-                .is_active(ip, port)
-                .await
-                .unwrap_or(false)
-            {
-                let placement_guard = self.object_placement_provider.read().await;
-                placement_guard
-                    .clean_server(server_address.to_string())
-                    .await?;
-                maybe_server_address.take();
+                None
             }
+            Err(err) => return Err(err.into()),
+        };
+
+        // In case the server in which this object is allocated is inactive/unavailable,
+        // we clean up the server (disassociate all the objects from it), and take
+        // `maybe_server_address` so it picks up a new placement at the end of this function
+        if let Some(server_address) = maybe_server_address.as_ref()
+            && !self.is_member_active(server_address).await.unwrap_or(false)
+        {
+            let placement_guard = self.object_placement_provider.read().await;
+            placement_guard.clean_server(server_address.clone()).await?;
+            maybe_server_address.take();
         }
 
         if let Some(server_address) = maybe_server_address {
             Ok(server_address)
         } else {
-            let new_placement = ObjectPlacementItem::new(object_id, Some(self.address.clone()));
+            let server_address = self.member_for_new_object().await?;
+            let new_placement = ObjectPlacementItem::new(object_id, Some(server_address.clone()));
             {
                 self.object_placement_provider
                     .write()
@@ -249,8 +426,38 @@ impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> Service<S, P>
                     .update(new_placement)
                     .await?;
             };
-            Ok(self.address.clone())
+            Ok(server_address)
+        }
+    }
+
+    /// Picks the server for an object that isn't placed anywhere yet
+    ///
+    /// It is this server, unless it is draining. In that case it is one of the other active
+    /// members that aren't draining, at random. It fails with [ResponseError::Unavailable]
+    /// if there are none
+    async fn member_for_new_object(&self) -> Result<Address, ResponseError> {
+        if !self.draining.is_draining() {
+            return Ok(self.address.clone());
         }
+        let members = self
+            .members_storage
+            .active_members()
+            .await
+            .map_err(|e| ResponseError::Membership(ErrorDetails::new(e.to_string()).into()))?;
+        let candidates: Vec<_> = members
+            .iter()
+            .filter(|member| !member.draining() && member.address() != &self.address)
+            .collect();
+        candidates
+            .choose(&mut rand::rng())
+            .map(|member| member.address().clone())
+            .ok_or_else(|| {
+                let details = ErrorDetails::new(
+                    "The server is draining, and no other server can take the object",
+                )
+                .with_server(self.address.clone());
+                ResponseError::Unavailable(details.into())
+            })
     }
 
     /// Checks if the given address is from the local server.
@@ -258,33 +465,19 @@ impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> Service<S, P>
     ///
     /// It returns an Error if it is not
     #[tracing::instrument]
-    async fn check_address_mismatch(&self, server_address: String) -> Result<(), ResponseError> {
+    async fn check_address_mismatch(&self, server_address: Address) -> Result<(), ResponseError> {
         if server_address == self.address {
             return Ok(());
         }
 
-        let mut split_address = server_address.split(':');
-        let ip = split_address.next().ok_or_else(|| {
-            ResponseError::Unknown(format!(
-                "Malformed address: Missing IP in '{}'",
-                server_address
-            ))
-        })?;
-        let port = split_address.next().ok_or_else(|| {
-            ResponseError::Unknown(format!(
-                "Malformed address: Missing PORT in '{}'",
-                server_address
-            ))
-        })?;
-
         let is_active = self
-            .members_storage
-            .is_active(ip, port)
+            .is_member_active(&server_address)
             .await
-            .map_err(|e| ResponseError::Unknown(e.to_string()))?;
+            .map_err(|e| ResponseError::Membership(ErrorDetails::new(e.to_string()).into()))?;
 
         // This object is active somewhere else
         if is_active {
+            metrics::redirect();
             return Err(ResponseError::Redirect(server_address));
         }
 
@@ -297,6 +490,380 @@ impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> Service<S, P>
         Err(ResponseError::DeallocateServiceObject)
     }
 
+    /// Checks whether the member is active, counting the check in [metrics]
+    async fn is_member_active(&self, address: &Address) -> MembershipResult<bool> {
+        let is_active = self.members_storage.is_active(address).await;
+        metrics::membership_check(match is_active {
+            Ok(true) => "active",
+            Ok(false) => "inactive",
+            Err(_) => "error",
+        });
+        is_active
+    }
+
+    /// Ensures the object is allocated in this server and started in the registry
+    ///
+    /// It fails with [ResponseError::Redirect] if the object lives in another server
+    async fn prepare_object(
+        &self,
+        handler_type: &str,
+        handler_id: &str,
+    ) -> Result<(), ResponseError> {
+        // Test if this object is in fact allocated in this instance
+        let server_address = self
+            .get_or_create_placement(handler_type.to_string(), handler_id.to_string())
+            .await?;
+        self.check_address_mismatch(server_address).await?;
+
+        let is_active = self
+            .registry
+            .read()
+            .await
+            .has(handler_type, handler_id)
+            .await;
+        if !is_active {
+            if let Err(err) = self.ensure_capacity(handler_type).await {
+                // Frees the object to be placed on another server
+                self.object_placement_provider
+                    .read()
+                    .await
+                    .remove(&ObjectId(handler_type.to_string(), handler_id.to_string()))
+                    .await?;
+                return Err(err);
+            }
+        }
+
+        // Ensure the object is started in the registry
+        self.start_service_object(handler_type, handler_id)
+            .await
+            .map_err(|err| {
+                // Transform some internal error types into better user facing errors
+                // while retaining other error types
+                match err {
+                    ResponseError::Unknown(_)
+                    | ResponseError::Panic(_)
+                    | ResponseError::Placement(_) => ResponseError::Allocate,
+                    e => e,
+                }
+            })
+    }
+
+    /// Makes room for a new object of `handler_type`, if this server reached its activation
+    /// limits
+    ///
+    /// Depending on the [ActivationLimitPolicy], it either deactivates the least recently
+    /// used objects, or fails with [ResponseError::Unavailable]
+    ///
+    /// The limits are best effort, as concurrent activations might briefly exceed them
+    async fn ensure_capacity(&self, handler_type: &str) -> Result<(), ResponseError> {
+        loop {
+            // Which objects are over their limit: the ones of `handler_type` or all of them
+            let over_limit = {
+                let registry = self.registry.read().await;
+                let type_limit = registry
+                    .max_activations(handler_type)
+                    .filter(|max| registry.count_objects(Some(handler_type)) >= *max)
+                    .map(|_| Some(handler_type));
+                let global_limit = self
+                    .activation_limits
+                    .max_activations
+                    .filter(|max| registry.count_objects(None) >= *max)
+                    .map(|_| None);
+                type_limit.or(global_limit)
+            };
+            let Some(scope) = over_limit else {
+                return Ok(());
+            };
+
+            let limit_reached = || {
+                let message = format!(
+                    "activation limit reached for {}",
+                    scope.unwrap_or("all types")
+                );
+                let details = ErrorDetails::new(message).with_server(self.address.clone());
+                ResponseError::Unavailable(details.into())
+            };
+            if self.activation_limits.policy == ActivationLimitPolicy::Reject {
+                return Err(limit_reached());
+            }
+            let least_recently_used = self.registry.read().await.least_recently_used(scope);
+            // Nothing to evict when the limit is zero
+            let (object_kind, object_id) = least_recently_used.ok_or_else(limit_reached)?;
+            let deadline = Instant::now() + self.activation_limits.hooks_timeout;
+            self.shutdown_object(&object_kind, &object_id, deadline)
+                .await?;
+        }
+    }
+
+    /// Wraps this service in `layer`
+    ///
+    /// The requests handled by [Service::run] and by the server's internal client go through
+    /// `layer` before reaching this service. Subscriptions and streamed requests don't
+    ///
+    /// The layered service runs on its own task, behind a [Buffer], and the clones of this
+    /// service share it. It must be called within a Tokio runtime
+    pub(crate) fn with_layer(mut self, layer: &dyn RequestLayer) -> Self {
+        let inner = BoxCloneService::new(self.clone());
+        self.layered = Some(LayeredService(Buffer::new(
+            layer.layer_service(inner),
+            LAYER_BUFFER,
+        )));
+        self
+    }
+
+    /// Handles a request through the [RequestLayer]s, if there are any
+    pub(crate) async fn handle_request(
+        &mut self,
+        req: RequestEnvelope,
+    ) -> Result<ResponseEnvelope, ResponseError> {
+        match &self.layered {
+            Some(layered) => layered.handle(req).await,
+            None => self.call(req).await,
+        }
+    }
+
+    /// Deactivates an object, running its [LifecycleMessage::Shutdown] hooks before it is
+    /// removed from both the registry and the ObjectPlacement (see
+    /// [Service::shutdown_object]). With `hooks_ran`, the object already ran them, so it is
+    /// only removed
+    ///
+    /// It fails with [ResponseError::Redirect] if the object lives in another server.
+    /// Deactivating an object that is not active anywhere succeeds, as there is nothing
+    /// left to do
+    pub(crate) async fn deactivate(
+        &self,
+        handler_type: &str,
+        handler_id: &str,
+        hooks_ran: bool,
+    ) -> Result<(), ResponseError> {
+        let object_id = ObjectId(handler_type.to_string(), handler_id.to_string());
+        let server_address = self
+            .object_placement_provider
+            .read()
+            .await
+            .lookup(&object_id)
+            .await?;
+        if let Some(server_address) = server_address {
+            match self.check_address_mismatch(server_address).await {
+                // The server it was placed on is gone, and its placement with it
+                Ok(()) | Err(ResponseError::DeallocateServiceObject) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if hooks_ran {
+            // The object might be waiting for this from one of its own handlers, so
+            // nothing more is sent to it
+            self.registry
+                .read()
+                .await
+                .close_mailbox(handler_type, handler_id);
+            return self.remove_object(handler_type, handler_id).await;
+        }
+        let deadline = Instant::now() + self.activation_limits.hooks_timeout;
+        self.shutdown_object(handler_type, handler_id, deadline)
+            .await
+    }
+
+    /// Runs the [LifecycleMessage::Shutdown] hooks of an object living in this server, then
+    /// removes it from both the registry and the ObjectPlacement
+    ///
+    /// The object takes no new requests once its hooks start, and the ones it already got
+    /// are handled before them. It is removed even if they fail, or if they don't finish
+    /// before `deadline`
+    pub(crate) async fn shutdown_object(
+        &self,
+        handler_type: &str,
+        handler_id: &str,
+        deadline: Instant,
+    ) -> Result<(), ResponseError> {
+        let is_active_here = self
+            .registry
+            .read()
+            .await
+            .close_mailbox(handler_type, handler_id);
+        if !is_active_here {
+            return self.remove_object(handler_type, handler_id).await;
+        }
+        if let Err(err) = self
+            .run_shutdown_hooks(handler_type, handler_id, deadline)
+            .await
+        {
+            warn!(
+                "{}/{} failed to shut down: {}",
+                handler_type, handler_id, err
+            );
+        }
+        self.remove_object(handler_type, handler_id).await
+    }
+
+    /// Moves an object living in this server to the server at `to`
+    ///
+    /// The object stops taking requests, which are refused with a retryable
+    /// [ResponseError::Unavailable], and it runs its [LifecycleMessage::Shutdown] hooks here,
+    /// so it can save its state. Then its placement is pointed to `to`. The object is
+    /// activated there by the next request, which this server redirects from then on
+    ///
+    /// It fails with [ResponseError::Redirect] if the object lives in another server, and
+    /// with [ResponseError::Unavailable] if `to` isn't an active member of the cluster. If
+    /// the hooks fail, or don't finish before `deadline`, the object stays here, taking
+    /// requests again
+    pub(crate) async fn migrate_object(
+        &self,
+        handler_type: &str,
+        handler_id: &str,
+        to: Address,
+        deadline: Instant,
+    ) -> Result<(), ResponseError> {
+        if to == self.address {
+            return Ok(());
+        }
+        let object_id = ObjectId(handler_type.to_string(), handler_id.to_string());
+        let server_address = self
+            .object_placement_provider
+            .read()
+            .await
+            .lookup(&object_id)
+            .await?;
+        match server_address {
+            Some(server_address) if server_address == self.address => {}
+            Some(server_address) => return Err(ResponseError::Redirect(server_address)),
+            None => return Err(ResponseError::ObjectNotFound),
+        }
+        let is_active = self
+            .is_member_active(&to)
+            .await
+            .map_err(|e| ResponseError::Membership(ErrorDetails::new(e.to_string()).into()))?;
+        if !is_active {
+            let details = ErrorDetails::new(format!("{} is not active", to))
+                .with_object(handler_type, handler_id)
+                .with_server(to);
+            return Err(ResponseError::Unavailable(details.into()));
+        }
+
+        // Nothing can change the object once it starts saving its state
+        let is_active_here = self
+            .registry
+            .read()
+            .await
+            .close_mailbox(handler_type, handler_id);
+        let moved = async {
+            if is_active_here {
+                self.run_shutdown_hooks(handler_type, handler_id, deadline)
+                    .await?;
+            }
+            // The placement moves before the object is removed, so the requests arriving
+            // in between are redirected instead of activating the object here again
+            self.object_placement_provider
+                .write()
+                .await
+                .update(ObjectPlacementItem::new(object_id, Some(to)))
+                .await?;
+            Ok::<_, ResponseError>(())
+        };
+        if let Err(err) = moved.await {
+            self.registry
+                .read()
+                .await
+                .reopen_mailbox(handler_type, handler_id);
+            return Err(err);
+        }
+        self.registry
+            .read()
+            .await
+            .remove(handler_type.to_string(), handler_id.to_string())
+            .await;
+        Ok(())
+    }
+
+    /// Runs the [LifecycleMessage::Shutdown] hooks of an object living in this server
+    ///
+    /// It fails if the hooks fail or panic, and with [ResponseError::DeadlineExceeded] if
+    /// they don't finish before `deadline`
+    async fn run_shutdown_hooks(
+        &self,
+        handler_type: &str,
+        handler_id: &str,
+        deadline: Instant,
+    ) -> Result<(), ResponseError> {
+        let registry = self.registry.read().await;
+        let lifecycle_msg = registry
+            .codec()
+            .serialize(&LifecycleMessage::Shutdown)
+            .map_err(|e| ResponseError::SeralizationError(e.to_string()))?;
+        let lifecycle_fut = registry.send(
+            handler_type,
+            handler_id,
+            "LifecycleMessage",
+            &lifecycle_msg,
+            self.app_data.clone(),
+        );
+        let lifecycle_fut = AssertUnwindSafe(lifecycle_fut).catch_unwind();
+        match timeout_at(deadline, lifecycle_fut).await {
+            // Objects without lifecycle handlers have nothing to run
+            Ok(Ok(Ok(_) | Err(HandlerError::HandlerNotFound))) => Ok(()),
+            Ok(Ok(Err(err))) => Err(err.into()),
+            Ok(Err(panic)) => {
+                let details = ErrorDetails::new(panic_message(panic.as_ref()))
+                    .with_object(handler_type, handler_id);
+                Err(ResponseError::Panic(details.into()))
+            }
+            Err(_) => Err(ResponseError::DeadlineExceeded),
+        }
+    }
+
+    /// Removes a service object from both the registry and the ObjectPlacement
+    ///
+    /// Used once the object panics, as its state can't be trusted anymore
+    async fn remove_object(
+        &self,
+        handler_type: &str,
+        handler_id: &str,
+    ) -> Result<(), ResponseError> {
+        self.registry
+            .read()
+            .await
+            .remove(handler_type.to_string(), handler_id.to_string())
+            .await;
+        self.object_placement_provider
+            .read()
+            .await
+            .remove(&ObjectId(handler_type.to_string(), handler_id.to_string()))
+            .await?;
+        Ok(())
+    }
+
+    /// Opens the stream of items for a message handled by a
+    /// [StreamHandler](crate::registry::StreamHandler)
+    ///
+    /// Just like a regular request, it fails with [ResponseError::Redirect] if the object
+    /// lives in another server. The request metadata is visible to the handler while it
+    /// creates the stream
+    async fn call_stream(&self, req: RequestEnvelope) -> Result<ItemStream, ResponseError> {
+        self.prepare_object(&req.handler_type, &req.handler_id)
+            .await?;
+
+        let guard = self.registry.read().await;
+        let fut = guard.send_stream(
+            &req.handler_type,
+            &req.handler_id,
+            &req.message_type,
+            &req.payload,
+            self.app_data.clone(),
+        );
+        let fut = metadata::scope(req.metadata, fut);
+        match AssertUnwindSafe(fut).catch_unwind().await {
+            Ok((result, _)) => result.map_err(ResponseError::from),
+            Err(panic) => {
+                drop(guard);
+                self.remove_object(&req.handler_type, &req.handler_id)
+                    .await?;
+                let details = ErrorDetails::new(panic_message(panic.as_ref()))
+                    .with_object(&req.handler_type, &req.handler_id);
+                Err(ResponseError::Panic(details.into()))
+            }
+        }
+    }
+
     /// Startup a service object and insert it into registry
     ///
     /// If is already running, ignore it
@@ -322,38 +889,42 @@ impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> Service<S, P>
                 .await;
         };
 
-        let lifecycle_result = {
-            let object_guard = self.registry.read().await;
+        // The Load hook runs on its own task, so the object isn't left half started if the
+        // request is cancelled, e.g. once its deadline passes
+        let registry = self.registry.clone();
+        let app_data = self.app_data.clone();
+        let (object_kind, object_id) = (handler_type.to_string(), handler_id.to_string());
+        let lifecycle_task = tokio::spawn(async move {
+            let object_guard = registry.read().await;
             let lifecycle_msg = LifecycleMessage::Load;
-            let lifecycle_ser_msg = bincode::serialize(&lifecycle_msg)
+            let lifecycle_ser_msg = object_guard
+                .codec()
+                .serialize(&lifecycle_msg)
                 .map_err(|e| ResponseError::SeralizationError(e.to_string()))?;
-            let lifecycle_fut = object_guard.send(
-                handler_type,
-                handler_id,
-                "LifecycleMessage",
-                &lifecycle_ser_msg,
-                self.app_data.clone(),
-            );
-
-            // Catch panics on LifecycleMessage::Load
-            let lifecycle_fut = AssertUnwindSafe(lifecycle_fut);
-            lifecycle_fut.catch_unwind().await
+            // Only panics fail the activation, like objects without lifecycle handlers
+            let _ = object_guard
+                .send(
+                    &object_kind,
+                    &object_id,
+                    "LifecycleMessage",
+                    &lifecycle_ser_msg,
+                    app_data,
+                )
+                .await;
+            Ok::<_, ResponseError>(())
+        });
+        // Catch panics on LifecycleMessage::Load
+        let lifecycle_result = match lifecycle_task.await {
+            Ok(result) => Ok(result?),
+            Err(err) if err.is_panic() => Err(err.into_panic()),
+            Err(err) => return Err(ResponseError::Unknown(err.to_string())),
         };
 
-        // TODO remove duplicated logic (Self::send)
         if let Err(e) = lifecycle_result {
-            self.registry
-                .read()
-                .await
-                .remove(handler_type.to_string(), handler_id.to_string())
-                .await;
-            self.object_placement_provider
-                .read()
-                .await
-                .remove(&ObjectId(handler_type.to_string(), handler_id.to_string()))
-                .await?;
-
-            return Err(ResponseError::Unknown(format!("Task panicked: {:?}", e)));
+            self.remove_object(handler_type, handler_id).await?;
+            let details =
+                ErrorDetails::new(panic_message(e.as_ref())).with_object(handler_type, handler_id);
+            return Err(ResponseError::Panic(details.into()));
         }
         Ok(())
     }
@@ -365,104 +936,653 @@ impl<S: MembershipStorage + 'static, P: ObjectPlacement + 'static> Service<S, P>
     ///
     /// Consumes a stream of frames, each containing a command sent from clients.
     ///
-    /// The commands might be either a request/response request or a subscription request
+    /// The commands might be either a request/response request, a batch of requests, a
+    /// streamed request, a subscription request, an unsubscription or a request to
+    /// deactivate an object.
+    ///
+    /// Each command is handled on its own task, so many requests can be in-flight on the
+    /// same connection. The responses are sent back as soon as they are ready, tagged with
+    /// their request id, so they might not follow the order of the requests. Once
+    /// `MAX_IN_FLIGHT_PER_CONNECTION` of them are being handled, the connection stops
+    /// reading until one of them finishes
     #[tracing::instrument]
-    pub async fn run(&mut self, stream: TcpStream) {
-        let codec = LengthDelimitedCodec::new();
-        let mut frames = Framed::new(stream, codec);
+    pub async fn run(&mut self, stream: transport::Stream) {
+        let mut frames = stream.framed();
 
-        while let Some(Ok(frame)) = StreamExt::next(&mut frames)
-            .instrument(info_span!("frame_receive"))
-            .await
-        {
-            let request: Result<RequestEnvelope, _> = bincode::deserialize(&frame);
-            let subscription: Result<SubscriptionRequest, _> = bincode::deserialize(&frame);
-
-            let either_request = match (request, subscription) {
-                (Ok(message), _) => AllRequest::ReqResp(message),
-                (_, Ok(message)) => AllRequest::PubSub(message),
-                _ => {
-                    unreachable!("Got both or neither requests")
-                }
-            };
-            match either_request {
-                AllRequest::ReqResp(message) => {
-                    let response = match self.call(message).await {
-                        Ok(x) => x,
-                        Err(err) => ResponseEnvelope::err(err),
-                    };
-                    let ser_result = bincode::serialize(&response);
-                    let ser_response = match ser_result {
-                        Ok(value) => value,
-                        Err(err) => {
-                            let new_return = ResponseEnvelope::err(
-                                ResponseError::SeralizationError(err.to_string()),
-                            );
-                            bincode::serialize(&new_return)
-                                .expect("Serialization of response error should be infalible")
-                        }
-                    };
-                    frames
-                        .send(ser_response.into())
-                        .instrument(info_span!("response_send"))
-                        .await
-                        .unwrap();
+        // The connection is dropped if the client is not compatible with this server
+        let accepted = match self.accept_handshake(&mut frames).await {
+            Ok(accepted) => accepted,
+            Err(_) => return,
+        };
+        let encoder = self
+            .compression
+            .encoder(accepted.compression.as_deref())
+            .with_version(accepted.protocol_version);
+        let (mut sink, mut frames) = frames.split();
+
+        // Every task sends its responses through this channel, so there is a single
+        // writer for the connection. Once it is full, they wait for the client to read
+        let (response_sender, mut response_receiver) = mpsc::channel::<Bytes>(RESPONSE_BUFFER);
+        tokio::spawn(async move {
+            while let Some(frame) = response_receiver.recv().await {
+                let send_result = sink
+                    .send(frame)
+                    .instrument(info_span!("response_send"))
+                    .await;
+                if let Err(err) = send_result {
+                    error!("Connection is closed due {}", err);
+                    break;
                 }
-                AllRequest::PubSub(message) => {
-                    let stream = self.call(message).await;
-
-                    // If there is an upstream error to establish the subscription,
-                    // wrapi it in a SubscriptionResponse and return earlier
-                    let mut stream = match stream {
-                        Ok(value) => value,
-                        Err(err) => {
-                            let sub_response = SubscriptionResponse::err(err);
-                            let ser_response = bincode::serialize(&sub_response)
-                                .expect("Error serialization should be infalible");
-                            frames
-                                .send(ser_response.into())
-                                .instrument(info_span!("response_send"))
-                                .await
-                                .ok();
-                            return;
-                        }
-                    };
+            }
+        });
 
-                    while let Some(value) = StreamExt::next(&mut stream).await {
-                        let ser_result = bincode::serialize(&value);
-                        let ser_response = match ser_result {
-                            Ok(value) => value,
-                            Err(err) => {
-                                let new_return = SubscriptionResponse::err(
-                                    ResponseError::SeralizationError(err.to_string()),
-                                );
-                                bincode::serialize(&new_return)
-                                    .expect("Serialization of response error should be infalible")
-                            }
-                        };
-
-                        let send_result = frames
-                            .send(ser_response.into())
-                            .instrument(info_span!("response_send"))
-                            .await;
+        // Subscriptions are bound to the connection, they are aborted once the client
+        // disconnects (dropping the JoinSet), or once it unsubscribes
+        let mut subscriptions = JoinSet::new();
+        let mut active_subscriptions: HashMap<u64, AbortHandle> = HashMap::new();
+
+        // Same for the streamed responses, which also keep the credits the client granted
+        let mut streams = JoinSet::new();
+        let mut active_streams: HashMap<u64, (AbortHandle, Arc<Semaphore>)> = HashMap::new();
 
-                        // Stop receiving messages if the sink we redirect messages to is
-                        // closed
-                        if let Err(err) = send_result {
-                            error!("Channel is closed due {}", err);
-                            break;
-                        }
+        // Bounds the requests handled at once for this connection. Once they are all taken,
+        // no more frames are read until one of them finishes
+        let in_flight_permits = Arc::new(Semaphore::new(MAX_IN_FLIGHT_PER_CONNECTION));
+
+        loop {
+            let frame = tokio::select! {
+                frame = StreamExt::next(&mut frames).instrument(info_span!("frame_receive")) => {
+                    match frame {
+                        Some(Ok(frame)) => frame,
+                        _ => break,
                     }
                 }
-            }
-        }
-    }
+                // Forgets the subscriptions that ended on their own
+                Some(finished) = subscriptions.join_next(), if !subscriptions.is_empty() => {
+                    if let Ok(subscription_id) = finished {
+                        active_subscriptions.remove(&subscription_id);
+                    }
+                    continue;
+                }
+                Some(finished) = streams.join_next(), if !streams.is_empty() => {
+                    if let Ok(request_id) = finished {
+                        active_streams.remove(&request_id);
+                    }
+                    continue;
+                }
+            };
+
+            // Frames the server can't make sense of are answered with an error
+            let either_request = match AllRequest::try_from(frame.as_ref()) {
+                Ok(request) => request,
+                Err(invalid) => {
+                    error!("Invalid frame: {}", invalid.error);
+                    send_invalid_request(&response_sender, encoder, invalid).await;
+                    continue;
+                }
+            };
+            let mut this = self.clone();
+            let response_sender = response_sender.clone();
+            match either_request {
+                AllRequest::ReqResp(message) => {
+                    let permit = acquire_in_flight_permit(&in_flight_permits).await;
+                    // Not bound to the connection, so a request that is already being
+                    // handled runs to completion even if the client goes away
+                    self.in_flight.spawn(async move {
+                        this.respond(message, response_sender, encoder).await;
+                        drop(permit);
+                    });
+                }
+                AllRequest::Batch(message) => {
+                    let permit = acquire_in_flight_permit(&in_flight_permits).await;
+                    self.in_flight.spawn(async move {
+                        this.respond_batch(message, response_sender, encoder).await;
+                        drop(permit);
+                    });
+                }
+                AllRequest::PubSub(message) => {
+                    let subscription_id = message.subscription_id;
+                    let handle = subscriptions.spawn(async move {
+                        this.subscribe(message, response_sender, encoder).await;
+                        subscription_id
+                    });
+                    if let Some(previous) = active_subscriptions.insert(subscription_id, handle) {
+                        previous.abort();
+                    }
+                }
+                AllRequest::Unsubscribe(message) => {
+                    if let Some(handle) = active_subscriptions.remove(&message.subscription_id) {
+                        handle.abort();
+                    }
+                    // Acknowledged even if the subscription had already ended
+                    send_subscription_closed(&response_sender, encoder, message.subscription_id)
+                        .await;
+                }
+                AllRequest::Stream(message) => {
+                    let request_id = message.request_id;
+                    let credits = Arc::new(Semaphore::new(INITIAL_CREDITS as usize));
+                    let task_credits = credits.clone();
+                    let handle = streams.spawn(async move {
+                        this.respond_stream(message, task_credits, response_sender, encoder)
+                            .await;
+                        request_id
+                    });
+                    if let Some((previous, _)) =
+                        active_streams.insert(request_id, (handle, credits))
+                    {
+                        previous.abort();
+                    }
+                }
+                AllRequest::StreamCredit(message) => {
+                    if let Some((_, credits)) = active_streams.get(&message.request_id) {
+                        credits.add_permits(message.credits as usize);
+                    }
+                }
+                AllRequest::StreamCancel(message) => {
+                    if let Some((handle, _)) = active_streams.remove(&message.request_id) {
+                        handle.abort();
+                    }
+                }
+                AllRequest::Deactivate(message) => {
+                    let permit = acquire_in_flight_permit(&in_flight_permits).await;
+                    self.in_flight.spawn(async move {
+                        this.respond_deactivate(message, response_sender, encoder)
+                            .await;
+                        drop(permit);
+                    });
+                }
+            }
+        }
+    }
+
+    /// Reads the [Handshake] sent by the client on a new connection and answers it
+    ///
+    /// Fails if the client disconnects, or if it can't talk to this server
+    async fn accept_handshake(
+        &self,
+        frames: &mut FramedStream,
+    ) -> Result<HandshakeAccepted, ResponseError> {
+        let handshake_frame = match StreamExt::next(frames)
+            .instrument(info_span!("handshake_receive"))
+            .await
+        {
+            Some(Ok(handshake_frame)) => handshake_frame,
+            _ => {
+                return Err(ResponseError::IncompatibleProtocol(
+                    "disconnected".to_string(),
+                ));
+            }
+        };
+
+        let codec = self.registry.read().await.codec();
+        let compression = self.compression.algorithm_names();
+        let result = Self::negotiate(&handshake_frame, codec.name(), &compression);
+        if let Err(err) = &result {
+            error!("Rejecting connection: {}", err);
+        }
+
+        let response = HandshakeResponse {
+            result: result.clone(),
+        };
+        let ser_response =
+            bincode::serialize(&response).expect("Handshake serialization should be infalible");
+        frames
+            .send(frame::encode(FrameKind::HandshakeResponse, &ser_response))
+            .await
+            .map_err(|e| ResponseError::Unknown(e.to_string()))?;
+        result
+    }
+
+    /// Checks whether the features the client asks for on its handshake frame are supported
+    ///
+    /// `codec` is the name of the codec used by the registry, and `compression` the
+    /// compression algorithms this server supports
+    fn negotiate(
+        handshake_frame: &[u8],
+        codec: &str,
+        compression: &[String],
+    ) -> Result<HandshakeAccepted, ResponseError> {
+        let incompatible = |message: String| ResponseError::IncompatibleProtocol(message);
+        let (header, payload) =
+            frame::decode(handshake_frame).map_err(|e| incompatible(e.to_string()))?;
+        if header.kind != FrameKind::Handshake {
+            let message = format!("expected a handshake, got {:?}", header.kind);
+            return Err(incompatible(message));
+        }
+        let handshake: Handshake =
+            bincode::deserialize(&payload).map_err(|e| incompatible(e.to_string()))?;
+        handshake.negotiate(frame::SUPPORTED_VERSIONS, codec, compression)
+    }
+
+    /// Handles a single [RequestEnvelope], sending the serialized response to `response_sender`
+    ///
+    /// The response frame is built by `encoder`, which compresses large responses
+    async fn respond(
+        &mut self,
+        message: RequestEnvelope,
+        response_sender: ResponseSender,
+        encoder: FrameEncoder,
+    ) {
+        let request_id = message.request_id;
+        let response = match self.handle_request(message).await {
+            Ok(x) => x,
+            Err(err) => ResponseEnvelope::err(err),
+        };
+        let response = response.with_request_id(request_id);
+        let ser_result = bincode::serialize(&response);
+        let ser_response = match ser_result {
+            Ok(value) => value,
+            Err(err) => {
+                let new_return =
+                    ResponseEnvelope::err(ResponseError::SeralizationError(err.to_string()))
+                        .with_request_id(request_id);
+                bincode::serialize(&new_return)
+                    .expect("Serialization of response error should be infalible")
+            }
+        };
+        response_sender
+            .send(encoder.encode(FrameKind::Response, &ser_response))
+            .await
+            .inspect_err(|_| error!("The connection was closed before the response was sent"))
+            .ok();
+    }
+
+    /// Handles a [FrameKind::Deactivate] request, answering with an empty
+    /// [ResponseEnvelope] once the object is deactivated
+    async fn respond_deactivate(
+        &self,
+        message: RequestEnvelope,
+        response_sender: ResponseSender,
+        encoder: FrameEncoder,
+    ) {
+        let deactivated = match bincode::deserialize::<DeactivateRequest>(&message.payload) {
+            Ok(request) => {
+                self.deactivate(
+                    &message.handler_type,
+                    &message.handler_id,
+                    request.hooks_ran,
+                )
+                .await
+            }
+            Err(err) => Err(ResponseError::InvalidFrame(err.to_string())),
+        };
+        let response = match deactivated {
+            Ok(()) => ResponseEnvelope::new(vec![]),
+            Err(err) => ResponseEnvelope::err(err),
+        };
+        let response = response.with_request_id(message.request_id);
+        let ser_response =
+            bincode::serialize(&response).expect("Response serialization should be infalible");
+        response_sender
+            .send(encoder.encode(FrameKind::Response, &ser_response))
+            .await
+            .inspect_err(|_| error!("The connection was closed before the response was sent"))
+            .ok();
+    }
+
+    /// Handles a [BatchRequestEnvelope], dispatching its requests concurrently and sending
+    /// all their responses back on a single [BatchResponseEnvelope]
+    async fn respond_batch(
+        &mut self,
+        message: BatchRequestEnvelope,
+        response_sender: ResponseSender,
+        encoder: FrameEncoder,
+    ) {
+        let request_id = message.request_id;
+        let request_ids: Vec<u64> = message.requests.iter().map(|r| r.request_id).collect();
+        let calls = message.requests.into_iter().map(|request| {
+            let mut this = self.clone();
+            async move {
+                let request_id = request.request_id;
+                let response = match this.handle_request(request).await {
+                    Ok(x) => x,
+                    Err(err) => ResponseEnvelope::err(err),
+                };
+                response.with_request_id(request_id)
+            }
+        });
+        let responses = futures::future::join_all(calls).await;
+
+        let batch = BatchResponseEnvelope {
+            request_id,
+            responses,
+            error: None,
+        };
+        let ser_batch = match bincode::serialize(&batch) {
+            Ok(value) => value,
+            Err(err) => {
+                let error = ResponseError::SeralizationError(err.to_string());
+                let responses = request_ids
+                    .into_iter()
+                    .map(|id| ResponseEnvelope::err(error.clone()).with_request_id(id))
+                    .collect();
+                bincode::serialize(&BatchResponseEnvelope {
+                    request_id,
+                    responses,
+                    error: None,
+                })
+                .expect("Serialization of response error should be infalible")
+            }
+        };
+        response_sender
+            .send(encoder.encode(FrameKind::BatchResponse, &ser_batch))
+            .await
+            .inspect_err(|_| error!("The connection was closed before the response was sent"))
+            .ok();
+    }
+
+    /// Handles a [RequestEnvelope] answered with a stream, sending each item to
+    /// `response_sender` as a [StreamItemEnvelope]
+    ///
+    /// An item is only pulled from the handler's stream once the client has granted a
+    /// credit for it, so slow clients don't pile up items on the server
+    async fn respond_stream(
+        &mut self,
+        message: RequestEnvelope,
+        credits: Arc<Semaphore>,
+        response_sender: ResponseSender,
+        encoder: FrameEncoder,
+    ) {
+        let request_id = message.request_id;
+        let (handler_type, handler_id) = (message.handler_type.clone(), message.handler_id.clone());
+        let send_item = async |item: StreamItem| {
+            let envelope = StreamItemEnvelope { request_id, item };
+            let ser_envelope = bincode::serialize(&envelope).unwrap_or_else(|err| {
+                let error = StreamItem::Error(ResponseError::SeralizationError(err.to_string()));
+                bincode::serialize(&StreamItemEnvelope {
+                    request_id,
+                    item: error,
+                })
+                .expect("Serialization of response error should be infalible")
+            });
+            response_sender
+                .send(encoder.encode(FrameKind::StreamItem, &ser_envelope))
+                .await
+        };
+
+        let mut stream = match self.call_stream(message).await {
+            Ok(stream) => stream,
+            Err(err) => {
+                send_item(StreamItem::Error(err)).await.ok();
+                send_item(StreamItem::End).await.ok();
+                return;
+            }
+        };
+
+        loop {
+            match credits.acquire().await {
+                Ok(permit) => permit.forget(),
+                Err(_) => return,
+            }
+            let item = match AssertUnwindSafe(StreamExt::next(&mut stream))
+                .catch_unwind()
+                .await
+            {
+                Ok(Some(Ok(item))) => StreamItem::Item(item),
+                Ok(Some(Err(err))) => StreamItem::Error(err.into()),
+                Ok(None) => break,
+                Err(panic) => {
+                    let message = panic_message(panic.as_ref());
+                    let details =
+                        ErrorDetails::new(message).with_object(&handler_type, &handler_id);
+                    send_item(StreamItem::Error(ResponseError::Panic(details.into())))
+                        .await
+                        .ok();
+                    break;
+                }
+            };
+            if send_item(item).await.is_err() {
+                error!("The connection was closed before the stream ended");
+                return;
+            }
+        }
+        send_item(StreamItem::End).await.ok();
+    }
+
+    /// Handles a [SubscriptionRequest], forwarding all the published messages to
+    /// `response_sender`
+    ///
+    /// Once the subscription ends, the client is told with a [SubscriptionClosed]
+    async fn subscribe(
+        &mut self,
+        message: SubscriptionRequest,
+        response_sender: ResponseSender,
+        encoder: FrameEncoder,
+    ) {
+        let subscription_id = message.subscription_id;
+        let stream = self.call(message).await;
+
+        // If there is an upstream error to establish the subscription,
+        // wrapi it in a SubscriptionResponse and return earlier
+        let mut stream = match stream {
+            Ok(value) => value,
+            Err(err) => {
+                let envelope = SubscriptionEnvelope {
+                    subscription_id,
+                    response: SubscriptionResponse::err(err),
+                };
+                let ser_response =
+                    bincode::serialize(&envelope).expect("Error serialization should be infalible");
+                response_sender
+                    .send(encoder.encode(FrameKind::SubscriptionResponse, &ser_response))
+                    .await
+                    .ok();
+                send_subscription_closed(&response_sender, encoder, subscription_id).await;
+                return;
+            }
+        };
+
+        while let Some(value) = StreamExt::next(&mut stream).await {
+            let envelope = SubscriptionEnvelope {
+                subscription_id,
+                response: value,
+            };
+            let ser_result = bincode::serialize(&envelope);
+            let ser_response = match ser_result {
+                Ok(value) => value,
+                Err(err) => {
+                    let new_return = SubscriptionEnvelope {
+                        subscription_id,
+                        response: SubscriptionResponse::err(ResponseError::SeralizationError(
+                            err.to_string(),
+                        )),
+                    };
+                    bincode::serialize(&new_return)
+                        .expect("Serialization of response error should be infalible")
+                }
+            };
+
+            // Stop receiving messages if the sink we redirect messages to is
+            // closed. While the client is slow to read them, the messages are left on the
+            // object's channel, and the subscription ends if it falls too far behind
+            let sub_frame = encoder.encode(FrameKind::SubscriptionResponse, &ser_response);
+            if let Err(err) = response_sender.send(sub_frame).await {
+                error!("Channel is closed due {}", err);
+                return;
+            }
+        }
+        send_subscription_closed(&response_sender, encoder, subscription_id).await;
+    }
+}
+
+/// Best effort description of a panic, from its payload
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string())
 }
 
+/// Tells the client no more messages will be sent for `subscription_id`
+async fn send_subscription_closed(
+    response_sender: &ResponseSender,
+    encoder: FrameEncoder,
+    subscription_id: u64,
+) {
+    let ser_closed = bincode::serialize(&SubscriptionClosed { subscription_id })
+        .expect("SubscriptionClosed serialization should be infalible");
+    response_sender
+        .send(encoder.encode(FrameKind::SubscriptionClosed, &ser_closed))
+        .await
+        .ok();
+}
+
+/// Number of response frames that can wait to be written on each connection
+const RESPONSE_BUFFER: usize = 1024;
+
+/// Number of requests that can wait for the [RequestLayer]s to be ready, see
+/// [Service::with_layer]
+const LAYER_BUFFER: usize = 1024;
+
+/// Number of requests, batches and deactivations handled at once for each connection
+const MAX_IN_FLIGHT_PER_CONNECTION: usize = 1024;
+
+/// Waits until the connection can handle one more request
+async fn acquire_in_flight_permit(permits: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+    permits
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("The in-flight semaphore is never closed")
+}
+
+/// Channel in which the serialized responses are sent, to be written on the connection
+type ResponseSender = mpsc::Sender<Bytes>;
+
 #[derive(Debug)]
 enum AllRequest {
     ReqResp(RequestEnvelope),
+    Batch(BatchRequestEnvelope),
     PubSub(SubscriptionRequest),
+    Unsubscribe(Unsubscribe),
+    Stream(RequestEnvelope),
+    StreamCredit(StreamCredit),
+    StreamCancel(StreamCancel),
+    Deactivate(RequestEnvelope),
+}
+
+/// Frame from a client that couldn't be parsed into an [AllRequest]
+#[derive(Debug)]
+struct InvalidRequest {
+    /// Kind of the frame and id of the request it carries, when they could be read
+    request: Option<(FrameKind, u64)>,
+    error: ResponseError,
+}
+
+impl From<FrameError> for InvalidRequest {
+    fn from(err: FrameError) -> Self {
+        InvalidRequest {
+            request: None,
+            error: err.into(),
+        }
+    }
+}
+
+/// Parses a frame coming from a client, using its header to tell
+/// which request it carries
+impl TryFrom<&[u8]> for AllRequest {
+    type Error = InvalidRequest;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let (header, payload) = frame::decode(value)?;
+        // Every request starts with its id, so it can be read even if the rest of the
+        // payload can't
+        let des_error = |e: bincode::Error| InvalidRequest {
+            request: bincode::deserialize::<u64>(&payload)
+                .ok()
+                .map(|request_id| (header.kind, request_id)),
+            error: ResponseError::InvalidFrame(e.to_string()),
+        };
+        match header.kind {
+            FrameKind::Request => Ok(AllRequest::ReqResp(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::BatchRequest => Ok(AllRequest::Batch(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::SubscriptionRequest => Ok(AllRequest::PubSub(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::Unsubscribe => Ok(AllRequest::Unsubscribe(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::StreamRequest => Ok(AllRequest::Stream(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::StreamCredit => Ok(AllRequest::StreamCredit(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::StreamCancel => Ok(AllRequest::StreamCancel(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            FrameKind::Deactivate => Ok(AllRequest::Deactivate(
+                bincode::deserialize(&payload).map_err(des_error)?,
+            )),
+            kind => Err(InvalidRequest {
+                request: None,
+                error: ResponseError::InvalidFrame(format!("unexpected frame kind {:?}", kind)),
+            }),
+        }
+    }
+}
+
+/// Answers a frame that couldn't be parsed
+///
+/// The error is sent to the request the frame carries, when its id could be read, so the
+/// client isn't left waiting for it. Otherwise it is sent on an untagged
+/// [FrameKind::Error] frame, and the client closes the connection
+async fn send_invalid_request(
+    response_sender: &ResponseSender,
+    encoder: FrameEncoder,
+    invalid: InvalidRequest,
+) {
+    let serialization_error = "Error serialization should be infalible";
+    let frame = match invalid.request {
+        Some((FrameKind::Request | FrameKind::Deactivate, request_id)) => {
+            let response = ResponseEnvelope::err(invalid.error).with_request_id(request_id);
+            let ser_response = bincode::serialize(&response).expect(serialization_error);
+            encoder.encode(FrameKind::Response, &ser_response)
+        }
+        Some((FrameKind::BatchRequest, request_id)) => {
+            let batch = BatchResponseEnvelope {
+                request_id,
+                responses: vec![],
+                error: Some(invalid.error),
+            };
+            let ser_batch = bincode::serialize(&batch).expect(serialization_error);
+            encoder.encode(FrameKind::BatchResponse, &ser_batch)
+        }
+        Some((FrameKind::StreamRequest, request_id)) => {
+            for item in [StreamItem::Error(invalid.error), StreamItem::End] {
+                let ser_item = bincode::serialize(&StreamItemEnvelope { request_id, item })
+                    .expect(serialization_error);
+                response_sender
+                    .send(encoder.encode(FrameKind::StreamItem, &ser_item))
+                    .await
+                    .ok();
+            }
+            return;
+        }
+        Some((FrameKind::SubscriptionRequest, subscription_id)) => {
+            let envelope = SubscriptionEnvelope {
+                subscription_id,
+                response: SubscriptionResponse::err(invalid.error),
+            };
+            let ser_response = bincode::serialize(&envelope).expect(serialization_error);
+            response_sender
+                .send(encoder.encode(FrameKind::SubscriptionResponse, &ser_response))
+                .await
+                .ok();
+            send_subscription_closed(response_sender, encoder, subscription_id).await;
+            return;
+        }
+        _ => {
+            let ser_error = bincode::serialize(&invalid.error).expect(serialization_error);
+            encoder.encode(FrameKind::Error, &ser_error)
+        }
+    };
+    response_sender.send(frame).await.ok();
 }
 
 #[cfg(test)]
@@ -472,12 +1592,17 @@ mod test {
     use async_trait::async_trait;
     use rio_macros::{Message, TypeName, WithId};
     use serde::{Deserialize, Serialize};
+    use tokio::net::TcpStream;
     use tokio::time::timeout;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
     use tower::ServiceExt;
 
     use super::*;
+    use crate::cluster::storage::Member;
     use crate::cluster::storage::local::LocalStorage;
     use crate::object_placement::local::LocalObjectPlacement;
+    use crate::protocol::error_code::{ErrorCode, Retryability};
+    use crate::protocol::frame::{FrameHeader, PROTOCOL_VERSION};
 
     use crate::registry::Handler;
 
@@ -515,17 +1640,42 @@ mod test {
         }
     }
 
+    /// Answers with the value of the `tenant` metadata key
+    #[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+    #[rio_path = "crate"]
+    struct TenantMessage {}
+
+    #[async_trait]
+    impl Handler<TenantMessage> for MockService {
+        type Returns = Option<String>;
+        type Error = ();
+        async fn handle(
+            &mut self,
+            _: TenantMessage,
+            _: Arc<AppData>,
+        ) -> Result<Self::Returns, Self::Error> {
+            metadata::set_response("served-by", self.id.clone());
+            Ok(metadata::get("tenant"))
+        }
+    }
+
     fn svc() -> Service<LocalStorage, LocalObjectPlacement> {
         let mut registry = Registry::new();
         registry.add_type::<MockService>();
         registry.add_handler::<MockService, MockMessage>();
+        registry.add_handler::<MockService, TenantMessage>();
 
         Service {
-            address: "0.0.0.0:5000".to_string(),
+            address: Address::new("0.0.0.0", 5000),
             registry: Arc::new(RwLock::new(registry)),
             members_storage: LocalStorage::default(),
             object_placement_provider: Arc::new(RwLock::new(LocalObjectPlacement::default())),
             app_data: Arc::new(AppData::new()),
+            compression: CompressionConfig::default(),
+            in_flight: TaskTracker::new(),
+            draining: DrainHandle::default(),
+            activation_limits: ActivationLimits::default(),
+            layered: None,
         }
     }
 
@@ -555,6 +1705,626 @@ mod test {
         assert_eq!(resp.text, "* received hi".to_string());
     }
 
+    #[tokio::test]
+    async fn test_service_call_metadata() {
+        let mut svc = svc();
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "TenantMessage".into(),
+            bincode::serialize(&TenantMessage {}).unwrap(),
+        )
+        .with_metadata(metadata::Metadata::from([(
+            "tenant".to_string(),
+            "acme".to_string(),
+        )]));
+        let resp = svc.call(req).await.unwrap();
+        assert_eq!(resp.metadata["served-by"], "*");
+        let tenant: Option<String> = bincode::deserialize(&resp.body.unwrap()).unwrap();
+        assert_eq!(tenant, Some("acme".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_service_call_deadline_exceeded() {
+        let mut svc = svc();
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        )
+        .with_deadline(Duration::ZERO);
+        let resp = svc.call(req).await;
+        assert_eq!(resp.unwrap_err(), ResponseError::DeadlineExceeded);
+
+        // Nothing was placed nor activated for it
+        assert!(!svc.registry.read().await.has("MockService", "*").await);
+        let placement = svc
+            .object_placement_provider
+            .read()
+            .await
+            .lookup(&ObjectId::new("MockService", "*"))
+            .await
+            .unwrap();
+        assert_eq!(placement, None);
+    }
+
+    #[tokio::test]
+    async fn test_draining_places_new_objects_elsewhere() {
+        let mut svc = svc();
+        let req = |handler_id: &str| {
+            RequestEnvelope::new(
+                "MockService".into(),
+                handler_id.into(),
+                "MockMessage".into(),
+                bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+            )
+        };
+        let mut this_server = Member::new(svc.address.clone());
+        this_server.set_active(true);
+        svc.members_storage.push(this_server).await.unwrap();
+        svc.call(req("1")).await.unwrap().body.unwrap();
+        svc.draining.drain();
+
+        // The objects that are already here keep being served
+        svc.call(req("1")).await.unwrap().body.unwrap();
+
+        // The new ones need another server
+        let resp = svc.call(req("2")).await;
+        assert!(matches!(resp, Err(ResponseError::Unavailable(_))));
+
+        let mut other_server = Member::new(Address::new("0.0.0.0", 5001));
+        other_server.set_active(true);
+        svc.members_storage.push(other_server).await.unwrap();
+        let resp = svc.call(req("2")).await;
+        assert_eq!(
+            resp.unwrap_err(),
+            ResponseError::Redirect(Address::new("0.0.0.0", 5001))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate_object() {
+        let mut svc = svc();
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "1".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+        let other_address = Address::new("0.0.0.0", 5001);
+        for address in [svc.address.clone(), other_address.clone()] {
+            let mut member = Member::new(address);
+            member.set_active(true);
+            svc.members_storage.push(member).await.unwrap();
+        }
+        svc.call(req.clone()).await.unwrap().body.unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        svc.migrate_object("MockService", "1", other_address.clone(), deadline)
+            .await
+            .unwrap();
+        assert!(!svc.registry.read().await.has("MockService", "1").await);
+        let resp = svc.call(req).await;
+        assert_eq!(
+            resp.unwrap_err(),
+            ResponseError::Redirect(other_address.clone())
+        );
+
+        // It isn't here anymore
+        let resp = svc
+            .migrate_object("MockService", "1", Address::new("0.0.0.0", 5002), deadline)
+            .await;
+        assert_eq!(resp.unwrap_err(), ResponseError::Redirect(other_address));
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_with_layer() {
+        let require_token = tower::layer::layer_fn(|inner: BoxRequestService| {
+            tower::service_fn(move |req: RequestEnvelope| {
+                let inner = inner.clone();
+                async move {
+                    if req.metadata.contains_key("token") {
+                        inner.oneshot(req).await
+                    } else {
+                        Err(ResponseError::NotSupported("missing token".to_string()))
+                    }
+                }
+            })
+        });
+        let mut svc = svc().with_layer(&require_token);
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+
+        let error = svc.handle_request(req.clone()).await.unwrap_err();
+        assert_eq!(
+            error,
+            ResponseError::NotSupported("missing token".to_string())
+        );
+
+        let req = req.with_metadata(metadata::Metadata::from([(
+            "token".to_string(),
+            "secret".to_string(),
+        )]));
+        let resp = svc.handle_request(req).await.unwrap();
+        let resp: MockResponse = bincode::deserialize(&resp.body.unwrap()).unwrap();
+        assert_eq!(resp.text, "* received hi".to_string());
+    }
+
+    /// Sends a [MockMessage] to the [MockService] `id`
+    async fn call_mock(
+        svc: &mut Service<LocalStorage, LocalObjectPlacement>,
+        id: &str,
+    ) -> Result<ResponseEnvelope, ResponseError> {
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            id.into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+        svc.call(req).await
+    }
+
+    #[tokio::test]
+    async fn test_service_call_evicts_least_recently_used() {
+        let mut svc = svc();
+        svc.activation_limits.max_activations = Some(2);
+        call_mock(&mut svc, "a").await.unwrap();
+        call_mock(&mut svc, "b").await.unwrap();
+        call_mock(&mut svc, "a").await.unwrap();
+        call_mock(&mut svc, "c").await.unwrap();
+
+        let registry = svc.registry.read().await;
+        assert!(registry.has("MockService", "a").await);
+        assert!(!registry.has("MockService", "b").await);
+        assert!(registry.has("MockService", "c").await);
+        let placement = svc.object_placement_provider.read().await;
+        let lookup = placement.lookup(&ObjectId::new("MockService", "b")).await;
+        assert_eq!(lookup.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_service_call_rejects_over_type_limit() {
+        let mut svc = svc();
+        svc.activation_limits.policy = ActivationLimitPolicy::Reject;
+        svc.registry
+            .write()
+            .await
+            .set_max_activations::<MockService>(Some(1));
+        call_mock(&mut svc, "a").await.unwrap();
+
+        let error = call_mock(&mut svc, "b").await.unwrap_err();
+        assert!(matches!(error, ResponseError::Unavailable(_)));
+        assert!(error.is_retryable());
+        // Another server can take it
+        let placement = svc.object_placement_provider.read().await;
+        let lookup = placement.lookup(&ObjectId::new("MockService", "b")).await;
+        assert_eq!(lookup.unwrap(), None);
+        drop(placement);
+
+        // The active objects are still served
+        call_mock(&mut svc, "a").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_service_call_handler_not_found() {
+        let mut svc = svc();
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "UnknownMessage".into(),
+            vec![],
+        );
+        let error = svc.call(req).await.unwrap_err();
+        assert_eq!(error, ResponseError::HandlerNotFound);
+        assert_eq!(error.code(), ErrorCode::HandlerNotFound);
+        assert_eq!(error.retryability(), Retryability::Never);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[tokio::test]
+    async fn test_service_call_metrics_labels() {
+        let mut svc = svc();
+        let req = RequestEnvelope::new(
+            "MadeUpService".into(),
+            "*".into(),
+            "MadeUpMessage".into(),
+            vec![],
+        );
+        let error = svc.call(req).await.unwrap_err();
+        assert_eq!(error.code(), ErrorCode::NotSupported);
+
+        // Only the registered names are used as labels
+        let text = metrics::gather();
+        assert!(!text.contains("MadeUp"));
+        assert!(text.contains(
+            r#"rio_requests_total{message="unknown",result="NotSupported",type="unknown"}"#
+        ));
+    }
+
+    /// Runs the service on a new TCP listener, and returns a stream connected to it
+    async fn run_service(
+        mut svc: Service<LocalStorage, LocalObjectPlacement>,
+    ) -> Framed<TcpStream, LengthDelimitedCodec> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            svc.run(stream.into()).await;
+        });
+        let stream = TcpStream::connect(address).await.unwrap();
+        Framed::new(stream, LengthDelimitedCodec::new())
+    }
+
+    /// Sends `handshake` to the service, and returns its response
+    async fn send_handshake(
+        frames: &mut Framed<TcpStream, LengthDelimitedCodec>,
+        handshake: &Handshake,
+    ) -> HandshakeResponse {
+        let ser_handshake = bincode::serialize(handshake).unwrap();
+        frames
+            .send(frame::encode(FrameKind::Handshake, &ser_handshake))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::HandshakeResponse);
+        bincode::deserialize(&payload).unwrap()
+    }
+
+    /// Same as [run_service], but the stream has already done the handshake
+    async fn connect(
+        svc: Service<LocalStorage, LocalObjectPlacement>,
+    ) -> Framed<TcpStream, LengthDelimitedCodec> {
+        let mut frames = run_service(svc).await;
+        let response = send_handshake(&mut frames, &Handshake::default()).await;
+        assert!(response.result.is_ok());
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_run_incompatible_handshake() {
+        let mut frames = run_service(svc()).await;
+        let handshake = Handshake {
+            codec: "not-a-codec".to_string(),
+            ..Default::default()
+        };
+        let response = send_handshake(&mut frames, &handshake).await;
+        assert!(matches!(
+            response.result,
+            Err(ResponseError::IncompatibleProtocol(_))
+        ));
+
+        // The server drops the connection
+        assert!(frames.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_newer_client_handshake() {
+        let mut frames = run_service(svc()).await;
+        // A client from a newer release, which still speaks this server's version
+        let handshake = Handshake {
+            max_protocol_version: PROTOCOL_VERSION + 1,
+            ..Default::default()
+        };
+        let header = FrameHeader {
+            version: PROTOCOL_VERSION + 1,
+            ..FrameHeader::new(FrameKind::Handshake)
+        };
+        let ser_handshake = bincode::serialize(&handshake).unwrap();
+        frames
+            .send(frame::encode_with_header(header, &ser_handshake))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (_, payload) = frame::decode(&response_frame).unwrap();
+        let response: HandshakeResponse = bincode::deserialize(&payload).unwrap();
+        assert_eq!(response.result.unwrap().protocol_version, PROTOCOL_VERSION);
+
+        // The connection goes on with the version they agreed on
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+        frames
+            .send(frame::encode(
+                FrameKind::Request,
+                &bincode::serialize(&req).unwrap(),
+            ))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, _) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::Response);
+        assert_eq!(header.version, PROTOCOL_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_run_missing_handshake() {
+        let mut frames = run_service(svc()).await;
+        frames
+            .send(frame::encode(FrameKind::Request, b""))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (_, payload) = frame::decode(&response_frame).unwrap();
+        let response: HandshakeResponse = bincode::deserialize(&payload).unwrap();
+        assert!(matches!(
+            response.result,
+            Err(ResponseError::IncompatibleProtocol(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_invalid_frame() {
+        let mut frames = connect(svc()).await;
+
+        // Unknown frame kind
+        frames.send(Bytes::from_static(&[0, 1, 200])).await.unwrap();
+        let error_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&error_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::Error);
+        let error: ResponseError = bincode::deserialize(&payload).unwrap();
+        assert!(matches!(error, ResponseError::InvalidFrame(_)));
+
+        // The connection is still usable after the invalid frame
+        let mut req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+        req.request_id = 7;
+        let ser_req = bincode::serialize(&req).unwrap();
+        frames
+            .send(frame::encode(FrameKind::Request, &ser_req))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::Response);
+        let resp: ResponseEnvelope = bincode::deserialize(&payload).unwrap();
+        assert_eq!(resp.request_id, 7);
+        assert!(resp.body.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_undecodable_request() {
+        let mut frames = connect(svc()).await;
+
+        // The id can be read, but not the rest of the request
+        let mut payload = bincode::serialize(&7u64).unwrap();
+        payload.extend_from_slice(&[255, 255]);
+        frames
+            .send(frame::encode(FrameKind::Request, &payload))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::Response);
+        let resp: ResponseEnvelope = bincode::deserialize(&payload).unwrap();
+        assert_eq!(resp.request_id, 7);
+        assert!(matches!(resp.body, Err(ResponseError::InvalidFrame(_))));
+    }
+
+    #[tokio::test]
+    async fn test_run_undecodable_batch() {
+        let mut frames = connect(svc()).await;
+
+        let mut payload = bincode::serialize(&7u64).unwrap();
+        payload.extend_from_slice(&[255, 255]);
+        frames
+            .send(frame::encode(FrameKind::BatchRequest, &payload))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::BatchResponse);
+        let batch: BatchResponseEnvelope = bincode::deserialize(&payload).unwrap();
+        assert_eq!(batch.request_id, 7);
+        assert!(batch.responses.is_empty());
+        assert!(matches!(batch.error, Some(ResponseError::InvalidFrame(_))));
+    }
+
+    /// Sends a [GatedMessage] to the [MockService] `id` over `frames`
+    async fn send_gated(frames: &mut Framed<TcpStream, LengthDelimitedCodec>, id: &str) {
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            id.into(),
+            "GatedMessage".into(),
+            bincode::serialize(&GatedMessage {}).unwrap(),
+        );
+        let ser_req = bincode::serialize(&req).unwrap();
+        frames
+            .send(frame::encode(FrameKind::Request, &ser_req))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_layer_shared_by_connections() {
+        let svc = svc().with_layer(&tower::limit::ConcurrencyLimitLayer::new(1));
+        svc.registry
+            .write()
+            .await
+            .add_handler::<MockService, GatedMessage>();
+        let app_data = svc.app_data.clone();
+        let gate = || app_data.get_or_default::<Gate>();
+        let started = || gate().started.load(std::sync::atomic::Ordering::SeqCst);
+        let mut first = connect(svc.clone()).await;
+        let mut second = connect(svc).await;
+
+        send_gated(&mut first, "1").await;
+        timeout(Duration::from_secs(5), async {
+            while started() < 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // The limit applies to the requests of every connection
+        send_gated(&mut second, "2").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(started(), 1);
+
+        gate().release.add_permits(2);
+        for frames in [&mut first, &mut second] {
+            timeout(Duration::from_secs(5), frames.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(started(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_with_rate_limit() {
+        // Layers that can't be cloned work too
+        let svc = svc().with_layer(&tower::limit::RateLimitLayer::new(
+            1,
+            Duration::from_secs(60),
+        ));
+        let mut first = svc.clone();
+        let mut second = svc;
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+
+        first.handle_request(req.clone()).await.unwrap();
+        let limited = timeout(Duration::from_millis(100), second.handle_request(req)).await;
+        assert!(limited.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_batch() {
+        let mut frames = connect(svc()).await;
+        let requests = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let mut req = RequestEnvelope::new(
+                    "MockService".into(),
+                    i.to_string(),
+                    "MockMessage".into(),
+                    bincode::serialize(&MockMessage {
+                        text: text.to_string(),
+                    })
+                    .unwrap(),
+                );
+                req.request_id = i as u64;
+                req
+            })
+            .collect();
+        let batch = BatchRequestEnvelope {
+            request_id: 7,
+            requests,
+        };
+        let ser_batch = bincode::serialize(&batch).unwrap();
+        frames
+            .send(frame::encode(FrameKind::BatchRequest, &ser_batch))
+            .await
+            .unwrap();
+
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::BatchResponse);
+        let batch: BatchResponseEnvelope = bincode::deserialize(&payload).unwrap();
+        assert_eq!(batch.request_id, 7);
+        let texts: Vec<_> = batch
+            .responses
+            .into_iter()
+            .enumerate()
+            .map(|(i, resp)| {
+                assert_eq!(resp.request_id, i as u64);
+                let resp: MockResponse = bincode::deserialize(&resp.body.unwrap()).unwrap();
+                resp.text
+            })
+            .collect();
+        assert_eq!(texts, vec!["0 received a", "1 received b", "2 received c"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_with_codec() {
+        let svc = svc();
+        svc.registry
+            .write()
+            .await
+            .set_codec(Arc::new(crate::codec::json::JsonCodec));
+
+        let mut frames = run_service(svc).await;
+        let handshake = Handshake {
+            codec: "json".to_string(),
+            ..Default::default()
+        };
+        let response = send_handshake(&mut frames, &handshake).await;
+        assert!(response.result.is_ok());
+
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            br#"{"text":"hi"}"#.to_vec(),
+        );
+        let ser_req = bincode::serialize(&req).unwrap();
+        frames
+            .send(frame::encode(FrameKind::Request, &ser_req))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (_, payload) = frame::decode(&response_frame).unwrap();
+        let resp: ResponseEnvelope = bincode::deserialize(&payload).unwrap();
+        assert_eq!(resp.body.unwrap(), br#"{"text":"* received hi"}"#);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn test_run_compressed_response() {
+        use crate::protocol::compression::Compression;
+
+        let mut svc = svc();
+        svc.compression = CompressionConfig::new(vec![Compression::Zstd]).with_threshold(0);
+        let mut frames = run_service(svc).await;
+        let handshake = Handshake {
+            compression: vec!["lz4".to_string(), "zstd".to_string()],
+            ..Default::default()
+        };
+        let response = send_handshake(&mut frames, &handshake).await;
+        assert_eq!(
+            response.result.unwrap().compression,
+            Some("zstd".to_string())
+        );
+
+        // Requests don't need to be compressed
+        let req = RequestEnvelope::new(
+            "MockService".into(),
+            "*".into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        );
+        let ser_req = bincode::serialize(&req).unwrap();
+        frames
+            .send(frame::encode(FrameKind::Request, &ser_req))
+            .await
+            .unwrap();
+        let response_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&response_frame).unwrap();
+        assert_eq!(header.compression, Some(Compression::Zstd));
+        let resp: ResponseEnvelope = bincode::deserialize(&payload).unwrap();
+        let resp: MockResponse = bincode::deserialize(&resp.body.unwrap()).unwrap();
+        assert_eq!(resp.text, "* received hi".to_string());
+    }
+
     #[tokio::test]
     async fn test_service_subscription() {
         let mut svc = svc();
@@ -563,6 +2333,7 @@ mod test {
             .unwrap();
 
         let req = SubscriptionRequest {
+            subscription_id: 1,
             handler_type: "MockService".into(),
             handler_id: "*".into(),
         };
@@ -571,4 +2342,328 @@ mod test {
         let _stream = call_future.await.unwrap();
         // TODO assert_eq!(..., stream.next().await);
     }
+
+    #[tokio::test]
+    async fn test_run_unsubscribe() {
+        let svc = svc();
+        let router = || svc.app_data.get_or_default::<MessageRouter>();
+        let mut frames = connect(svc.clone()).await;
+
+        let req = SubscriptionRequest {
+            subscription_id: 3,
+            handler_type: "MockService".into(),
+            handler_id: "*".into(),
+        };
+        let ser_req = bincode::serialize(&req).unwrap();
+        frames
+            .send(frame::encode(FrameKind::SubscriptionRequest, &ser_req))
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(3), async {
+            while !router().has_subscription("MockService".into(), "*".into()) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // Published messages are tagged with the subscription id
+        router().publish(
+            "MockService".into(),
+            "*".into(),
+            SubscriptionResponse::new(vec![42]),
+        );
+        let message_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&message_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::SubscriptionResponse);
+        let envelope: SubscriptionEnvelope = bincode::deserialize(&payload).unwrap();
+        assert_eq!(envelope.subscription_id, 3);
+        assert_eq!(envelope.response.body, Ok(vec![42]));
+
+        let ser_unsubscribe = bincode::serialize(&Unsubscribe { subscription_id: 3 }).unwrap();
+        frames
+            .send(frame::encode(FrameKind::Unsubscribe, &ser_unsubscribe))
+            .await
+            .unwrap();
+        let closed_frame = frames.next().await.unwrap().unwrap();
+        let (header, payload) = frame::decode(&closed_frame).unwrap();
+        assert_eq!(header.kind, FrameKind::SubscriptionClosed);
+        let closed: SubscriptionClosed = bincode::deserialize(&payload).unwrap();
+        assert_eq!(closed.subscription_id, 3);
+
+        // The channel is dropped once it has no subscribers left
+        timeout(Duration::from_secs(3), async {
+            while router().has_subscription("MockService".into(), "*".into()) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    /// Its Shutdown hook fails for the id `fail`, and takes a while for any other id
+    #[derive(Default, WithId, TypeName)]
+    #[rio_path = "crate"]
+    struct ShutdownService {
+        id: String,
+    }
+
+    /// Number of [ShutdownService]s that finished their Shutdown hook
+    #[derive(Default)]
+    struct ShutdownCount(std::sync::atomic::AtomicUsize);
+
+    #[async_trait]
+    impl Handler<LifecycleMessage> for ShutdownService {
+        type Returns = ();
+        type Error = crate::errors::ServiceObjectLifeCycleError;
+        async fn handle(
+            &mut self,
+            message: LifecycleMessage,
+            app_data: Arc<AppData>,
+        ) -> Result<(), Self::Error> {
+            if !matches!(message, LifecycleMessage::Shutdown) {
+                return Ok(());
+            }
+            if self.id == "fail" {
+                return Err(crate::errors::ServiceObjectLifeCycleError::Shutdown);
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            app_data
+                .get_or_default::<ShutdownCount>()
+                .0
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<MockMessage> for ShutdownService {
+        type Returns = MockResponse;
+        type Error = ();
+        async fn handle(
+            &mut self,
+            message: MockMessage,
+            _: Arc<AppData>,
+        ) -> Result<Self::Returns, Self::Error> {
+            Ok(MockResponse { text: message.text })
+        }
+    }
+
+    /// A [svc] that also hosts [ShutdownService], with this server and `other_address` as
+    /// active members
+    async fn shutdown_svc(other_address: &Address) -> Service<LocalStorage, LocalObjectPlacement> {
+        let svc = svc();
+        {
+            let mut registry = svc.registry.write().await;
+            registry.add_type::<ShutdownService>();
+            registry.add_handler::<ShutdownService, LifecycleMessage>();
+            registry.add_handler::<ShutdownService, MockMessage>();
+        }
+        for address in [svc.address.clone(), other_address.clone()] {
+            let mut member = Member::new(address);
+            member.set_active(true);
+            svc.members_storage.push(member).await.unwrap();
+        }
+        svc
+    }
+
+    fn shutdown_service_request(id: &str) -> RequestEnvelope {
+        RequestEnvelope::new(
+            "ShutdownService".into(),
+            id.into(),
+            "MockMessage".into(),
+            bincode::serialize(&MockMessage { text: "hi".into() }).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_deactivate() {
+        let other_address = Address::new("0.0.0.0", 5001);
+        let mut svc = shutdown_svc(&other_address).await;
+        let app_data = svc.app_data.clone();
+        let shutdowns = || {
+            app_data
+                .get_or_default::<ShutdownCount>()
+                .0
+                .load(std::sync::atomic::Ordering::SeqCst)
+        };
+
+        svc.call(shutdown_service_request("1")).await.unwrap();
+        svc.deactivate("ShutdownService", "1", false).await.unwrap();
+        assert_eq!(shutdowns(), 1);
+        assert!(!svc.registry.read().await.has("ShutdownService", "1").await);
+        let placement = svc
+            .object_placement_provider
+            .read()
+            .await
+            .lookup(&ObjectId::new("ShutdownService", "1"))
+            .await;
+        assert_eq!(placement, Ok(None));
+
+        // It already ran its hooks, so they don't run again
+        svc.call(shutdown_service_request("2")).await.unwrap();
+        svc.deactivate("ShutdownService", "2", true).await.unwrap();
+        assert_eq!(shutdowns(), 1);
+        assert!(!svc.registry.read().await.has("ShutdownService", "2").await);
+
+        // Nothing to do for the objects that aren't active
+        svc.deactivate("ShutdownService", "3", false).await.unwrap();
+        assert_eq!(shutdowns(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_object_failed_shutdown() {
+        let other_address = Address::new("0.0.0.0", 5001);
+        let mut svc = shutdown_svc(&other_address).await;
+        let deadline = Instant::now() + Duration::from_secs(1);
+
+        svc.call(shutdown_service_request("fail")).await.unwrap();
+        let resp = svc
+            .migrate_object("ShutdownService", "fail", other_address.clone(), deadline)
+            .await;
+        assert!(resp.is_err());
+
+        // Nothing moved, and the object takes requests again
+        assert!(
+            svc.registry
+                .read()
+                .await
+                .has("ShutdownService", "fail")
+                .await
+        );
+        let placement = svc
+            .object_placement_provider
+            .read()
+            .await
+            .lookup(&ObjectId::new("ShutdownService", "fail"))
+            .await;
+        assert_eq!(placement, Ok(Some(svc.address.clone())));
+        svc.call(shutdown_service_request("fail")).await.unwrap();
+
+        // Same when the hook takes too long
+        svc.call(shutdown_service_request("slow")).await.unwrap();
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let resp = svc
+            .migrate_object("ShutdownService", "slow", other_address, deadline)
+            .await;
+        assert_eq!(resp.unwrap_err(), ResponseError::DeadlineExceeded);
+        assert!(
+            svc.registry
+                .read()
+                .await
+                .has("ShutdownService", "slow")
+                .await
+        );
+        svc.call(shutdown_service_request("slow")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_object_blocks_requests() {
+        let other_address = Address::new("0.0.0.0", 5001);
+        let mut svc = shutdown_svc(&other_address).await;
+        svc.call(shutdown_service_request("1")).await.unwrap();
+
+        let migrating_svc = svc.clone();
+        let to = other_address.clone();
+        let migration = tokio::spawn(async move {
+            let deadline = Instant::now() + Duration::from_secs(1);
+            migrating_svc
+                .migrate_object("ShutdownService", "1", to, deadline)
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The object is saving its state, so it can't take changes anymore
+        let resp = svc.call(shutdown_service_request("1")).await;
+        assert!(matches!(resp, Err(ResponseError::Unavailable(_))));
+
+        migration.await.unwrap().unwrap();
+        let resp = svc.call(shutdown_service_request("1")).await;
+        assert_eq!(resp.unwrap_err(), ResponseError::Redirect(other_address));
+    }
+
+    /// Counts the [GatedMessage]s being handled, which wait until they are released
+    struct Gate {
+        started: std::sync::atomic::AtomicUsize,
+        release: Semaphore,
+    }
+
+    impl Default for Gate {
+        fn default() -> Self {
+            Gate {
+                started: Default::default(),
+                release: Semaphore::new(0),
+            }
+        }
+    }
+
+    #[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+    #[rio_path = "crate"]
+    struct GatedMessage {}
+
+    #[async_trait]
+    impl Handler<GatedMessage> for MockService {
+        type Returns = ();
+        type Error = ();
+        async fn handle(&mut self, _: GatedMessage, app_data: Arc<AppData>) -> Result<(), ()> {
+            let gate = app_data.get_or_default::<Gate>();
+            gate.started
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            gate.release.acquire().await.unwrap().forget();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_in_flight_limit() {
+        let svc = svc();
+        svc.registry
+            .write()
+            .await
+            .add_handler::<MockService, GatedMessage>();
+        let app_data = svc.app_data.clone();
+        let gate = || app_data.get_or_default::<Gate>();
+        let started = || gate().started.load(std::sync::atomic::Ordering::SeqCst);
+        let mut frames = connect(svc).await;
+
+        let total = MAX_IN_FLIGHT_PER_CONNECTION + 10;
+        for i in 0..total {
+            let mut req = RequestEnvelope::new(
+                "MockService".into(),
+                i.to_string(),
+                "GatedMessage".into(),
+                bincode::serialize(&GatedMessage {}).unwrap(),
+            );
+            req.request_id = i as u64;
+            let ser_req = bincode::serialize(&req).unwrap();
+            frames
+                .send(frame::encode(FrameKind::Request, &ser_req))
+                .await
+                .unwrap();
+        }
+
+        // The connection stops reading requests once the limit is reached
+        timeout(Duration::from_secs(5), async {
+            while started() < MAX_IN_FLIGHT_PER_CONNECTION {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(started(), MAX_IN_FLIGHT_PER_CONNECTION);
+
+        // And picks them up again as they finish
+        gate().release.add_permits(total);
+        for _ in 0..total {
+            let response_frame = timeout(Duration::from_secs(5), frames.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            let (header, _) = frame::decode(&response_frame).unwrap();
+            assert_eq!(header.kind, FrameKind::Response);
+        }
+        assert_eq!(started(), total);
+    }
 }
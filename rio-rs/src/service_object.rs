@@ -4,11 +4,14 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::sync::oneshot;
 use tracing::error;
 
 use crate::app_data::AppData;
+use crate::codec::{Codec, default_codec};
 use crate::errors::ServiceObjectLifeCycleError;
 use crate::protocol::{ClientError, RequestEnvelope, RequestError};
+use crate::protocol::{deadline, metadata};
 use crate::registry::{Handler, IdentifiableType, Message};
 use crate::server::{AdminCommands, AdminSender, InternalClientSender, SendCommand};
 
@@ -16,7 +19,7 @@ use crate::server::{AdminCommands, AdminSender, InternalClientSender, SendComman
 ///
 /// It is stuct name + the object id (as in [WithId]).
 /// It is used lookups across tthis project
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ObjectId(pub String, pub String);
 
 impl ObjectId {
@@ -49,6 +52,9 @@ pub trait WithId {
 #[async_trait]
 pub trait ServiceObject: Default + WithId + IdentifiableType {
     /// Send a message to Rio cluster using a client tht is stored in AppData
+    ///
+    /// The request carries the [metadata](crate::protocol::metadata) of the request
+    /// being handled, and what is left of its [deadline](crate::protocol::deadline), if any
     async fn send<T, V, E>(
         app_data: &AppData,
         handler_type_id: impl ToString + Send + Sync,
@@ -61,13 +67,22 @@ pub trait ServiceObject: Default + WithId + IdentifiableType {
         V: Serialize + IdentifiableType + Send + Sync,
     {
         let client = app_data.get::<InternalClientSender>();
-        let payload = bincode::serialize(&payload).map_err(|_| RequestError::SerializationError)?;
-        let request = RequestEnvelope::new(
+        let codec = app_data
+            .try_get::<Arc<dyn Codec>>()
+            .cloned()
+            .unwrap_or_else(default_codec);
+        let payload = codec
+            .serialize(payload)
+            .map_err(|_| RequestError::SerializationError)?;
+        let mut request = RequestEnvelope::new(
             handler_type_id.to_string(),
             handler_id.to_string(),
             V::user_defined_type_id().to_string(),
             payload,
-        );
+        )
+        // Propagates the metadata of the request being handled
+        .with_metadata(metadata::current());
+        request.deadline = deadline::remaining();
         let (request_message, channel) = SendCommand::build(request);
         client
             .send(request_message)
@@ -75,9 +90,11 @@ pub trait ServiceObject: Default + WithId + IdentifiableType {
 
         let resp = channel
             .await
-            .map_err(|e| ClientError::IoError(e.to_string()))??;
+            .map_err(|e| ClientError::IoError(e.to_string()))?
+            .map_err(|err| RequestError::from_response_error(err, codec.as_ref()))?;
 
-        let parsed_body = bincode::deserialize::<T>(&resp)
+        let parsed_body = codec
+            .deserialize::<T>(&resp)
             .map_err(|e| ClientError::DeseralizationError(e.to_string()))?;
         Ok(parsed_body)
     }
@@ -97,22 +114,34 @@ pub trait ServiceObject: Default + WithId + IdentifiableType {
         Ok(())
     }
 
+    /// Runs [ServiceObject::before_shutdown] and deactivates the object, waiting for the
+    /// server holding it to acknowledge it
     async fn shutdown(
         &mut self,
         app_data: Arc<AppData>,
     ) -> Result<(), ServiceObjectLifeCycleError> {
         self.before_shutdown(app_data.clone()).await?;
         let admin_sender = app_data.get::<AdminSender>().clone();
+        let (ack_sender, ack) = oneshot::channel();
         admin_sender
-            .send(AdminCommands::Shutdown(
+            .send(AdminCommands::ShutdownAfterHooks(
                 Self::user_defined_type_id().to_string(),
                 self.id().to_string(),
+                ack_sender,
             ))
             .map_err(|err| {
                 error!("{}", err);
                 ServiceObjectLifeCycleError::Shutdown
             })?;
-        Ok(())
+        ack.await
+            .map_err(|err| {
+                error!("{}", err);
+                ServiceObjectLifeCycleError::Shutdown
+            })?
+            .map_err(|err| {
+                error!("{}", err);
+                ServiceObjectLifeCycleError::Shutdown
+            })
     }
 }
 
@@ -129,6 +158,8 @@ pub trait ServiceObjectStateLoad {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum LifecycleMessage {
     Load,
+    /// Sent when the server deactivates the object, e.g. when it is shutting down
+    Shutdown,
 }
 
 impl Message for LifecycleMessage {}
@@ -159,6 +190,7 @@ where
                 self.after_load(context.clone()).await?;
                 Ok(())
             }
+            LifecycleMessage::Shutdown => self.before_shutdown(context).await,
         }
     }
 }
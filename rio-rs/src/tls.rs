@@ -0,0 +1,225 @@
+//! TLS for the connections between clients and servers
+//!
+//! Servers are configured with [TlsServerConfig] (see `Server::builder().tls(...)`) and
+//! clients with [TlsClientConfig] (see [ClientBuilder::tls](crate::client::ClientBuilder::tls)).
+//!
+//! Both can be built from PEM encoded certificates and keys. Mutual TLS is enabled by
+//! giving the server the CA that signs the client certificates, and giving the client
+//! its own certificate and key.
+//!
+//! It uses [rustls](tokio_rustls::rustls) with the aws-lc-rs crypto provider.
+//!
+//! ```rust,no_run
+//! # use rio_rs::tls::{TlsClientConfig, TlsServerConfig};
+//! let server_tls = TlsServerConfig::from_pem(
+//!     &std::fs::read("server.pem").unwrap(),
+//!     &std::fs::read("server.key").unwrap(),
+//! )
+//! .unwrap();
+//!
+//! let client_tls = TlsClientConfig::from_pem(&std::fs::read("ca.pem").unwrap())
+//!     .unwrap()
+//!     .server_name("rio.internal");
+//! ```
+
+use std::sync::Arc;
+
+use rustls::crypto::{CryptoProvider, aws_lc_rs};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio::net::TcpStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Re-exported, for building the configs with [TlsServerConfig::from_rustls] and
+/// [TlsClientConfig::from_rustls]
+pub use tokio_rustls::rustls;
+
+use crate::errors::TlsError;
+use crate::transport::Stream;
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(aws_lc_rs::default_provider())
+}
+
+fn certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::InvalidPem(e.to_string()))?;
+    if certificates.is_empty() {
+        return Err(TlsError::InvalidPem("no certificates found".to_string()));
+    }
+    Ok(certificates)
+}
+
+fn private_key(pem: &[u8]) -> Result<PrivateKeyDer<'static>, TlsError> {
+    PrivateKeyDer::from_pem_slice(pem).map_err(|e| TlsError::InvalidPem(e.to_string()))
+}
+
+fn root_store(ca_pem: &[u8]) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in certificates(ca_pem)? {
+        roots
+            .add(certificate)
+            .map_err(|e| TlsError::InvalidCertificate(e.to_string()))?;
+    }
+    Ok(roots)
+}
+
+/// TLS settings for the server's listener
+#[derive(Clone, Debug)]
+pub struct TlsServerConfig {
+    config: Arc<ServerConfig>,
+}
+
+impl TlsServerConfig {
+    /// Server identified by `cert_chain`, that doesn't ask for client certificates
+    pub fn from_pem(cert_chain: &[u8], private_key: &[u8]) -> Result<Self, TlsError> {
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TlsError::Config(e.to_string()))?
+            .with_no_client_auth()
+            .with_single_cert(certificates(cert_chain)?, self::private_key(private_key)?)
+            .map_err(|e| TlsError::Config(e.to_string()))?;
+        Ok(Self::from_rustls(config))
+    }
+
+    /// Same as [TlsServerConfig::from_pem], but it only accepts clients presenting a
+    /// certificate signed by `client_ca` (mutual TLS)
+    pub fn from_pem_with_client_auth(
+        cert_chain: &[u8],
+        private_key: &[u8],
+        client_ca: &[u8],
+    ) -> Result<Self, TlsError> {
+        let verifier = WebPkiClientVerifier::builder_with_provider(
+            Arc::new(root_store(client_ca)?),
+            provider(),
+        )
+        .build()
+        .map_err(|e| TlsError::Config(e.to_string()))?;
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TlsError::Config(e.to_string()))?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certificates(cert_chain)?, self::private_key(private_key)?)
+            .map_err(|e| TlsError::Config(e.to_string()))?;
+        Ok(Self::from_rustls(config))
+    }
+
+    /// Uses a rustls config as is
+    pub fn from_rustls(config: ServerConfig) -> Self {
+        TlsServerConfig {
+            config: Arc::new(config),
+        }
+    }
+
+    /// Runs the TLS handshake on a newly accepted connection
+    pub(crate) async fn accept(&self, stream: TcpStream) -> Result<Stream, TlsError> {
+        let stream = TlsAcceptor::from(self.config.clone())
+            .accept(stream)
+            .await
+            .map_err(|e| TlsError::Handshake(e.to_string()))?;
+        Ok(Stream::Tls(Box::new(stream.into())))
+    }
+}
+
+/// TLS settings for the client's connections
+#[derive(Clone, Debug)]
+pub struct TlsClientConfig {
+    config: Arc<ClientConfig>,
+
+    /// Name checked against the server certificates, instead of the server address
+    server_name: Option<String>,
+}
+
+impl TlsClientConfig {
+    /// Client that trusts the servers whose certificates are signed by `ca`
+    pub fn from_pem(ca: &[u8]) -> Result<Self, TlsError> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TlsError::Config(e.to_string()))?
+            .with_root_certificates(root_store(ca)?)
+            .with_no_client_auth();
+        Ok(Self::from_rustls(config))
+    }
+
+    /// Same as [TlsClientConfig::from_pem], but it identifies itself to the servers with
+    /// `cert_chain` (mutual TLS)
+    pub fn from_pem_with_client_auth(
+        ca: &[u8],
+        cert_chain: &[u8],
+        private_key: &[u8],
+    ) -> Result<Self, TlsError> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TlsError::Config(e.to_string()))?
+            .with_root_certificates(root_store(ca)?)
+            .with_client_auth_cert(certificates(cert_chain)?, self::private_key(private_key)?)
+            .map_err(|e| TlsError::Config(e.to_string()))?;
+        Ok(Self::from_rustls(config))
+    }
+
+    /// Uses a rustls config as is
+    pub fn from_rustls(config: ClientConfig) -> Self {
+        TlsClientConfig {
+            config: Arc::new(config),
+            server_name: None,
+        }
+    }
+
+    /// Name to verify the server certificates against
+    ///
+    /// By default, the host of the server address (usually an IP) is used, so the
+    /// certificates need to include it in their subject alternative names
+    pub fn server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = Some(server_name.into());
+        self
+    }
+
+    /// Runs the TLS handshake on a new connection to `address`
+    pub(crate) async fn connect(
+        &self,
+        address: &str,
+        stream: TcpStream,
+    ) -> Result<Stream, TlsError> {
+        let host = match &self.server_name {
+            Some(server_name) => server_name.as_str(),
+            None => host(address),
+        };
+        let server_name =
+            ServerName::try_from(host.to_string()).map_err(|e| TlsError::Config(e.to_string()))?;
+        let stream = TlsConnector::from(self.config.clone())
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| TlsError::Handshake(e.to_string()))?;
+        Ok(Stream::Tls(Box::new(stream.into())))
+    }
+}
+
+/// Host part of a `host:port` address, without the brackets around IPv6 addresses
+fn host(address: &str) -> &str {
+    let host = address
+        .rsplit_once(':')
+        .map(|(host, _port)| host)
+        .unwrap_or(address);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_host() {
+        assert_eq!(host("127.0.0.1:5000"), "127.0.0.1");
+        assert_eq!(host("[::1]:5000"), "::1");
+        assert_eq!(host("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_invalid_pem() {
+        let result = TlsClientConfig::from_pem(b"not a certificate");
+        assert!(matches!(result, Err(TlsError::InvalidPem(_))));
+    }
+}
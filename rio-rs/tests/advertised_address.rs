@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+use rio_rs::prelude::*;
+
+use rio_rs::cluster::storage::local::LocalStorage;
+use rio_rs::object_placement::ObjectPlacement;
+use rio_rs::object_placement::local::LocalObjectPlacement;
+use rio_rs::server::Server;
+
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct Ping {}
+
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct Pong {}
+
+#[derive(Debug, Default, WithId, TypeName)]
+struct MockService {
+    id: String,
+}
+
+#[async_trait]
+impl Handler<Ping> for MockService {
+    type Returns = Pong;
+    type Error = NoopError;
+    async fn handle(&mut self, _: Ping, _: Arc<AppData>) -> Result<Self::Returns, Self::Error> {
+        Ok(Pong {})
+    }
+}
+
+#[tokio::test]
+async fn server_registers_the_advertised_address() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    let mut registry = Registry::new();
+    registry.add_type::<MockService>();
+    registry.add_handler::<MockService, Ping>();
+
+    // The bind address is reachable here, unlike the one picked by default. It is
+    // advertised by its DNS name
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let advertised_address = Address::new("localhost", listener.local_addr().unwrap().port());
+    let mut server = Server::builder()
+        .advertised_address(advertised_address.clone())
+        .registry(registry)
+        .cluster_provider(
+            PeerToPeerClusterProvider::builder()
+                .members_storage(members_storage.clone())
+                .build(),
+        )
+        .object_placement_provider(object_placement_provider.clone())
+        .build();
+    let server_task = tokio::spawn(async move { server.run(listener).await });
+
+    while members_storage.active_members().await.unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let members = members_storage.active_members().await.unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].address(), &advertised_address);
+
+    let mut client = ClientBuilder::new()
+        .members_storage(members_storage.clone())
+        .build()
+        .unwrap();
+    let _: Pong = client
+        .send::<_, NoopError>("MockService", "1", &Ping {})
+        .await
+        .unwrap();
+    let placement = object_placement_provider
+        .lookup(&ObjectId::new("MockService", "1"))
+        .await
+        .unwrap();
+    assert_eq!(placement, Some(advertised_address));
+    server_task.abort();
+}
@@ -3,14 +3,17 @@ use futures::{StreamExt, pin_mut};
 use rio_macros::{Message, TypeName, WithId};
 use rio_rs::app_data::AppDataExt;
 use rio_rs::cluster::storage::local::LocalStorage;
+use rio_rs::codec::json::JsonCodec;
 use rio_rs::message_router::MessageRouter;
 use rio_rs::object_placement::local::LocalObjectPlacement;
 use rio_rs::prelude::*;
 use rio_rs::protocol::NoopError;
 use rio_rs::protocol::pubsub::SubscriptionResponse;
-use rio_rs::registry::IdentifiableType;
+use rio_rs::protocol::streaming::INITIAL_CREDITS;
+use rio_rs::registry::{HandlerStream, IdentifiableType, StreamHandler};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 use thiserror::Error;
 use tokio::time::sleep;
@@ -33,6 +36,24 @@ struct CausePublishMessage {
     text: String,
 }
 
+/// Takes `millis` to be handled
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct SlowMessage {
+    millis: u64,
+}
+
+/// Streams `count` responses back
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct CountMessage {
+    count: u32,
+}
+
+/// Streams responses for as long as the caller reads them, counting them on [PRODUCED]
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct EndlessMessage {}
+
+static PRODUCED: AtomicU32 = AtomicU32::new(0);
+
 #[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
 struct MockResponse {
     text: String,
@@ -85,11 +106,69 @@ impl Handler<CausePublishMessage> for MockService {
     }
 }
 
+#[async_trait]
+impl Handler<SlowMessage> for MockService {
+    type Returns = ();
+    type Error = MockError;
+    async fn handle(
+        &mut self,
+        message: SlowMessage,
+        _: Arc<AppData>,
+    ) -> Result<Self::Returns, Self::Error> {
+        sleep(Duration::from_millis(message.millis)).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StreamHandler<CountMessage> for MockService {
+    type Item = MockResponse;
+    type Error = MockError;
+    async fn handle_stream(
+        &mut self,
+        message: CountMessage,
+        _: Arc<AppData>,
+    ) -> Result<HandlerStream<Self::Item, Self::Error>, Self::Error> {
+        if message.count == 0 {
+            return Err(MockError::VariantB);
+        }
+        let id = self.id.clone();
+        let items = (0..message.count).map(move |i| {
+            Ok(MockResponse {
+                text: format!("{} counted {}", id, i),
+            })
+        });
+        Ok(futures::stream::iter(items).boxed())
+    }
+}
+
+#[async_trait]
+impl StreamHandler<EndlessMessage> for MockService {
+    type Item = MockResponse;
+    type Error = MockError;
+    async fn handle_stream(
+        &mut self,
+        _: EndlessMessage,
+        _: Arc<AppData>,
+    ) -> Result<HandlerStream<Self::Item, Self::Error>, Self::Error> {
+        let items = futures::stream::repeat_with(|| {
+            let i = PRODUCED.fetch_add(1, Ordering::SeqCst);
+            Ok(MockResponse {
+                text: i.to_string(),
+            })
+        });
+        Ok(items.boxed())
+    }
+}
+
 fn build_registry() -> Registry {
     let mut registry = Registry::new();
     registry.add_type::<MockService>();
     registry.add_handler::<MockService, MockMessage>();
     registry.add_handler::<MockService, CausePublishMessage>();
+    registry.add_handler::<MockService, SlowMessage>();
+    registry.add_stream_handler::<MockService, CountMessage>();
+    registry.add_stream_handler::<MockService, EndlessMessage>();
     registry
 }
 
@@ -150,6 +229,121 @@ async fn request_response_error() {
     .await;
 }
 
+#[tokio::test]
+async fn request_response_deadline_exceeded() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .request_timeout(Duration::from_millis(200))
+                .build()
+                .unwrap();
+            let message = SlowMessage { millis: 5_000 };
+            let resp = client
+                .send::<(), MockError>("MockService", "1", &message)
+                .await;
+            assert_eq!(
+                resp,
+                Err(RequestError::ResponseError(ResponseError::DeadlineExceeded))
+            );
+
+            // The server cancelled the slow handler, so the object can take new requests
+            let message = MockMessage {
+                text: "hi".to_string(),
+            };
+            let resp: MockResponse = client
+                .send::<_, MockError>("MockService", "1", &message)
+                .await
+                .unwrap();
+            assert_eq!(&resp.text, "1 received hi");
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn request_response_with_codec() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+    let build_json_registry = || {
+        let mut registry = build_registry();
+        registry.set_codec(Arc::new(JsonCodec));
+        registry
+    };
+    run_integration_test(
+        20,
+        &build_json_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .codec(Arc::new(JsonCodec))
+                .build()
+                .unwrap();
+            let message = MockMessage {
+                text: "hi".to_string(),
+            };
+            let resp: MockResponse = client
+                .send::<_, NoopError>("MockService", "1", &message)
+                .await
+                .unwrap();
+            assert_eq!(&resp.text, "1 received hi");
+
+            let message = MockMessage {
+                text: "ERROR".to_string(),
+            };
+            let resp: Result<MockResponse, RequestError<MockError>> =
+                client.send("MockService", "1", &message).await;
+            assert!(matches!(
+                resp,
+                Err(RequestError::ApplicationError(MockError::VariantA))
+            ));
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn request_response_codec_mismatch() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .codec(Arc::new(JsonCodec))
+                .build()
+                .unwrap();
+            let message = MockMessage {
+                text: "hi".to_string(),
+            };
+            let resp: Result<MockResponse, RequestError<NoopError>> =
+                client.send("MockService", "1", &message).await;
+            assert!(matches!(
+                resp,
+                Err(RequestError::ClientError(
+                    ClientError::IncompatibleProtocol(_)
+                ))
+            ));
+        },
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn request_response_redirectt() {
     let members_storage = LocalStorage::default();
@@ -305,3 +499,209 @@ async fn pubsub_redirect() {
     )
     .await;
 }
+
+#[tokio::test]
+async fn pubsub_many_subscriptions_on_one_connection() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            // All the clones share the same connection to the server
+            let client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .build()
+                .unwrap();
+
+            let mut publishing_client = client.clone();
+            let publishing_task = tokio::spawn(async move {
+                for i in 0.. {
+                    let _: () = publishing_client
+                        .send::<_, MockError>(
+                            "MockService",
+                            "1",
+                            &CausePublishMessage {
+                                text: format!("hey {}", i),
+                            },
+                        )
+                        .await
+                        .unwrap();
+                    sleep(Duration::from_millis(5)).await;
+                }
+            });
+
+            let mut first_client = client.clone();
+            let mut first = Box::pin(
+                first_client
+                    .subscribe::<CausePublishMessage>("MockService", "1")
+                    .await
+                    .unwrap(),
+            );
+            let mut second_client = client.clone();
+            let second = second_client
+                .subscribe::<CausePublishMessage>("MockService", "1")
+                .await
+                .unwrap();
+            pin_mut!(second);
+
+            assert!(first.next().await.unwrap().is_ok());
+            assert!(second.next().await.unwrap().is_ok());
+
+            // Unsubscribing one of them leaves the other, and the connection, untouched
+            drop(first);
+            for _ in 0..5 {
+                assert!(second.next().await.unwrap().is_ok());
+            }
+            publishing_task.abort();
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn request_stream() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .build()
+                .unwrap();
+
+            // Goes past the items the server sends before the client grants it more
+            let count = INITIAL_CREDITS * 3;
+            let stream = client
+                .send_stream::<MockResponse, MockError>("MockService", "1", &CountMessage { count })
+                .await
+                .unwrap();
+            let responses: Vec<_> = stream.map(|item| item.unwrap().text).collect().await;
+            let expected: Vec<_> = (0..count).map(|i| format!("1 counted {}", i)).collect();
+            assert_eq!(responses, expected);
+
+            // Errors creating the stream are the first and only item
+            let stream = client
+                .send_stream::<MockResponse, MockError>(
+                    "MockService",
+                    "1",
+                    &CountMessage { count: 0 },
+                )
+                .await
+                .unwrap();
+            let responses: Vec<_> = stream.map(|item| item.unwrap_err()).collect().await;
+            assert_eq!(
+                responses,
+                vec![RequestError::ApplicationError(MockError::VariantB)]
+            );
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn request_stream_backpressure_and_cancel() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .build()
+                .unwrap();
+
+            let mut stream = client
+                .send_stream::<MockResponse, MockError>("MockService", "1", &EndlessMessage {})
+                .await
+                .unwrap();
+            assert_eq!(stream.next().await.unwrap().unwrap().text, "0");
+
+            // The server waits for the client to read what it has already sent
+            sleep(Duration::from_millis(200)).await;
+            assert!(PRODUCED.load(Ordering::SeqCst) <= INITIAL_CREDITS);
+
+            // Dropping the stream cancels it, without affecting the connection
+            drop(stream);
+            let resp: MockResponse = client
+                .send::<_, MockError>("MockService", "1", &MockMessage { text: "hi".into() })
+                .await
+                .unwrap();
+            assert_eq!(&resp.text, "1 received hi");
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn request_batch() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        3,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage.clone())
+                .build()
+                .unwrap();
+            let messages: Vec<_> = (0..50)
+                .map(|i| (i.to_string(), MockMessage::default()))
+                .collect();
+            client
+                .send_batch::<MockResponse, MockError>("MockService", &messages)
+                .await
+                .unwrap();
+
+            // The objects are spread over the servers, and the new client doesn't know
+            // where they are, so some of its requests are redirected
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .build()
+                .unwrap();
+            let messages: Vec<_> = (0..50)
+                .map(|i| {
+                    let text = if i == 10 { "ERROR" } else { "hi" };
+                    (
+                        i.to_string(),
+                        MockMessage {
+                            text: text.to_string(),
+                        },
+                    )
+                })
+                .collect();
+            let results = client
+                .send_batch::<MockResponse, MockError>("MockService", &messages)
+                .await
+                .unwrap();
+            assert_eq!(results.len(), 50);
+            for (i, result) in results.into_iter().enumerate() {
+                if i == 10 {
+                    assert_eq!(
+                        result.unwrap_err(),
+                        RequestError::ApplicationError(MockError::VariantA)
+                    );
+                } else {
+                    assert_eq!(result.unwrap().text, format!("{} received hi", i));
+                }
+            }
+        },
+    )
+    .await;
+}
@@ -3,7 +3,7 @@ mod db_utils;
 
 use rand::prelude::*;
 
-use rio_rs::{cluster::storage::Member, prelude::MembershipStorage};
+use rio_rs::{address::Address, cluster::storage::Member, prelude::MembershipStorage};
 
 async fn members_sanity_check<T: MembershipStorage>(storage: T) {
     storage.prepare().await;
@@ -11,32 +11,48 @@ async fn members_sanity_check<T: MembershipStorage>(storage: T) {
     let members = storage.members().await.unwrap();
     assert_eq!(members.len(), 0);
 
-    storage
-        .push(Member::new("0.0.0.0".to_string(), "9090".to_string()))
-        .await
-        .unwrap();
+    let address = Address::new("0.0.0.0", 9090);
+    storage.push(Member::new(address.clone())).await.unwrap();
 
     let members = storage.members().await.unwrap();
     assert_eq!(members.len(), 1);
 
-    storage.set_active("0.0.0.0", "9090").await.unwrap();
+    storage.set_active(&address).await.unwrap();
     let members = storage.active_members().await.unwrap();
     assert_eq!(members.len(), 1);
 
-    storage.set_inactive("0.0.0.0", "9090").await.unwrap();
+    storage.set_is_draining(&address, true).await.unwrap();
+    let members = storage.active_members().await.unwrap();
+    assert!(members[0].draining());
+    storage.set_is_draining(&address, false).await.unwrap();
+    let members = storage.active_members().await.unwrap();
+    assert!(!members[0].draining());
+
+    storage.set_inactive(&address).await.unwrap();
     let members = storage.active_members().await.unwrap();
     assert_eq!(members.len(), 0);
+
+    // IPv6 and DNS names are stored as they are
+    for address in [Address::new("::1", 9090), Address::new("rio-0.rio", 9090)] {
+        storage.push(Member::new(address.clone())).await.unwrap();
+        storage.set_active(&address).await.unwrap();
+        let members = storage.active_members().await.unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].address(), &address);
+        storage.set_inactive(&address).await.unwrap();
+    }
 }
 
 async fn failures_sanity_check<T: MembershipStorage>(storage: T) {
     storage.prepare().await;
 
-    let failures = storage.member_failures("0.0.0.0", "9090").await.unwrap();
+    let address = Address::new("0.0.0.0", 9090);
+    let failures = storage.member_failures(&address).await.unwrap();
     assert_eq!(failures.len(), 0);
 
-    storage.notify_failure("0.0.0.0", "9090").await.unwrap();
+    storage.notify_failure(&address).await.unwrap();
 
-    let failures = storage.member_failures("0.0.0.0", "9090").await.unwrap();
+    let failures = storage.member_failures(&address).await.unwrap();
     assert_eq!(failures.len(), 1);
 }
 
@@ -20,6 +20,12 @@ struct OkMessage {}
 #[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
 struct KillServer {}
 
+/// Shuts down another object, wherever it lives
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct ShutdownObject {
+    id: String,
+}
+
 #[derive(Default, Debug, PartialEq, Message, TypeName, Serialize, Deserialize)]
 struct MockResponse {}
 
@@ -61,11 +67,34 @@ impl Handler<KillServer> for MockService {
     }
 }
 
+#[async_trait]
+impl Handler<ShutdownObject> for MockService {
+    type Returns = MockResponse;
+    type Error = ();
+    async fn handle(
+        &mut self,
+        message: ShutdownObject,
+        ctx: Arc<AppData>,
+    ) -> Result<Self::Returns, Self::Error> {
+        let (ack_sender, ack) = tokio::sync::oneshot::channel();
+        ctx.get::<AdminSender>()
+            .send(rio_rs::server::AdminCommands::ShutdownWithAck(
+                "MockService".to_string(),
+                message.id,
+                ack_sender,
+            ))
+            .unwrap();
+        ack.await.unwrap().map_err(|_| ())?;
+        Ok(MockResponse {})
+    }
+}
+
 fn build_registry() -> Registry {
     let mut registry = Registry::new();
     registry.add_type::<MockService>();
     registry.add_handler::<MockService, OkMessage>();
     registry.add_handler::<MockService, KillServer>();
+    registry.add_handler::<MockService, ShutdownObject>();
     registry
 }
 
@@ -170,3 +199,116 @@ async fn error_when_server_doesnt_have_service_in_registry() {
     )
     .await;
 }
+
+#[tokio::test]
+/// Objects can shut down objects that live on another server
+async fn shutdown_object_on_another_server() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        2,
+        || async move {
+            wait_for_active_members(&members_storage, 2, Duration::from_secs(5)).await;
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage.clone())
+                .build()
+                .unwrap();
+
+            client
+                .send::<MockResponse, NoopError>("MockService", "1", &OkMessage {})
+                .await
+                .unwrap();
+            let first_server = object_placement_provider
+                .lookup(&ObjectId::new("MockService", "1"))
+                .await
+                .unwrap();
+
+            // Finds an object that lives on the other server
+            let mut caller = 0;
+            loop {
+                caller += 1;
+                let caller_id = format!("caller-{}", caller);
+                client
+                    .send::<MockResponse, NoopError>("MockService", &caller_id, &OkMessage {})
+                    .await
+                    .unwrap();
+                let caller_server = object_placement_provider
+                    .lookup(&ObjectId::new("MockService", caller_id))
+                    .await
+                    .unwrap();
+                if caller_server != first_server {
+                    break;
+                }
+            }
+
+            let message = ShutdownObject {
+                id: "1".to_string(),
+            };
+            client
+                .send::<MockResponse, NoopError>(
+                    "MockService",
+                    format!("caller-{}", caller),
+                    &message,
+                )
+                .await
+                .unwrap();
+            assert!(!is_allocated(&object_placement_provider, "MockService", "1").await);
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+/// Clients can deactivate objects, wherever they live
+async fn client_deactivate() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        2,
+        || async move {
+            wait_for_active_members(&members_storage, 2, Duration::from_secs(5)).await;
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage.clone())
+                .build()
+                .unwrap();
+
+            for id in ["1", "2", "3"] {
+                client
+                    .send::<MockResponse, NoopError>("MockService", id, &OkMessage {})
+                    .await
+                    .unwrap();
+            }
+
+            // This client doesn't know where the objects are, so it is redirected to them
+            let mut other_client = ClientBuilder::new()
+                .members_storage(members_storage.clone())
+                .build()
+                .unwrap();
+            for id in ["1", "2", "3"] {
+                other_client.deactivate("MockService", id).await.unwrap();
+                assert!(!is_allocated(&object_placement_provider, "MockService", id).await);
+            }
+
+            // There is nothing left to deactivate
+            other_client.deactivate("MockService", "1").await.unwrap();
+
+            // The objects are activated again on the next message
+            client
+                .send::<MockResponse, NoopError>("MockService", "1", &OkMessage {})
+                .await
+                .unwrap();
+            assert!(is_allocated(&object_placement_provider, "MockService", "1").await);
+        },
+    )
+    .await;
+}
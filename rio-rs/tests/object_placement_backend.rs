@@ -2,6 +2,7 @@ use rand::prelude::*;
 
 use rio_rs::{
     ObjectId,
+    address::Address,
     object_placement::{ObjectPlacement, ObjectPlacementItem},
 };
 
@@ -19,14 +20,34 @@ async fn save_and_load<S: ObjectPlacement>(provider: S) {
     provider.prepare().await.unwrap();
 
     let obj_id = ObjectId::new("obj", "1");
-    let placement = ObjectPlacementItem::new(obj_id, Some("0.0.0.0:8888".to_string()));
+    let placement = ObjectPlacementItem::new(obj_id, Some(Address::new("0.0.0.0", 8888)));
     provider.update(placement).await.unwrap();
 
     let server_addr = provider.lookup(&ObjectId::new("obj", "1")).await.unwrap();
-    assert_eq!(server_addr.as_ref().unwrap(), "0.0.0.0:8888");
+    assert_eq!(server_addr, Some(Address::new("0.0.0.0", 8888)));
+
+    // IPv6 and DNS names are stored as they are
+    for address in [Address::new("::1", 8888), Address::new("rio-0.rio", 8888)] {
+        let placement = ObjectPlacementItem::new(ObjectId::new("obj", "2"), Some(address.clone()));
+        provider.update(placement).await.unwrap();
+        let server_addr = provider.lookup(&ObjectId::new("obj", "2")).await.unwrap();
+        assert_eq!(server_addr, Some(address));
+    }
+
+    // The object moved, so it is only listed on the last server
+    let objects = provider
+        .server_objects(&Address::new("::1", 8888))
+        .await
+        .unwrap();
+    assert!(objects.is_empty());
+    let objects = provider
+        .server_objects(&Address::new("rio-0.rio", 8888))
+        .await
+        .unwrap();
+    assert_eq!(objects, vec![ObjectId::new("obj", "2")]);
 
     provider
-        .clean_server("0.0.0.0:8888".to_string())
+        .clean_server(Address::new("0.0.0.0", 8888))
         .await
         .unwrap();
     let server_addr = provider.lookup(&ObjectId::new("obj", "1")).await.unwrap();
@@ -7,6 +7,7 @@ use rio_rs::prelude::*;
 
 use rio_rs::cluster::storage::local::LocalStorage;
 use rio_rs::object_placement::local::LocalObjectPlacement;
+use rio_rs::protocol::metadata::{self, Metadata};
 use rio_rs::state::local::LocalState;
 
 mod server_utils;
@@ -34,6 +35,7 @@ struct MockMessage {
 #[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
 struct MockResponse {
     text: String,
+    tenant: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Error, Clone)]
@@ -61,6 +63,7 @@ impl Handler<MockMessage> for MockService {
         } else {
             MockResponse {
                 text: format!("{} received {}", self.id, message.text),
+                tenant: metadata::get("tenant"),
             }
         };
         Ok(resp)
@@ -107,3 +110,35 @@ async fn request_response_with_proxy() {
     )
     .await;
 }
+
+#[tokio::test]
+async fn request_response_with_proxy_propagates_metadata() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    run_integration_test(
+        20,
+        &build_registry,
+        members_storage.clone(),
+        object_placement_provider.clone(),
+        1,
+        || async move {
+            let mut client = ClientBuilder::new()
+                .members_storage(members_storage)
+                .build()
+                .unwrap();
+            let message = MockMessage {
+                text: "hi".to_string(),
+                send_to: Some("2000".to_string()),
+            };
+            let request_metadata = Metadata::from([("tenant".to_string(), "acme".to_string())]);
+            let resp: MockResponse = client
+                .send_with_metadata::<_, MockError>("MockService", "1", &message, request_metadata)
+                .await
+                .unwrap();
+            assert_eq!(&resp.text, "2000 received hi");
+            assert_eq!(resp.tenant, Some("acme".to_string()));
+        },
+    )
+    .await;
+}
@@ -1,4 +1,5 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -7,6 +8,7 @@ use rio_rs::prelude::*;
 
 use rio_rs::cluster::storage::local::LocalStorage;
 use rio_rs::object_placement::local::LocalObjectPlacement;
+use rio_rs::server::Server;
 use rio_rs::state::local::LocalState;
 
 mod server_utils;
@@ -129,3 +131,167 @@ async fn service_is_not_allocated_on_lifecycle_handlers_error() {
     )
     .await;
 }
+
+/// Objects that ran their shutdown hooks
+static SHUT_DOWN: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct SlowMessage {}
+
+#[derive(Debug, Default, WithId, TypeName)]
+struct DrainService {
+    id: String,
+}
+
+impl ServiceObjectStateLoad for DrainService {}
+
+#[async_trait]
+impl ServiceObject for DrainService {
+    async fn before_shutdown(
+        &mut self,
+        _: Arc<AppData>,
+    ) -> Result<(), ServiceObjectLifeCycleError> {
+        SHUT_DOWN.lock().unwrap().push(self.id.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<OkMessage> for DrainService {
+    type Returns = MockResponse;
+    type Error = MockError;
+    async fn handle(
+        &mut self,
+        _: OkMessage,
+        _: Arc<AppData>,
+    ) -> Result<Self::Returns, Self::Error> {
+        Ok(MockResponse {})
+    }
+}
+
+#[async_trait]
+impl Handler<SlowMessage> for DrainService {
+    type Returns = MockResponse;
+    type Error = MockError;
+    async fn handle(
+        &mut self,
+        _: SlowMessage,
+        _: Arc<AppData>,
+    ) -> Result<Self::Returns, Self::Error> {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        Ok(MockResponse {})
+    }
+}
+
+#[tokio::test]
+async fn graceful_shutdown_drains_and_deactivates() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    let mut registry = Registry::new();
+    registry.add_type::<DrainService>();
+    registry.add_handler::<DrainService, LifecycleMessage>();
+    registry.add_handler::<DrainService, SlowMessage>();
+
+    let mut server = Server::builder()
+        .registry(registry)
+        .cluster_provider(
+            PeerToPeerClusterProvider::builder()
+                .members_storage(members_storage.clone())
+                .build(),
+        )
+        .object_placement_provider(object_placement_provider.clone())
+        .drain_timeout(Duration::from_secs(5))
+        .build();
+    server.app_data(LocalState::default());
+    let shutdown = server.shutdown_handle();
+    let listener = server.bind().await.unwrap();
+    let server_task = tokio::spawn(async move { server.run(listener).await });
+
+    while members_storage.active_members().await.unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let mut client = ClientBuilder::new()
+        .members_storage(members_storage.clone())
+        .build()
+        .unwrap();
+    let _: MockResponse = client
+        .send::<_, MockError>("DrainService", "a", &SlowMessage {})
+        .await
+        .unwrap();
+    assert!(is_allocated(&object_placement_provider, "DrainService", "a").await);
+
+    // Shuts down while a request is being handled
+    let mut inner_client = client.clone();
+    let in_flight = tokio::spawn(async move {
+        inner_client
+            .send::<MockResponse, MockError>("DrainService", "a", &SlowMessage {})
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    shutdown.shutdown();
+
+    assert_eq!(in_flight.await.unwrap().unwrap(), MockResponse {});
+    server_task.await.unwrap().unwrap();
+    assert!(SHUT_DOWN.lock().unwrap().contains(&"a".to_string()));
+    assert!(!is_allocated(&object_placement_provider, "DrainService", "a").await);
+    assert!(members_storage.active_members().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn idle_objects_are_deactivated() {
+    let members_storage = LocalStorage::default();
+    let object_placement_provider = LocalObjectPlacement::default();
+
+    let mut registry = Registry::new();
+    registry.add_type::<DrainService>();
+    registry.add_handler::<DrainService, LifecycleMessage>();
+    registry.add_handler::<DrainService, OkMessage>();
+
+    let mut server = Server::builder()
+        .registry(registry)
+        .cluster_provider(
+            PeerToPeerClusterProvider::builder()
+                .members_storage(members_storage.clone())
+                .build(),
+        )
+        .object_placement_provider(object_placement_provider.clone())
+        .idle_timeout(Duration::from_millis(200))
+        .collection_interval(Duration::from_millis(50))
+        .build();
+    server.app_data(LocalState::default());
+    let listener = server.bind().await.unwrap();
+    let server_task = tokio::spawn(async move { server.run(listener).await });
+
+    while members_storage.active_members().await.unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let mut client = ClientBuilder::new()
+        .members_storage(members_storage.clone())
+        .build()
+        .unwrap();
+    let _: MockResponse = client
+        .send::<_, MockError>("DrainService", "idle", &OkMessage {})
+        .await
+        .unwrap();
+
+    // Still active while it gets messages
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(is_allocated(&object_placement_provider, "DrainService", "idle").await);
+        let _: MockResponse = client
+            .send::<_, MockError>("DrainService", "idle", &OkMessage {})
+            .await
+            .unwrap();
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while is_allocated(&object_placement_provider, "DrainService", "idle").await {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("The idle object should be deactivated");
+    assert!(SHUT_DOWN.lock().unwrap().contains(&"idle".to_string()));
+    server_task.abort();
+}
@@ -0,0 +1,192 @@
+#![cfg(feature = "tls")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use rio_macros::{Message, TypeName, WithId};
+use rio_rs::cluster::storage::local::LocalStorage;
+use rio_rs::object_placement::local::LocalObjectPlacement;
+use rio_rs::prelude::*;
+use rio_rs::protocol::NoopError;
+use rio_rs::tls::{TlsClientConfig, TlsServerConfig};
+use serde::{Deserialize, Serialize};
+
+mod server_utils;
+use server_utils::wait_for_active_members;
+
+#[derive(Default, WithId, TypeName)]
+struct MockService {
+    id: String,
+}
+
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct MockMessage {
+    text: String,
+}
+
+#[derive(Default, Debug, Message, TypeName, Serialize, Deserialize)]
+struct MockResponse {
+    text: String,
+}
+
+#[async_trait]
+impl Handler<MockMessage> for MockService {
+    type Returns = MockResponse;
+    type Error = NoopError;
+    async fn handle(
+        &mut self,
+        message: MockMessage,
+        _: Arc<AppData>,
+    ) -> Result<Self::Returns, Self::Error> {
+        Ok(MockResponse {
+            text: format!("{} received {}", self.id, message.text),
+        })
+    }
+}
+
+/// Certificate authority, and a server and a client certificate signed by it (PEM encoded)
+struct Pki {
+    ca: String,
+    server_cert: String,
+    server_key: String,
+    client_cert: String,
+    client_key: String,
+}
+
+fn signed(
+    names: Vec<String>,
+    usage: ExtendedKeyUsagePurpose,
+    ca: &Certificate,
+    ca_key: &KeyPair,
+) -> (String, String) {
+    let key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(names).unwrap();
+    params.extended_key_usages = vec![usage];
+    let cert = params.signed_by(&key, ca, ca_key).unwrap();
+    (cert.pem(), key.serialize_pem())
+}
+
+fn generate_pki() -> Pki {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(vec![]).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let (server_cert, server_key) = signed(
+        vec!["localhost".to_string()],
+        ExtendedKeyUsagePurpose::ServerAuth,
+        &ca,
+        &ca_key,
+    );
+    let (client_cert, client_key) = signed(
+        vec!["client".to_string()],
+        ExtendedKeyUsagePurpose::ClientAuth,
+        &ca,
+        &ca_key,
+    );
+    Pki {
+        ca: ca.pem(),
+        server_cert,
+        server_key,
+        client_cert,
+        client_key,
+    }
+}
+
+/// Starts a server that only accepts TLS connections, and waits for it to join the cluster
+async fn run_tls_server(members_storage: LocalStorage, tls: TlsServerConfig) {
+    let mut registry = Registry::new();
+    registry.add_type::<MockService>();
+    registry.add_handler::<MockService, MockMessage>();
+
+    let cluster_provider = PeerToPeerClusterProvider::builder()
+        .members_storage(members_storage.clone())
+        .build();
+    let mut server = Server::builder()
+        .address("0.0.0.0:0".to_string())
+        .registry(registry)
+        .cluster_provider(cluster_provider)
+        .object_placement_provider(LocalObjectPlacement::default())
+        .tls(tls)
+        .build();
+    let listener = server.bind().await.unwrap();
+    tokio::spawn(async move { server.run(listener).await });
+    wait_for_active_members(&members_storage, 1, Duration::from_secs(5)).await;
+}
+
+async fn send(client_tls: Option<TlsClientConfig>, members_storage: LocalStorage) -> bool {
+    let mut builder = ClientBuilder::new().members_storage(members_storage);
+    if let Some(tls) = client_tls {
+        builder = builder.tls(tls);
+    }
+    let mut client = builder.build().unwrap();
+    let message = MockMessage {
+        text: "hi".to_string(),
+    };
+    let response: Result<MockResponse, _> = client
+        .send::<_, NoopError>("MockService", "1", &message)
+        .await;
+    match response {
+        Ok(response) => {
+            assert_eq!(response.text, "1 received hi");
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[tokio::test]
+async fn request_response_over_tls() {
+    let pki = generate_pki();
+    let members_storage = LocalStorage::default();
+    let server_tls =
+        TlsServerConfig::from_pem(pki.server_cert.as_bytes(), pki.server_key.as_bytes()).unwrap();
+    run_tls_server(members_storage.clone(), server_tls).await;
+
+    let client_tls = TlsClientConfig::from_pem(pki.ca.as_bytes())
+        .unwrap()
+        .server_name("localhost");
+    assert!(send(Some(client_tls), members_storage.clone()).await);
+
+    // Plain text clients can't talk to the server
+    assert!(!send(None, members_storage.clone()).await);
+
+    // Neither can clients that don't trust its certificate
+    let other_ca = generate_pki();
+    let client_tls = TlsClientConfig::from_pem(other_ca.ca.as_bytes())
+        .unwrap()
+        .server_name("localhost");
+    assert!(!send(Some(client_tls), members_storage).await);
+}
+
+#[tokio::test]
+async fn request_response_over_mutual_tls() {
+    let pki = generate_pki();
+    let members_storage = LocalStorage::default();
+    let server_tls = TlsServerConfig::from_pem_with_client_auth(
+        pki.server_cert.as_bytes(),
+        pki.server_key.as_bytes(),
+        pki.ca.as_bytes(),
+    )
+    .unwrap();
+    run_tls_server(members_storage.clone(), server_tls).await;
+
+    let client_tls = TlsClientConfig::from_pem_with_client_auth(
+        pki.ca.as_bytes(),
+        pki.client_cert.as_bytes(),
+        pki.client_key.as_bytes(),
+    )
+    .unwrap()
+    .server_name("localhost");
+    assert!(send(Some(client_tls), members_storage.clone()).await);
+
+    // Clients without a certificate are rejected
+    let client_tls = TlsClientConfig::from_pem(pki.ca.as_bytes())
+        .unwrap()
+        .server_name("localhost");
+    assert!(!send(Some(client_tls), members_storage).await);
+}